@@ -1,3 +0,0 @@
-@echo off
-:: PATH shim for mcp-run - delegates to ~/.mcp/mcp-run.cmd
-"%USERPROFILE%\.mcp\mcp-run.cmd" %*
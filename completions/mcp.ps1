@@ -22,39 +22,91 @@ Register-ArgumentCompleter -Native -CommandName 'mcp' -ScriptBlock {
     $completions = @(switch ($command) {
         'mcp' {
             [CompletionResult]::new('--mcp-dir', '--mcp-dir', [CompletionResultType]::ParameterName, 'Path to MCP directory (default: ~/.mcp)')
+            [CompletionResult]::new('--profile', '--profile', [CompletionResultType]::ParameterName, 'Profile to use instead of the active one')
+            [CompletionResult]::new('--project-dir', '--project-dir', [CompletionResultType]::ParameterName, 'Project whose .mcp/servers.json is merged in (default: found by walking up from the current directory)')
             [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('-V', '-V ', [CompletionResultType]::ParameterName, 'Print version')
             [CompletionResult]::new('--version', '--version', [CompletionResultType]::ParameterName, 'Print version')
             [CompletionResult]::new('list', 'list', [CompletionResultType]::ParameterValue, 'List all configured MCP servers')
             [CompletionResult]::new('run', 'run', [CompletionResultType]::ParameterValue, 'Run an MCP server')
-            [CompletionResult]::new('gen', 'gen', [CompletionResultType]::ParameterValue, 'Generate Cursor and OpenCode configuration files')
+            [CompletionResult]::new('serve', 'serve', [CompletionResultType]::ParameterValue, 'Serve a stdio server over streamable HTTP and SSE')
+            [CompletionResult]::new('replay', 'replay', [CompletionResultType]::ParameterValue, 'Act as a fake server answering from a recording')
+            [CompletionResult]::new('gen', 'gen', [CompletionResultType]::ParameterValue, 'Generate MCP client configuration files')
             [CompletionResult]::new('add', 'add', [CompletionResultType]::ParameterValue, 'Add a new MCP server')
-            [CompletionResult]::new('remove', 'remove', [CompletionResultType]::ParameterValue, 'Remove an MCP server')
-            [CompletionResult]::new('enable', 'enable', [CompletionResultType]::ParameterValue, 'Enable an MCP server')
-            [CompletionResult]::new('disable', 'disable', [CompletionResultType]::ParameterValue, 'Disable an MCP server')
+            [CompletionResult]::new('edit', 'edit', [CompletionResultType]::ParameterValue, 'Change an existing server')
+            [CompletionResult]::new('import', 'import', [CompletionResultType]::ParameterValue, 'Import servers from an existing client config')
+            [CompletionResult]::new('install', 'install', [CompletionResultType]::ParameterValue, 'Install the packages of runtime-based servers')
+            [CompletionResult]::new('update', 'update', [CompletionResultType]::ParameterValue, 'Update installed server packages to their latest version')
+            [CompletionResult]::new('remove', 'remove', [CompletionResultType]::ParameterValue, 'Remove MCP servers')
+            [CompletionResult]::new('enable', 'enable', [CompletionResultType]::ParameterValue, 'Enable MCP servers')
+            [CompletionResult]::new('disable', 'disable', [CompletionResultType]::ParameterValue, 'Disable MCP servers')
+            [CompletionResult]::new('validate', 'validate', [CompletionResultType]::ParameterValue, 'Check servers.json (and the project overlay) for mistakes')
+            [CompletionResult]::new('schema', 'schema', [CompletionResultType]::ParameterValue, 'Print the JSON Schema for servers.json')
+            [CompletionResult]::new('migrate', 'migrate', [CompletionResultType]::ParameterValue, 'Rewrite servers.json (and the project overlay) in the current format')
             [CompletionResult]::new('show', 'show', [CompletionResultType]::ParameterValue, 'Show server details')
             [CompletionResult]::new('env', 'env', [CompletionResultType]::ParameterValue, 'Manage environment variables')
+            [CompletionResult]::new('logs', 'logs', [CompletionResultType]::ParameterValue, 'Show a server''s log')
+            [CompletionResult]::new('profile', 'profile', [CompletionResultType]::ParameterValue, 'Switch between named sets of servers')
+            [CompletionResult]::new('daemon', 'daemon', [CompletionResultType]::ParameterValue, 'Keep shared server instances running in the background')
             [CompletionResult]::new('info', 'info', [CompletionResultType]::ParameterValue, 'Show paths and configuration')
             [CompletionResult]::new('completions', 'completions', [CompletionResultType]::ParameterValue, 'Generate shell completions')
             [CompletionResult]::new('help', 'help', [CompletionResultType]::ParameterValue, 'Print this message or the help of the given subcommand(s)')
             break
         }
         'mcp;list' {
+            [CompletionResult]::new('--tag', '--tag', [CompletionResultType]::ParameterName, 'Only servers with this tag (repeatable)')
+            [CompletionResult]::new('--runtime', '--runtime', [CompletionResultType]::ParameterName, 'Only servers on this runtime (node, bun, ...)')
+            [CompletionResult]::new('--type', '--type', [CompletionResultType]::ParameterName, 'Only servers of this type')
+            [CompletionResult]::new('-s', '-s', [CompletionResultType]::ParameterName, 'Only servers whose name, description or tags contain this text')
+            [CompletionResult]::new('--search', '--search', [CompletionResultType]::ParameterName, 'Only servers whose name, description or tags contain this text')
+            [CompletionResult]::new('--sort', '--sort', [CompletionResultType]::ParameterName, 'Order of the rows')
+            [CompletionResult]::new('--columns', '--columns', [CompletionResultType]::ParameterName, 'Columns to show (comma-separated)')
             [CompletionResult]::new('--mcp-dir', '--mcp-dir', [CompletionResultType]::ParameterName, 'Path to MCP directory (default: ~/.mcp)')
+            [CompletionResult]::new('--profile', '--profile', [CompletionResultType]::ParameterName, 'Profile to use instead of the active one')
+            [CompletionResult]::new('--project-dir', '--project-dir', [CompletionResultType]::ParameterName, 'Project whose .mcp/servers.json is merged in (default: found by walking up from the current directory)')
             [CompletionResult]::new('-e', '-e', [CompletionResultType]::ParameterName, 'Show only enabled servers')
             [CompletionResult]::new('--enabled', '--enabled', [CompletionResultType]::ParameterName, 'Show only enabled servers')
             [CompletionResult]::new('-d', '-d', [CompletionResultType]::ParameterName, 'Show only disabled servers')
             [CompletionResult]::new('--disabled', '--disabled', [CompletionResultType]::ParameterName, 'Show only disabled servers')
+            [CompletionResult]::new('-w', '-w', [CompletionResultType]::ParameterName, 'Show descriptions in full')
+            [CompletionResult]::new('--wide', '--wide', [CompletionResultType]::ParameterName, 'Show descriptions in full')
             [CompletionResult]::new('--json', '--json', [CompletionResultType]::ParameterName, 'Output as JSON')
+            [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help (see more with ''--help'')')
+            [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help (see more with ''--help'')')
+            [CompletionResult]::new('-V', '-V ', [CompletionResultType]::ParameterName, 'Print version')
+            [CompletionResult]::new('--version', '--version', [CompletionResultType]::ParameterName, 'Print version')
+            break
+        }
+        'mcp;run' {
+            [CompletionResult]::new('--record', '--record', [CompletionResultType]::ParameterName, 'Record JSON-RPC traffic to a JSONL file')
+            [CompletionResult]::new('--mcp-dir', '--mcp-dir', [CompletionResultType]::ParameterName, 'Path to MCP directory (default: ~/.mcp)')
+            [CompletionResult]::new('--profile', '--profile', [CompletionResultType]::ParameterName, 'Profile to use instead of the active one')
+            [CompletionResult]::new('--project-dir', '--project-dir', [CompletionResultType]::ParameterName, 'Project whose .mcp/servers.json is merged in (default: found by walking up from the current directory)')
+            [CompletionResult]::new('--no-daemon', '--no-daemon', [CompletionResultType]::ParameterName, 'Start a private process even if the daemon runs this server')
             [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('-V', '-V ', [CompletionResultType]::ParameterName, 'Print version')
             [CompletionResult]::new('--version', '--version', [CompletionResultType]::ParameterName, 'Print version')
             break
         }
-        'mcp;run' {
+        'mcp;serve' {
+            [CompletionResult]::new('--http', '--http', [CompletionResultType]::ParameterName, 'Address to listen on, e.g. 127.0.0.1:8808')
+            [CompletionResult]::new('--token', '--token', [CompletionResultType]::ParameterName, 'Require `Authorization: Bearer <TOKEN>`')
+            [CompletionResult]::new('--token-env', '--token-env', [CompletionResultType]::ParameterName, 'Read the bearer token from this .env variable')
             [CompletionResult]::new('--mcp-dir', '--mcp-dir', [CompletionResultType]::ParameterName, 'Path to MCP directory (default: ~/.mcp)')
+            [CompletionResult]::new('--profile', '--profile', [CompletionResultType]::ParameterName, 'Profile to use instead of the active one')
+            [CompletionResult]::new('--project-dir', '--project-dir', [CompletionResultType]::ParameterName, 'Project whose .mcp/servers.json is merged in (default: found by walking up from the current directory)')
+            [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('-V', '-V ', [CompletionResultType]::ParameterName, 'Print version')
+            [CompletionResult]::new('--version', '--version', [CompletionResultType]::ParameterName, 'Print version')
+            break
+        }
+        'mcp;replay' {
+            [CompletionResult]::new('--mcp-dir', '--mcp-dir', [CompletionResultType]::ParameterName, 'Path to MCP directory (default: ~/.mcp)')
+            [CompletionResult]::new('--profile', '--profile', [CompletionResultType]::ParameterName, 'Profile to use instead of the active one')
+            [CompletionResult]::new('--project-dir', '--project-dir', [CompletionResultType]::ParameterName, 'Project whose .mcp/servers.json is merged in (default: found by walking up from the current directory)')
             [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('-V', '-V ', [CompletionResultType]::ParameterName, 'Print version')
@@ -62,31 +114,114 @@ Register-ArgumentCompleter -Native -CommandName 'mcp' -ScriptBlock {
             break
         }
         'mcp;gen' {
+            [CompletionResult]::new('-t', '-t', [CompletionResultType]::ParameterName, 'Client to generate for (repeatable; default: `targets` in servers.json)')
+            [CompletionResult]::new('--target', '--target', [CompletionResultType]::ParameterName, 'Client to generate for (repeatable; default: `targets` in servers.json)')
+            [CompletionResult]::new('--env', '--env', [CompletionResultType]::ParameterName, 'Write each server''s declared env vars into the client config')
             [CompletionResult]::new('--mcp-dir', '--mcp-dir', [CompletionResultType]::ParameterName, 'Path to MCP directory (default: ~/.mcp)')
-            [CompletionResult]::new('--cursor', '--cursor', [CompletionResultType]::ParameterName, 'Only generate Cursor config')
-            [CompletionResult]::new('--opencode', '--opencode', [CompletionResultType]::ParameterName, 'Only generate OpenCode config')
+            [CompletionResult]::new('--profile', '--profile', [CompletionResultType]::ParameterName, 'Profile to use instead of the active one')
+            [CompletionResult]::new('--project-dir', '--project-dir', [CompletionResultType]::ParameterName, 'Project whose .mcp/servers.json is merged in (default: found by walking up from the current directory)')
+            [CompletionResult]::new('--cursor', '--cursor', [CompletionResultType]::ParameterName, 'Only generate Cursor config (same as --target cursor)')
+            [CompletionResult]::new('--opencode', '--opencode', [CompletionResultType]::ParameterName, 'Only generate OpenCode config (same as --target opencode)')
             [CompletionResult]::new('-d', '-d', [CompletionResultType]::ParameterName, 'Dry run - print configs without writing')
             [CompletionResult]::new('--dry-run', '--dry-run', [CompletionResultType]::ParameterName, 'Dry run - print configs without writing')
-            [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
-            [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('-y', '-y', [CompletionResultType]::ParameterName, 'Write without asking for confirmation')
+            [CompletionResult]::new('--yes', '--yes', [CompletionResultType]::ParameterName, 'Write without asking for confirmation')
+            [CompletionResult]::new('--check', '--check', [CompletionResultType]::ParameterName, 'Compare with the files on disk and exit non-zero if any are stale')
+            [CompletionResult]::new('--project', '--project', [CompletionResultType]::ParameterName, 'Write the project''s client files next to its .mcp/servers.json')
+            [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help (see more with ''--help'')')
+            [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help (see more with ''--help'')')
             [CompletionResult]::new('-V', '-V ', [CompletionResultType]::ParameterName, 'Print version')
             [CompletionResult]::new('--version', '--version', [CompletionResultType]::ParameterName, 'Print version')
             break
         }
         'mcp;add' {
-            [CompletionResult]::new('-r', '-r', [CompletionResultType]::ParameterName, 'Runtime (node, bun, deno, npx, bunx)')
-            [CompletionResult]::new('--runtime', '--runtime', [CompletionResultType]::ParameterName, 'Runtime (node, bun, deno, npx, bunx)')
+            [CompletionResult]::new('--type', '--type', [CompletionResultType]::ParameterName, 'Server type (default: implied by --url, --command or --image, else runtime)')
+            [CompletionResult]::new('-r', '-r', [CompletionResultType]::ParameterName, 'Runtime (node, bun, deno, npx, bunx, or one from servers.json `runtimes`)')
+            [CompletionResult]::new('--runtime', '--runtime', [CompletionResultType]::ParameterName, 'Runtime (node, bun, deno, npx, bunx, or one from servers.json `runtimes`)')
             [CompletionResult]::new('-p', '-p', [CompletionResultType]::ParameterName, 'Path to server entry point')
             [CompletionResult]::new('--path', '--path', [CompletionResultType]::ParameterName, 'Path to server entry point')
+            [CompletionResult]::new('--command', '--command', [CompletionResultType]::ParameterName, 'Executable of a native server')
+            [CompletionResult]::new('--url', '--url', [CompletionResultType]::ParameterName, 'Endpoint of a remote server')
+            [CompletionResult]::new('--header', '--header', [CompletionResultType]::ParameterName, 'HTTP header of a remote server; values may use ${VAR} (repeatable)')
+            [CompletionResult]::new('--image', '--image', [CompletionResultType]::ParameterName, 'Image of a docker server')
+            [CompletionResult]::new('--docker-arg', '--docker-arg', [CompletionResultType]::ParameterName, 'Option for `docker run` (repeatable)')
+            [CompletionResult]::new('--arg', '--arg', [CompletionResultType]::ParameterName, 'Argument passed to the server (repeatable)')
             [CompletionResult]::new('-d', '-d', [CompletionResultType]::ParameterName, 'Description')
             [CompletionResult]::new('--description', '--description', [CompletionResultType]::ParameterName, 'Description')
             [CompletionResult]::new('-t', '-t', [CompletionResultType]::ParameterName, 'Tool names (comma-separated)')
             [CompletionResult]::new('--tools', '--tools', [CompletionResultType]::ParameterName, 'Tool names (comma-separated)')
-            [CompletionResult]::new('-e', '-e', [CompletionResultType]::ParameterName, 'Environment variables required (comma-separated)')
-            [CompletionResult]::new('--env', '--env', [CompletionResultType]::ParameterName, 'Environment variables required (comma-separated)')
+            [CompletionResult]::new('-e', '-e', [CompletionResultType]::ParameterName, 'Environment variables required (comma-separated, NAME or NAME=ENV_NAME)')
+            [CompletionResult]::new('--env', '--env', [CompletionResultType]::ParameterName, 'Environment variables required (comma-separated, NAME or NAME=ENV_NAME)')
             [CompletionResult]::new('--mcp-dir', '--mcp-dir', [CompletionResultType]::ParameterName, 'Path to MCP directory (default: ~/.mcp)')
+            [CompletionResult]::new('--profile', '--profile', [CompletionResultType]::ParameterName, 'Profile to use instead of the active one')
+            [CompletionResult]::new('--project-dir', '--project-dir', [CompletionResultType]::ParameterName, 'Project whose .mcp/servers.json is merged in (default: found by walking up from the current directory)')
             [CompletionResult]::new('--disabled', '--disabled', [CompletionResultType]::ParameterName, 'Mark as disabled')
             [CompletionResult]::new('--opencode-only', '--opencode-only', [CompletionResultType]::ParameterName, 'Mark as OpenCode-only')
+            [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help (see more with ''--help'')')
+            [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help (see more with ''--help'')')
+            [CompletionResult]::new('-V', '-V ', [CompletionResultType]::ParameterName, 'Print version')
+            [CompletionResult]::new('--version', '--version', [CompletionResultType]::ParameterName, 'Print version')
+            break
+        }
+        'mcp;edit' {
+            [CompletionResult]::new('--type', '--type', [CompletionResultType]::ParameterName, 'Server type (default: implied by --url, --command or --image, else runtime)')
+            [CompletionResult]::new('-r', '-r', [CompletionResultType]::ParameterName, 'Runtime (node, bun, deno, npx, bunx, or one from servers.json `runtimes`)')
+            [CompletionResult]::new('--runtime', '--runtime', [CompletionResultType]::ParameterName, 'Runtime (node, bun, deno, npx, bunx, or one from servers.json `runtimes`)')
+            [CompletionResult]::new('-p', '-p', [CompletionResultType]::ParameterName, 'Path to server entry point')
+            [CompletionResult]::new('--path', '--path', [CompletionResultType]::ParameterName, 'Path to server entry point')
+            [CompletionResult]::new('--command', '--command', [CompletionResultType]::ParameterName, 'Executable of a native server')
+            [CompletionResult]::new('--url', '--url', [CompletionResultType]::ParameterName, 'Endpoint of a remote server')
+            [CompletionResult]::new('--header', '--header', [CompletionResultType]::ParameterName, 'HTTP header of a remote server; values may use ${VAR} (repeatable)')
+            [CompletionResult]::new('--image', '--image', [CompletionResultType]::ParameterName, 'Image of a docker server')
+            [CompletionResult]::new('--docker-arg', '--docker-arg', [CompletionResultType]::ParameterName, 'Option for `docker run` (repeatable)')
+            [CompletionResult]::new('--arg', '--arg', [CompletionResultType]::ParameterName, 'Argument passed to the server (repeatable)')
+            [CompletionResult]::new('-d', '-d', [CompletionResultType]::ParameterName, 'Description')
+            [CompletionResult]::new('--description', '--description', [CompletionResultType]::ParameterName, 'Description')
+            [CompletionResult]::new('-t', '-t', [CompletionResultType]::ParameterName, 'Tool names (comma-separated)')
+            [CompletionResult]::new('--tools', '--tools', [CompletionResultType]::ParameterName, 'Tool names (comma-separated)')
+            [CompletionResult]::new('-e', '-e', [CompletionResultType]::ParameterName, 'Environment variables required (comma-separated, NAME or NAME=ENV_NAME)')
+            [CompletionResult]::new('--env', '--env', [CompletionResultType]::ParameterName, 'Environment variables required (comma-separated, NAME or NAME=ENV_NAME)')
+            [CompletionResult]::new('--mcp-dir', '--mcp-dir', [CompletionResultType]::ParameterName, 'Path to MCP directory (default: ~/.mcp)')
+            [CompletionResult]::new('--profile', '--profile', [CompletionResultType]::ParameterName, 'Profile to use instead of the active one')
+            [CompletionResult]::new('--project-dir', '--project-dir', [CompletionResultType]::ParameterName, 'Project whose .mcp/servers.json is merged in (default: found by walking up from the current directory)')
+            [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help (see more with ''--help'')')
+            [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help (see more with ''--help'')')
+            [CompletionResult]::new('-V', '-V ', [CompletionResultType]::ParameterName, 'Print version')
+            [CompletionResult]::new('--version', '--version', [CompletionResultType]::ParameterName, 'Print version')
+            break
+        }
+        'mcp;import' {
+            [CompletionResult]::new('--from', '--from', [CompletionResultType]::ParameterName, 'Client config format to read')
+            [CompletionResult]::new('--mcp-dir', '--mcp-dir', [CompletionResultType]::ParameterName, 'Path to MCP directory (default: ~/.mcp)')
+            [CompletionResult]::new('--profile', '--profile', [CompletionResultType]::ParameterName, 'Profile to use instead of the active one')
+            [CompletionResult]::new('--project-dir', '--project-dir', [CompletionResultType]::ParameterName, 'Project whose .mcp/servers.json is merged in (default: found by walking up from the current directory)')
+            [CompletionResult]::new('--overwrite', '--overwrite', [CompletionResultType]::ParameterName, 'Replace existing servers and .env values that differ')
+            [CompletionResult]::new('-d', '-d', [CompletionResultType]::ParameterName, 'Dry run - show what would be imported')
+            [CompletionResult]::new('--dry-run', '--dry-run', [CompletionResultType]::ParameterName, 'Dry run - show what would be imported')
+            [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('-V', '-V ', [CompletionResultType]::ParameterName, 'Print version')
+            [CompletionResult]::new('--version', '--version', [CompletionResultType]::ParameterName, 'Print version')
+            break
+        }
+        'mcp;install' {
+            [CompletionResult]::new('--mcp-dir', '--mcp-dir', [CompletionResultType]::ParameterName, 'Path to MCP directory (default: ~/.mcp)')
+            [CompletionResult]::new('--profile', '--profile', [CompletionResultType]::ParameterName, 'Profile to use instead of the active one')
+            [CompletionResult]::new('--project-dir', '--project-dir', [CompletionResultType]::ParameterName, 'Project whose .mcp/servers.json is merged in (default: found by walking up from the current directory)')
+            [CompletionResult]::new('-d', '-d', [CompletionResultType]::ParameterName, 'Dry run - show what would be installed')
+            [CompletionResult]::new('--dry-run', '--dry-run', [CompletionResultType]::ParameterName, 'Dry run - show what would be installed')
+            [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('-V', '-V ', [CompletionResultType]::ParameterName, 'Print version')
+            [CompletionResult]::new('--version', '--version', [CompletionResultType]::ParameterName, 'Print version')
+            break
+        }
+        'mcp;update' {
+            [CompletionResult]::new('--mcp-dir', '--mcp-dir', [CompletionResultType]::ParameterName, 'Path to MCP directory (default: ~/.mcp)')
+            [CompletionResult]::new('--profile', '--profile', [CompletionResultType]::ParameterName, 'Profile to use instead of the active one')
+            [CompletionResult]::new('--project-dir', '--project-dir', [CompletionResultType]::ParameterName, 'Project whose .mcp/servers.json is merged in (default: found by walking up from the current directory)')
+            [CompletionResult]::new('-d', '-d', [CompletionResultType]::ParameterName, 'Dry run - show what would be updated')
+            [CompletionResult]::new('--dry-run', '--dry-run', [CompletionResultType]::ParameterName, 'Dry run - show what would be updated')
             [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('-V', '-V ', [CompletionResultType]::ParameterName, 'Print version')
@@ -94,7 +229,15 @@ Register-ArgumentCompleter -Native -CommandName 'mcp' -ScriptBlock {
             break
         }
         'mcp;remove' {
+            [CompletionResult]::new('--tag', '--tag', [CompletionResultType]::ParameterName, 'Only servers with this tag (repeatable)')
+            [CompletionResult]::new('--runtime', '--runtime', [CompletionResultType]::ParameterName, 'Only servers on this runtime (node, bun, ...)')
+            [CompletionResult]::new('--type', '--type', [CompletionResultType]::ParameterName, 'Only servers of this type')
+            [CompletionResult]::new('-s', '-s', [CompletionResultType]::ParameterName, 'Only servers whose name, description or tags contain this text')
+            [CompletionResult]::new('--search', '--search', [CompletionResultType]::ParameterName, 'Only servers whose name, description or tags contain this text')
             [CompletionResult]::new('--mcp-dir', '--mcp-dir', [CompletionResultType]::ParameterName, 'Path to MCP directory (default: ~/.mcp)')
+            [CompletionResult]::new('--profile', '--profile', [CompletionResultType]::ParameterName, 'Profile to use instead of the active one')
+            [CompletionResult]::new('--project-dir', '--project-dir', [CompletionResultType]::ParameterName, 'Project whose .mcp/servers.json is merged in (default: found by walking up from the current directory)')
+            [CompletionResult]::new('--all', '--all', [CompletionResultType]::ParameterName, 'Every server (narrowed by the filter options)')
             [CompletionResult]::new('-f', '-f', [CompletionResultType]::ParameterName, 'Skip confirmation')
             [CompletionResult]::new('--force', '--force', [CompletionResultType]::ParameterName, 'Skip confirmation')
             [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
@@ -104,7 +247,17 @@ Register-ArgumentCompleter -Native -CommandName 'mcp' -ScriptBlock {
             break
         }
         'mcp;enable' {
+            [CompletionResult]::new('--tag', '--tag', [CompletionResultType]::ParameterName, 'Only servers with this tag (repeatable)')
+            [CompletionResult]::new('--runtime', '--runtime', [CompletionResultType]::ParameterName, 'Only servers on this runtime (node, bun, ...)')
+            [CompletionResult]::new('--type', '--type', [CompletionResultType]::ParameterName, 'Only servers of this type')
+            [CompletionResult]::new('-s', '-s', [CompletionResultType]::ParameterName, 'Only servers whose name, description or tags contain this text')
+            [CompletionResult]::new('--search', '--search', [CompletionResultType]::ParameterName, 'Only servers whose name, description or tags contain this text')
             [CompletionResult]::new('--mcp-dir', '--mcp-dir', [CompletionResultType]::ParameterName, 'Path to MCP directory (default: ~/.mcp)')
+            [CompletionResult]::new('--profile', '--profile', [CompletionResultType]::ParameterName, 'Profile to use instead of the active one')
+            [CompletionResult]::new('--project-dir', '--project-dir', [CompletionResultType]::ParameterName, 'Project whose .mcp/servers.json is merged in (default: found by walking up from the current directory)')
+            [CompletionResult]::new('--all', '--all', [CompletionResultType]::ParameterName, 'Every server (narrowed by the filter options)')
+            [CompletionResult]::new('-f', '-f', [CompletionResultType]::ParameterName, 'Skip confirmation')
+            [CompletionResult]::new('--force', '--force', [CompletionResultType]::ParameterName, 'Skip confirmation')
             [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('-V', '-V ', [CompletionResultType]::ParameterName, 'Print version')
@@ -112,7 +265,51 @@ Register-ArgumentCompleter -Native -CommandName 'mcp' -ScriptBlock {
             break
         }
         'mcp;disable' {
+            [CompletionResult]::new('--tag', '--tag', [CompletionResultType]::ParameterName, 'Only servers with this tag (repeatable)')
+            [CompletionResult]::new('--runtime', '--runtime', [CompletionResultType]::ParameterName, 'Only servers on this runtime (node, bun, ...)')
+            [CompletionResult]::new('--type', '--type', [CompletionResultType]::ParameterName, 'Only servers of this type')
+            [CompletionResult]::new('-s', '-s', [CompletionResultType]::ParameterName, 'Only servers whose name, description or tags contain this text')
+            [CompletionResult]::new('--search', '--search', [CompletionResultType]::ParameterName, 'Only servers whose name, description or tags contain this text')
+            [CompletionResult]::new('--mcp-dir', '--mcp-dir', [CompletionResultType]::ParameterName, 'Path to MCP directory (default: ~/.mcp)')
+            [CompletionResult]::new('--profile', '--profile', [CompletionResultType]::ParameterName, 'Profile to use instead of the active one')
+            [CompletionResult]::new('--project-dir', '--project-dir', [CompletionResultType]::ParameterName, 'Project whose .mcp/servers.json is merged in (default: found by walking up from the current directory)')
+            [CompletionResult]::new('--all', '--all', [CompletionResultType]::ParameterName, 'Every server (narrowed by the filter options)')
+            [CompletionResult]::new('-f', '-f', [CompletionResultType]::ParameterName, 'Skip confirmation')
+            [CompletionResult]::new('--force', '--force', [CompletionResultType]::ParameterName, 'Skip confirmation')
+            [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('-V', '-V ', [CompletionResultType]::ParameterName, 'Print version')
+            [CompletionResult]::new('--version', '--version', [CompletionResultType]::ParameterName, 'Print version')
+            break
+        }
+        'mcp;validate' {
             [CompletionResult]::new('--mcp-dir', '--mcp-dir', [CompletionResultType]::ParameterName, 'Path to MCP directory (default: ~/.mcp)')
+            [CompletionResult]::new('--profile', '--profile', [CompletionResultType]::ParameterName, 'Profile to use instead of the active one')
+            [CompletionResult]::new('--project-dir', '--project-dir', [CompletionResultType]::ParameterName, 'Project whose .mcp/servers.json is merged in (default: found by walking up from the current directory)')
+            [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('-V', '-V ', [CompletionResultType]::ParameterName, 'Print version')
+            [CompletionResult]::new('--version', '--version', [CompletionResultType]::ParameterName, 'Print version')
+            break
+        }
+        'mcp;schema' {
+            [CompletionResult]::new('--mcp-dir', '--mcp-dir', [CompletionResultType]::ParameterName, 'Path to MCP directory (default: ~/.mcp)')
+            [CompletionResult]::new('--profile', '--profile', [CompletionResultType]::ParameterName, 'Profile to use instead of the active one')
+            [CompletionResult]::new('--project-dir', '--project-dir', [CompletionResultType]::ParameterName, 'Project whose .mcp/servers.json is merged in (default: found by walking up from the current directory)')
+            [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('-V', '-V ', [CompletionResultType]::ParameterName, 'Print version')
+            [CompletionResult]::new('--version', '--version', [CompletionResultType]::ParameterName, 'Print version')
+            break
+        }
+        'mcp;migrate' {
+            [CompletionResult]::new('--mcp-dir', '--mcp-dir', [CompletionResultType]::ParameterName, 'Path to MCP directory (default: ~/.mcp)')
+            [CompletionResult]::new('--profile', '--profile', [CompletionResultType]::ParameterName, 'Profile to use instead of the active one')
+            [CompletionResult]::new('--project-dir', '--project-dir', [CompletionResultType]::ParameterName, 'Project whose .mcp/servers.json is merged in (default: found by walking up from the current directory)')
+            [CompletionResult]::new('-d', '-d', [CompletionResultType]::ParameterName, 'Dry run - show the changes without writing')
+            [CompletionResult]::new('--dry-run', '--dry-run', [CompletionResultType]::ParameterName, 'Dry run - show the changes without writing')
+            [CompletionResult]::new('-y', '-y', [CompletionResultType]::ParameterName, 'Write without asking for confirmation')
+            [CompletionResult]::new('--yes', '--yes', [CompletionResultType]::ParameterName, 'Write without asking for confirmation')
             [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('-V', '-V ', [CompletionResultType]::ParameterName, 'Print version')
@@ -121,6 +318,8 @@ Register-ArgumentCompleter -Native -CommandName 'mcp' -ScriptBlock {
         }
         'mcp;show' {
             [CompletionResult]::new('--mcp-dir', '--mcp-dir', [CompletionResultType]::ParameterName, 'Path to MCP directory (default: ~/.mcp)')
+            [CompletionResult]::new('--profile', '--profile', [CompletionResultType]::ParameterName, 'Profile to use instead of the active one')
+            [CompletionResult]::new('--project-dir', '--project-dir', [CompletionResultType]::ParameterName, 'Project whose .mcp/servers.json is merged in (default: found by walking up from the current directory)')
             [CompletionResult]::new('--json', '--json', [CompletionResultType]::ParameterName, 'Output as JSON')
             [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
@@ -130,6 +329,8 @@ Register-ArgumentCompleter -Native -CommandName 'mcp' -ScriptBlock {
         }
         'mcp;env' {
             [CompletionResult]::new('--mcp-dir', '--mcp-dir', [CompletionResultType]::ParameterName, 'Path to MCP directory (default: ~/.mcp)')
+            [CompletionResult]::new('--profile', '--profile', [CompletionResultType]::ParameterName, 'Profile to use instead of the active one')
+            [CompletionResult]::new('--project-dir', '--project-dir', [CompletionResultType]::ParameterName, 'Project whose .mcp/servers.json is merged in (default: found by walking up from the current directory)')
             [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('-V', '-V ', [CompletionResultType]::ParameterName, 'Print version')
@@ -137,12 +338,16 @@ Register-ArgumentCompleter -Native -CommandName 'mcp' -ScriptBlock {
             [CompletionResult]::new('list', 'list', [CompletionResultType]::ParameterValue, 'List environment variables')
             [CompletionResult]::new('set', 'set', [CompletionResultType]::ParameterValue, 'Set an environment variable')
             [CompletionResult]::new('remove', 'remove', [CompletionResultType]::ParameterValue, 'Remove an environment variable')
+            [CompletionResult]::new('check', 'check', [CompletionResultType]::ParameterValue, 'Check that enabled servers have the variables they need')
+            [CompletionResult]::new('migrate', 'migrate', [CompletionResultType]::ParameterValue, 'Move values from .env into the secret store set in servers.json')
             [CompletionResult]::new('edit', 'edit', [CompletionResultType]::ParameterValue, 'Edit .env file in default editor')
             [CompletionResult]::new('help', 'help', [CompletionResultType]::ParameterValue, 'Print this message or the help of the given subcommand(s)')
             break
         }
         'mcp;env;list' {
             [CompletionResult]::new('--mcp-dir', '--mcp-dir', [CompletionResultType]::ParameterName, 'Path to MCP directory (default: ~/.mcp)')
+            [CompletionResult]::new('--profile', '--profile', [CompletionResultType]::ParameterName, 'Profile to use instead of the active one')
+            [CompletionResult]::new('--project-dir', '--project-dir', [CompletionResultType]::ParameterName, 'Project whose .mcp/servers.json is merged in (default: found by walking up from the current directory)')
             [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('-V', '-V ', [CompletionResultType]::ParameterName, 'Print version')
@@ -151,6 +356,8 @@ Register-ArgumentCompleter -Native -CommandName 'mcp' -ScriptBlock {
         }
         'mcp;env;set' {
             [CompletionResult]::new('--mcp-dir', '--mcp-dir', [CompletionResultType]::ParameterName, 'Path to MCP directory (default: ~/.mcp)')
+            [CompletionResult]::new('--profile', '--profile', [CompletionResultType]::ParameterName, 'Profile to use instead of the active one')
+            [CompletionResult]::new('--project-dir', '--project-dir', [CompletionResultType]::ParameterName, 'Project whose .mcp/servers.json is merged in (default: found by walking up from the current directory)')
             [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('-V', '-V ', [CompletionResultType]::ParameterName, 'Print version')
@@ -159,6 +366,30 @@ Register-ArgumentCompleter -Native -CommandName 'mcp' -ScriptBlock {
         }
         'mcp;env;remove' {
             [CompletionResult]::new('--mcp-dir', '--mcp-dir', [CompletionResultType]::ParameterName, 'Path to MCP directory (default: ~/.mcp)')
+            [CompletionResult]::new('--profile', '--profile', [CompletionResultType]::ParameterName, 'Profile to use instead of the active one')
+            [CompletionResult]::new('--project-dir', '--project-dir', [CompletionResultType]::ParameterName, 'Project whose .mcp/servers.json is merged in (default: found by walking up from the current directory)')
+            [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('-V', '-V ', [CompletionResultType]::ParameterName, 'Print version')
+            [CompletionResult]::new('--version', '--version', [CompletionResultType]::ParameterName, 'Print version')
+            break
+        }
+        'mcp;env;check' {
+            [CompletionResult]::new('--mcp-dir', '--mcp-dir', [CompletionResultType]::ParameterName, 'Path to MCP directory (default: ~/.mcp)')
+            [CompletionResult]::new('--profile', '--profile', [CompletionResultType]::ParameterName, 'Profile to use instead of the active one')
+            [CompletionResult]::new('--project-dir', '--project-dir', [CompletionResultType]::ParameterName, 'Project whose .mcp/servers.json is merged in (default: found by walking up from the current directory)')
+            [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('-V', '-V ', [CompletionResultType]::ParameterName, 'Print version')
+            [CompletionResult]::new('--version', '--version', [CompletionResultType]::ParameterName, 'Print version')
+            break
+        }
+        'mcp;env;migrate' {
+            [CompletionResult]::new('--mcp-dir', '--mcp-dir', [CompletionResultType]::ParameterName, 'Path to MCP directory (default: ~/.mcp)')
+            [CompletionResult]::new('--profile', '--profile', [CompletionResultType]::ParameterName, 'Profile to use instead of the active one')
+            [CompletionResult]::new('--project-dir', '--project-dir', [CompletionResultType]::ParameterName, 'Project whose .mcp/servers.json is merged in (default: found by walking up from the current directory)')
+            [CompletionResult]::new('-d', '-d', [CompletionResultType]::ParameterName, 'Dry run - show what would be moved')
+            [CompletionResult]::new('--dry-run', '--dry-run', [CompletionResultType]::ParameterName, 'Dry run - show what would be moved')
             [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('-V', '-V ', [CompletionResultType]::ParameterName, 'Print version')
@@ -167,6 +398,8 @@ Register-ArgumentCompleter -Native -CommandName 'mcp' -ScriptBlock {
         }
         'mcp;env;edit' {
             [CompletionResult]::new('--mcp-dir', '--mcp-dir', [CompletionResultType]::ParameterName, 'Path to MCP directory (default: ~/.mcp)')
+            [CompletionResult]::new('--profile', '--profile', [CompletionResultType]::ParameterName, 'Profile to use instead of the active one')
+            [CompletionResult]::new('--project-dir', '--project-dir', [CompletionResultType]::ParameterName, 'Project whose .mcp/servers.json is merged in (default: found by walking up from the current directory)')
             [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('-V', '-V ', [CompletionResultType]::ParameterName, 'Print version')
@@ -177,6 +410,8 @@ Register-ArgumentCompleter -Native -CommandName 'mcp' -ScriptBlock {
             [CompletionResult]::new('list', 'list', [CompletionResultType]::ParameterValue, 'List environment variables')
             [CompletionResult]::new('set', 'set', [CompletionResultType]::ParameterValue, 'Set an environment variable')
             [CompletionResult]::new('remove', 'remove', [CompletionResultType]::ParameterValue, 'Remove an environment variable')
+            [CompletionResult]::new('check', 'check', [CompletionResultType]::ParameterValue, 'Check that enabled servers have the variables they need')
+            [CompletionResult]::new('migrate', 'migrate', [CompletionResultType]::ParameterValue, 'Move values from .env into the secret store set in servers.json')
             [CompletionResult]::new('edit', 'edit', [CompletionResultType]::ParameterValue, 'Edit .env file in default editor')
             [CompletionResult]::new('help', 'help', [CompletionResultType]::ParameterValue, 'Print this message or the help of the given subcommand(s)')
             break
@@ -190,14 +425,180 @@ Register-ArgumentCompleter -Native -CommandName 'mcp' -ScriptBlock {
         'mcp;env;help;remove' {
             break
         }
+        'mcp;env;help;check' {
+            break
+        }
+        'mcp;env;help;migrate' {
+            break
+        }
         'mcp;env;help;edit' {
             break
         }
         'mcp;env;help;help' {
             break
         }
+        'mcp;logs' {
+            [CompletionResult]::new('--since', '--since', [CompletionResultType]::ParameterName, 'Only lines newer than a duration (30s, 10m, 2h, 1d) or a time')
+            [CompletionResult]::new('-n', '-n', [CompletionResultType]::ParameterName, 'Only the last N lines')
+            [CompletionResult]::new('--lines', '--lines', [CompletionResultType]::ParameterName, 'Only the last N lines')
+            [CompletionResult]::new('--mcp-dir', '--mcp-dir', [CompletionResultType]::ParameterName, 'Path to MCP directory (default: ~/.mcp)')
+            [CompletionResult]::new('--profile', '--profile', [CompletionResultType]::ParameterName, 'Profile to use instead of the active one')
+            [CompletionResult]::new('--project-dir', '--project-dir', [CompletionResultType]::ParameterName, 'Project whose .mcp/servers.json is merged in (default: found by walking up from the current directory)')
+            [CompletionResult]::new('--daemon', '--daemon', [CompletionResultType]::ParameterName, 'Show the daemon''s log instead')
+            [CompletionResult]::new('-f', '-f', [CompletionResultType]::ParameterName, 'Keep printing lines as they are written')
+            [CompletionResult]::new('--follow', '--follow', [CompletionResultType]::ParameterName, 'Keep printing lines as they are written')
+            [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('-V', '-V ', [CompletionResultType]::ParameterName, 'Print version')
+            [CompletionResult]::new('--version', '--version', [CompletionResultType]::ParameterName, 'Print version')
+            break
+        }
+        'mcp;profile' {
+            [CompletionResult]::new('--mcp-dir', '--mcp-dir', [CompletionResultType]::ParameterName, 'Path to MCP directory (default: ~/.mcp)')
+            [CompletionResult]::new('--profile', '--profile', [CompletionResultType]::ParameterName, 'Profile to use instead of the active one')
+            [CompletionResult]::new('--project-dir', '--project-dir', [CompletionResultType]::ParameterName, 'Project whose .mcp/servers.json is merged in (default: found by walking up from the current directory)')
+            [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('-V', '-V ', [CompletionResultType]::ParameterName, 'Print version')
+            [CompletionResult]::new('--version', '--version', [CompletionResultType]::ParameterName, 'Print version')
+            [CompletionResult]::new('list', 'list', [CompletionResultType]::ParameterValue, 'List profiles')
+            [CompletionResult]::new('use', 'use', [CompletionResultType]::ParameterValue, 'Make a profile active')
+            [CompletionResult]::new('clear', 'clear', [CompletionResultType]::ParameterValue, 'Go back to the `disabled` flags of each server')
+            [CompletionResult]::new('show', 'show', [CompletionResultType]::ParameterValue, 'Show the servers a profile selects (default: the active one)')
+            [CompletionResult]::new('help', 'help', [CompletionResultType]::ParameterValue, 'Print this message or the help of the given subcommand(s)')
+            break
+        }
+        'mcp;profile;list' {
+            [CompletionResult]::new('--mcp-dir', '--mcp-dir', [CompletionResultType]::ParameterName, 'Path to MCP directory (default: ~/.mcp)')
+            [CompletionResult]::new('--profile', '--profile', [CompletionResultType]::ParameterName, 'Profile to use instead of the active one')
+            [CompletionResult]::new('--project-dir', '--project-dir', [CompletionResultType]::ParameterName, 'Project whose .mcp/servers.json is merged in (default: found by walking up from the current directory)')
+            [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('-V', '-V ', [CompletionResultType]::ParameterName, 'Print version')
+            [CompletionResult]::new('--version', '--version', [CompletionResultType]::ParameterName, 'Print version')
+            break
+        }
+        'mcp;profile;use' {
+            [CompletionResult]::new('--mcp-dir', '--mcp-dir', [CompletionResultType]::ParameterName, 'Path to MCP directory (default: ~/.mcp)')
+            [CompletionResult]::new('--profile', '--profile', [CompletionResultType]::ParameterName, 'Profile to use instead of the active one')
+            [CompletionResult]::new('--project-dir', '--project-dir', [CompletionResultType]::ParameterName, 'Project whose .mcp/servers.json is merged in (default: found by walking up from the current directory)')
+            [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('-V', '-V ', [CompletionResultType]::ParameterName, 'Print version')
+            [CompletionResult]::new('--version', '--version', [CompletionResultType]::ParameterName, 'Print version')
+            break
+        }
+        'mcp;profile;clear' {
+            [CompletionResult]::new('--mcp-dir', '--mcp-dir', [CompletionResultType]::ParameterName, 'Path to MCP directory (default: ~/.mcp)')
+            [CompletionResult]::new('--profile', '--profile', [CompletionResultType]::ParameterName, 'Profile to use instead of the active one')
+            [CompletionResult]::new('--project-dir', '--project-dir', [CompletionResultType]::ParameterName, 'Project whose .mcp/servers.json is merged in (default: found by walking up from the current directory)')
+            [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('-V', '-V ', [CompletionResultType]::ParameterName, 'Print version')
+            [CompletionResult]::new('--version', '--version', [CompletionResultType]::ParameterName, 'Print version')
+            break
+        }
+        'mcp;profile;show' {
+            [CompletionResult]::new('--mcp-dir', '--mcp-dir', [CompletionResultType]::ParameterName, 'Path to MCP directory (default: ~/.mcp)')
+            [CompletionResult]::new('--profile', '--profile', [CompletionResultType]::ParameterName, 'Profile to use instead of the active one')
+            [CompletionResult]::new('--project-dir', '--project-dir', [CompletionResultType]::ParameterName, 'Project whose .mcp/servers.json is merged in (default: found by walking up from the current directory)')
+            [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('-V', '-V ', [CompletionResultType]::ParameterName, 'Print version')
+            [CompletionResult]::new('--version', '--version', [CompletionResultType]::ParameterName, 'Print version')
+            break
+        }
+        'mcp;profile;help' {
+            [CompletionResult]::new('list', 'list', [CompletionResultType]::ParameterValue, 'List profiles')
+            [CompletionResult]::new('use', 'use', [CompletionResultType]::ParameterValue, 'Make a profile active')
+            [CompletionResult]::new('clear', 'clear', [CompletionResultType]::ParameterValue, 'Go back to the `disabled` flags of each server')
+            [CompletionResult]::new('show', 'show', [CompletionResultType]::ParameterValue, 'Show the servers a profile selects (default: the active one)')
+            [CompletionResult]::new('help', 'help', [CompletionResultType]::ParameterValue, 'Print this message or the help of the given subcommand(s)')
+            break
+        }
+        'mcp;profile;help;list' {
+            break
+        }
+        'mcp;profile;help;use' {
+            break
+        }
+        'mcp;profile;help;clear' {
+            break
+        }
+        'mcp;profile;help;show' {
+            break
+        }
+        'mcp;profile;help;help' {
+            break
+        }
+        'mcp;daemon' {
+            [CompletionResult]::new('--mcp-dir', '--mcp-dir', [CompletionResultType]::ParameterName, 'Path to MCP directory (default: ~/.mcp)')
+            [CompletionResult]::new('--profile', '--profile', [CompletionResultType]::ParameterName, 'Profile to use instead of the active one')
+            [CompletionResult]::new('--project-dir', '--project-dir', [CompletionResultType]::ParameterName, 'Project whose .mcp/servers.json is merged in (default: found by walking up from the current directory)')
+            [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('-V', '-V ', [CompletionResultType]::ParameterName, 'Print version')
+            [CompletionResult]::new('--version', '--version', [CompletionResultType]::ParameterName, 'Print version')
+            [CompletionResult]::new('start', 'start', [CompletionResultType]::ParameterValue, 'Start the daemon and the enabled servers')
+            [CompletionResult]::new('stop', 'stop', [CompletionResultType]::ParameterValue, 'Stop the daemon and the servers it runs')
+            [CompletionResult]::new('status', 'status', [CompletionResultType]::ParameterValue, 'Show the servers the daemon runs')
+            [CompletionResult]::new('help', 'help', [CompletionResultType]::ParameterValue, 'Print this message or the help of the given subcommand(s)')
+            break
+        }
+        'mcp;daemon;start' {
+            [CompletionResult]::new('--mcp-dir', '--mcp-dir', [CompletionResultType]::ParameterName, 'Path to MCP directory (default: ~/.mcp)')
+            [CompletionResult]::new('--profile', '--profile', [CompletionResultType]::ParameterName, 'Profile to use instead of the active one')
+            [CompletionResult]::new('--project-dir', '--project-dir', [CompletionResultType]::ParameterName, 'Project whose .mcp/servers.json is merged in (default: found by walking up from the current directory)')
+            [CompletionResult]::new('--foreground', '--foreground', [CompletionResultType]::ParameterName, 'Stay in the foreground instead of detaching')
+            [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('-V', '-V ', [CompletionResultType]::ParameterName, 'Print version')
+            [CompletionResult]::new('--version', '--version', [CompletionResultType]::ParameterName, 'Print version')
+            break
+        }
+        'mcp;daemon;stop' {
+            [CompletionResult]::new('--mcp-dir', '--mcp-dir', [CompletionResultType]::ParameterName, 'Path to MCP directory (default: ~/.mcp)')
+            [CompletionResult]::new('--profile', '--profile', [CompletionResultType]::ParameterName, 'Profile to use instead of the active one')
+            [CompletionResult]::new('--project-dir', '--project-dir', [CompletionResultType]::ParameterName, 'Project whose .mcp/servers.json is merged in (default: found by walking up from the current directory)')
+            [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('-V', '-V ', [CompletionResultType]::ParameterName, 'Print version')
+            [CompletionResult]::new('--version', '--version', [CompletionResultType]::ParameterName, 'Print version')
+            break
+        }
+        'mcp;daemon;status' {
+            [CompletionResult]::new('--mcp-dir', '--mcp-dir', [CompletionResultType]::ParameterName, 'Path to MCP directory (default: ~/.mcp)')
+            [CompletionResult]::new('--profile', '--profile', [CompletionResultType]::ParameterName, 'Profile to use instead of the active one')
+            [CompletionResult]::new('--project-dir', '--project-dir', [CompletionResultType]::ParameterName, 'Project whose .mcp/servers.json is merged in (default: found by walking up from the current directory)')
+            [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('-V', '-V ', [CompletionResultType]::ParameterName, 'Print version')
+            [CompletionResult]::new('--version', '--version', [CompletionResultType]::ParameterName, 'Print version')
+            break
+        }
+        'mcp;daemon;help' {
+            [CompletionResult]::new('start', 'start', [CompletionResultType]::ParameterValue, 'Start the daemon and the enabled servers')
+            [CompletionResult]::new('stop', 'stop', [CompletionResultType]::ParameterValue, 'Stop the daemon and the servers it runs')
+            [CompletionResult]::new('status', 'status', [CompletionResultType]::ParameterValue, 'Show the servers the daemon runs')
+            [CompletionResult]::new('help', 'help', [CompletionResultType]::ParameterValue, 'Print this message or the help of the given subcommand(s)')
+            break
+        }
+        'mcp;daemon;help;start' {
+            break
+        }
+        'mcp;daemon;help;stop' {
+            break
+        }
+        'mcp;daemon;help;status' {
+            break
+        }
+        'mcp;daemon;help;help' {
+            break
+        }
         'mcp;info' {
             [CompletionResult]::new('--mcp-dir', '--mcp-dir', [CompletionResultType]::ParameterName, 'Path to MCP directory (default: ~/.mcp)')
+            [CompletionResult]::new('--profile', '--profile', [CompletionResultType]::ParameterName, 'Profile to use instead of the active one')
+            [CompletionResult]::new('--project-dir', '--project-dir', [CompletionResultType]::ParameterName, 'Project whose .mcp/servers.json is merged in (default: found by walking up from the current directory)')
             [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('-V', '-V ', [CompletionResultType]::ParameterName, 'Print version')
@@ -206,6 +607,8 @@ Register-ArgumentCompleter -Native -CommandName 'mcp' -ScriptBlock {
         }
         'mcp;completions' {
             [CompletionResult]::new('--mcp-dir', '--mcp-dir', [CompletionResultType]::ParameterName, 'Path to MCP directory (default: ~/.mcp)')
+            [CompletionResult]::new('--profile', '--profile', [CompletionResultType]::ParameterName, 'Profile to use instead of the active one')
+            [CompletionResult]::new('--project-dir', '--project-dir', [CompletionResultType]::ParameterName, 'Project whose .mcp/servers.json is merged in (default: found by walking up from the current directory)')
             [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('-V', '-V ', [CompletionResultType]::ParameterName, 'Print version')
@@ -215,13 +618,25 @@ Register-ArgumentCompleter -Native -CommandName 'mcp' -ScriptBlock {
         'mcp;help' {
             [CompletionResult]::new('list', 'list', [CompletionResultType]::ParameterValue, 'List all configured MCP servers')
             [CompletionResult]::new('run', 'run', [CompletionResultType]::ParameterValue, 'Run an MCP server')
-            [CompletionResult]::new('gen', 'gen', [CompletionResultType]::ParameterValue, 'Generate Cursor and OpenCode configuration files')
+            [CompletionResult]::new('serve', 'serve', [CompletionResultType]::ParameterValue, 'Serve a stdio server over streamable HTTP and SSE')
+            [CompletionResult]::new('replay', 'replay', [CompletionResultType]::ParameterValue, 'Act as a fake server answering from a recording')
+            [CompletionResult]::new('gen', 'gen', [CompletionResultType]::ParameterValue, 'Generate MCP client configuration files')
             [CompletionResult]::new('add', 'add', [CompletionResultType]::ParameterValue, 'Add a new MCP server')
-            [CompletionResult]::new('remove', 'remove', [CompletionResultType]::ParameterValue, 'Remove an MCP server')
-            [CompletionResult]::new('enable', 'enable', [CompletionResultType]::ParameterValue, 'Enable an MCP server')
-            [CompletionResult]::new('disable', 'disable', [CompletionResultType]::ParameterValue, 'Disable an MCP server')
+            [CompletionResult]::new('edit', 'edit', [CompletionResultType]::ParameterValue, 'Change an existing server')
+            [CompletionResult]::new('import', 'import', [CompletionResultType]::ParameterValue, 'Import servers from an existing client config')
+            [CompletionResult]::new('install', 'install', [CompletionResultType]::ParameterValue, 'Install the packages of runtime-based servers')
+            [CompletionResult]::new('update', 'update', [CompletionResultType]::ParameterValue, 'Update installed server packages to their latest version')
+            [CompletionResult]::new('remove', 'remove', [CompletionResultType]::ParameterValue, 'Remove MCP servers')
+            [CompletionResult]::new('enable', 'enable', [CompletionResultType]::ParameterValue, 'Enable MCP servers')
+            [CompletionResult]::new('disable', 'disable', [CompletionResultType]::ParameterValue, 'Disable MCP servers')
+            [CompletionResult]::new('validate', 'validate', [CompletionResultType]::ParameterValue, 'Check servers.json (and the project overlay) for mistakes')
+            [CompletionResult]::new('schema', 'schema', [CompletionResultType]::ParameterValue, 'Print the JSON Schema for servers.json')
+            [CompletionResult]::new('migrate', 'migrate', [CompletionResultType]::ParameterValue, 'Rewrite servers.json (and the project overlay) in the current format')
             [CompletionResult]::new('show', 'show', [CompletionResultType]::ParameterValue, 'Show server details')
             [CompletionResult]::new('env', 'env', [CompletionResultType]::ParameterValue, 'Manage environment variables')
+            [CompletionResult]::new('logs', 'logs', [CompletionResultType]::ParameterValue, 'Show a server''s log')
+            [CompletionResult]::new('profile', 'profile', [CompletionResultType]::ParameterValue, 'Switch between named sets of servers')
+            [CompletionResult]::new('daemon', 'daemon', [CompletionResultType]::ParameterValue, 'Keep shared server instances running in the background')
             [CompletionResult]::new('info', 'info', [CompletionResultType]::ParameterValue, 'Show paths and configuration')
             [CompletionResult]::new('completions', 'completions', [CompletionResultType]::ParameterValue, 'Generate shell completions')
             [CompletionResult]::new('help', 'help', [CompletionResultType]::ParameterValue, 'Print this message or the help of the given subcommand(s)')
@@ -233,12 +648,30 @@ Register-ArgumentCompleter -Native -CommandName 'mcp' -ScriptBlock {
         'mcp;help;run' {
             break
         }
+        'mcp;help;serve' {
+            break
+        }
+        'mcp;help;replay' {
+            break
+        }
         'mcp;help;gen' {
             break
         }
         'mcp;help;add' {
             break
         }
+        'mcp;help;edit' {
+            break
+        }
+        'mcp;help;import' {
+            break
+        }
+        'mcp;help;install' {
+            break
+        }
+        'mcp;help;update' {
+            break
+        }
         'mcp;help;remove' {
             break
         }
@@ -248,6 +681,15 @@ Register-ArgumentCompleter -Native -CommandName 'mcp' -ScriptBlock {
         'mcp;help;disable' {
             break
         }
+        'mcp;help;validate' {
+            break
+        }
+        'mcp;help;schema' {
+            break
+        }
+        'mcp;help;migrate' {
+            break
+        }
         'mcp;help;show' {
             break
         }
@@ -255,6 +697,8 @@ Register-ArgumentCompleter -Native -CommandName 'mcp' -ScriptBlock {
             [CompletionResult]::new('list', 'list', [CompletionResultType]::ParameterValue, 'List environment variables')
             [CompletionResult]::new('set', 'set', [CompletionResultType]::ParameterValue, 'Set an environment variable')
             [CompletionResult]::new('remove', 'remove', [CompletionResultType]::ParameterValue, 'Remove an environment variable')
+            [CompletionResult]::new('check', 'check', [CompletionResultType]::ParameterValue, 'Check that enabled servers have the variables they need')
+            [CompletionResult]::new('migrate', 'migrate', [CompletionResultType]::ParameterValue, 'Move values from .env into the secret store set in servers.json')
             [CompletionResult]::new('edit', 'edit', [CompletionResultType]::ParameterValue, 'Edit .env file in default editor')
             break
         }
@@ -267,9 +711,52 @@ Register-ArgumentCompleter -Native -CommandName 'mcp' -ScriptBlock {
         'mcp;help;env;remove' {
             break
         }
+        'mcp;help;env;check' {
+            break
+        }
+        'mcp;help;env;migrate' {
+            break
+        }
         'mcp;help;env;edit' {
             break
         }
+        'mcp;help;logs' {
+            break
+        }
+        'mcp;help;profile' {
+            [CompletionResult]::new('list', 'list', [CompletionResultType]::ParameterValue, 'List profiles')
+            [CompletionResult]::new('use', 'use', [CompletionResultType]::ParameterValue, 'Make a profile active')
+            [CompletionResult]::new('clear', 'clear', [CompletionResultType]::ParameterValue, 'Go back to the `disabled` flags of each server')
+            [CompletionResult]::new('show', 'show', [CompletionResultType]::ParameterValue, 'Show the servers a profile selects (default: the active one)')
+            break
+        }
+        'mcp;help;profile;list' {
+            break
+        }
+        'mcp;help;profile;use' {
+            break
+        }
+        'mcp;help;profile;clear' {
+            break
+        }
+        'mcp;help;profile;show' {
+            break
+        }
+        'mcp;help;daemon' {
+            [CompletionResult]::new('start', 'start', [CompletionResultType]::ParameterValue, 'Start the daemon and the enabled servers')
+            [CompletionResult]::new('stop', 'stop', [CompletionResultType]::ParameterValue, 'Stop the daemon and the servers it runs')
+            [CompletionResult]::new('status', 'status', [CompletionResultType]::ParameterValue, 'Show the servers the daemon runs')
+            break
+        }
+        'mcp;help;daemon;start' {
+            break
+        }
+        'mcp;help;daemon;stop' {
+            break
+        }
+        'mcp;help;daemon;status' {
+            break
+        }
         'mcp;help;info' {
             break
         }
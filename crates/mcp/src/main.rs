@@ -1,13 +1,21 @@
 use anyhow::{Context, Result};
-use clap::{CommandFactory, Parser, Subcommand};
+use base64::Engine;
+use chrono::SecondsFormat;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
 use clap_complete::{generate, Shell};
 use colored::Colorize;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use sha2::{Digest, Sha512};
+use similar::{ChangeTag, TextDiff};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs;
-use std::io;
-use std::path::PathBuf;
+use std::io::{self, BufRead, BufReader, IsTerminal, Write};
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
 use tabled::{Table, Tabled};
 
 /// MCP (Model Context Protocol) Server Manager
@@ -22,6 +30,15 @@ struct Cli {
     /// Path to MCP directory (default: ~/.mcp)
     #[arg(long, global = true)]
     mcp_dir: Option<PathBuf>,
+
+    /// Profile to use instead of the active one
+    #[arg(long, global = true)]
+    profile: Option<String>,
+
+    /// Project whose .mcp/servers.json is merged in (default: found by
+    /// walking up from the current directory)
+    #[arg(long, global = true, value_name = "DIR")]
+    project_dir: Option<PathBuf>,
 }
 
 #[derive(Subcommand)]
@@ -36,6 +53,21 @@ enum Commands {
         #[arg(short, long)]
         disabled: bool,
 
+        #[command(flatten)]
+        filter: ServerFilter,
+
+        /// Order of the rows
+        #[arg(long, value_enum, default_value = "name")]
+        sort: ListSort,
+
+        /// Columns to show (comma-separated)
+        #[arg(long, value_enum, value_delimiter = ',', default_value = "name,runtime,status,tags,description")]
+        columns: Vec<ListColumn>,
+
+        /// Show descriptions in full
+        #[arg(short, long)]
+        wide: bool,
+
         /// Output as JSON
         #[arg(long)]
         json: bool,
@@ -46,50 +78,87 @@ enum Commands {
         /// Server name (from servers.json)
         name: String,
 
+        /// Record JSON-RPC traffic to a JSONL file
+        #[arg(long)]
+        record: Option<PathBuf>,
+
+        /// Start a private process even if the daemon runs this server
+        #[arg(long)]
+        no_daemon: bool,
+
         /// Additional arguments to pass to the server
         #[arg(trailing_var_arg = true)]
         args: Vec<String>,
     },
 
-    /// Generate Cursor and OpenCode configuration files
+    /// Serve a stdio server over streamable HTTP and SSE
+    Serve {
+        /// Server name (from servers.json)
+        name: String,
+
+        /// Address to listen on, e.g. 127.0.0.1:8808
+        #[arg(long, value_name = "ADDR")]
+        http: String,
+
+        /// Require `Authorization: Bearer <TOKEN>`
+        #[arg(long, conflicts_with = "token_env")]
+        token: Option<String>,
+
+        /// Read the bearer token from this .env variable
+        #[arg(long, value_name = "VAR")]
+        token_env: Option<String>,
+    },
+
+    /// Act as a fake server answering from a recording
+    Replay {
+        /// Recording file (from `mcp run --record`)
+        file: PathBuf,
+    },
+
+    /// Generate MCP client configuration files
     Gen {
-        /// Only generate Cursor config
-        #[arg(long)]
+        /// Client to generate for (repeatable; default: `targets` in servers.json)
+        #[arg(short, long = "target", value_parser = target_names())]
+        targets: Vec<String>,
+
+        /// Only generate Cursor config (same as --target cursor)
+        #[arg(long, hide = true)]
         cursor: bool,
 
-        /// Only generate OpenCode config
-        #[arg(long)]
+        /// Only generate OpenCode config (same as --target opencode)
+        #[arg(long, hide = true)]
         opencode: bool,
 
         /// Dry run - print configs without writing
         #[arg(short, long)]
         dry_run: bool,
-    },
-
-    /// Add a new MCP server
-    Add {
-        /// Server name
-        name: String,
 
-        /// Runtime (node, bun, deno, npx, bunx)
-        #[arg(short, long, default_value = "node")]
-        runtime: String,
+        /// Write each server's declared env vars into the client config
+        #[arg(long, value_enum, default_value_t = EnvMode::None)]
+        env: EnvMode,
 
-        /// Path to server entry point
+        /// Write without asking for confirmation
         #[arg(short, long)]
-        path: String,
+        yes: bool,
 
-        /// Description
-        #[arg(short, long)]
-        description: Option<String>,
+        /// Compare with the files on disk and exit non-zero if any are stale
+        #[arg(long, conflicts_with = "dry_run")]
+        check: bool,
 
-        /// Tool names (comma-separated)
-        #[arg(short, long)]
-        tools: Option<String>,
+        /// Write the project's client files next to its .mcp/servers.json
+        #[arg(long)]
+        project: bool,
+    },
 
-        /// Environment variables required (comma-separated)
-        #[arg(short, long)]
-        env: Option<String>,
+    /// Add a new MCP server
+    ///
+    /// Without any server flags, asks for each setting instead.
+    Add {
+        /// Server name
+        name: Option<String>,
+
+        #[command(flatten)]
+        fields: ServerFields,
 
         /// Mark as disabled
         #[arg(long)]
@@ -100,26 +169,104 @@ enum Commands {
         opencode_only: bool,
     },
 
-    /// Remove an MCP server
-    Remove {
+    /// Change an existing server
+    ///
+    /// Flags replace single settings; without any, walks through every
+    /// setting showing the current value.
+    Edit {
         /// Server name
         name: String,
 
+        #[command(flatten)]
+        fields: ServerFields,
+    },
+
+    /// Import servers from an existing client config
+    Import {
+        /// Client config format to read
+        #[arg(long, value_parser = target_names())]
+        from: String,
+
+        /// Config file (default: the client's usual location)
+        path: Option<PathBuf>,
+
+        /// Replace existing servers and .env values that differ
+        #[arg(long)]
+        overwrite: bool,
+
+        /// Dry run - show what would be imported
+        #[arg(short, long)]
+        dry_run: bool,
+    },
+
+    /// Install the packages of runtime-based servers
+    Install {
+        /// Servers to install (default: enabled servers that are missing)
+        names: Vec<String>,
+
+        /// Dry run - show what would be installed
+        #[arg(short, long)]
+        dry_run: bool,
+    },
+
+    /// Update installed server packages to their latest version
+    Update {
+        /// Servers to update (default: every enabled server)
+        names: Vec<String>,
+
+        /// Dry run - show what would be updated
+        #[arg(short, long)]
+        dry_run: bool,
+    },
+
+    /// Remove MCP servers
+    Remove {
+        #[command(flatten)]
+        selection: ServerSelection,
+
         /// Skip confirmation
         #[arg(short, long)]
         force: bool,
     },
 
-    /// Enable an MCP server
+    /// Enable MCP servers
     Enable {
-        /// Server name
-        name: String,
+        #[command(flatten)]
+        selection: ServerSelection,
+
+        /// Skip confirmation
+        #[arg(short, long)]
+        force: bool,
     },
 
-    /// Disable an MCP server
+    /// Disable MCP servers
     Disable {
-        /// Server name
-        name: String,
+        #[command(flatten)]
+        selection: ServerSelection,
+
+        /// Skip confirmation
+        #[arg(short, long)]
+        force: bool,
+    },
+
+    /// Check servers.json (and the project overlay) for mistakes
+    Validate {
+        /// File to check instead
+        file: Option<PathBuf>,
+    },
+
+    /// Print the JSON Schema for servers.json
+    Schema,
+
+    /// Rewrite servers.json (and the project overlay) in the current format
+    Migrate {
+        /// Dry run - show the changes without writing
+        #[arg(short, long)]
+        dry_run: bool,
+
+        /// Write without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
     },
 
     /// Show server details
@@ -138,6 +285,41 @@ enum Commands {
         action: EnvCommands,
     },
 
+    /// Show a server's log
+    Logs {
+        /// Server name
+        #[arg(required_unless_present = "daemon")]
+        name: Option<String>,
+
+        /// Show the daemon's log instead
+        #[arg(long, conflicts_with = "name")]
+        daemon: bool,
+
+        /// Keep printing lines as they are written
+        #[arg(short, long)]
+        follow: bool,
+
+        /// Only lines newer than a duration (30s, 10m, 2h, 1d) or a time
+        #[arg(long, value_name = "WHEN")]
+        since: Option<String>,
+
+        /// Only the last N lines
+        #[arg(short = 'n', long, value_name = "N")]
+        lines: Option<usize>,
+    },
+
+    /// Switch between named sets of servers
+    Profile {
+        #[command(subcommand)]
+        action: ProfileCommands,
+    },
+
+    /// Keep shared server instances running in the background
+    Daemon {
+        #[command(subcommand)]
+        action: DaemonCommands,
+    },
+
     /// Show paths and configuration
     Info,
 
@@ -149,6 +331,258 @@ enum Commands {
     },
 }
 
+/// Which servers `list`, `enable`, `disable` and `remove` act on. Options
+/// combine; a repeated `--tag` matches servers with any of the tags.
+#[derive(clap::Args, Default)]
+struct ServerFilter {
+    /// Only servers with this tag (repeatable)
+    #[arg(long = "tag", value_name = "TAG")]
+    tags: Vec<String>,
+
+    /// Only servers on this runtime (node, bun, ...)
+    #[arg(long)]
+    runtime: Option<String>,
+
+    /// Only servers of this type
+    #[arg(long = "type", value_enum)]
+    server_type: Option<ServerType>,
+
+    /// Only servers whose name, description or tags contain this text
+    #[arg(short, long)]
+    search: Option<String>,
+}
+
+impl ServerFilter {
+    fn is_empty(&self) -> bool {
+        self.tags.is_empty() && self.runtime.is_none() && self.server_type.is_none() && self.search.is_none()
+    }
+
+    fn matches(&self, name: &str, server: &Server) -> bool {
+        let search = self.search.as_deref().map(str::to_lowercase);
+        (self.tags.is_empty() || self.tags.iter().any(|tag| server.tags.contains(tag)))
+            && self.runtime.as_deref().is_none_or(|runtime| server.kind_label() == runtime)
+            && self.server_type.is_none_or(|server_type| ServerType::of(&server.kind) == server_type)
+            && search.is_none_or(|search| {
+                name.to_lowercase().contains(&search)
+                    || server.description.as_deref().unwrap_or_default().to_lowercase().contains(&search)
+                    || server.tags.iter().any(|tag| tag.to_lowercase().contains(&search))
+            })
+    }
+}
+
+/// Servers picked by name or glob, by filter options, or all of them.
+#[derive(clap::Args)]
+struct ServerSelection {
+    /// Server names or glob patterns (`*-thinking`)
+    names: Vec<String>,
+
+    /// Every server (narrowed by the filter options)
+    #[arg(long, conflicts_with = "names")]
+    all: bool,
+
+    #[command(flatten)]
+    filter: ServerFilter,
+}
+
+impl ServerSelection {
+    /// Names of the selected servers, in name order. Every name or
+    /// pattern must match something.
+    fn select(&self, config: &ServersConfig) -> Result<Vec<String>> {
+        if self.names.is_empty() && !self.all && self.filter.is_empty() {
+            anyhow::bail!("Name servers or patterns, use filter options (--tag, --runtime, --type, --search), or pass --all");
+        }
+        let mut named = BTreeSet::new();
+        for pattern in &self.names {
+            let matched: Vec<&String> =
+                config.servers.keys().filter(|name| glob_match(pattern, name)).collect();
+            if matched.is_empty() {
+                if pattern.contains(['*', '?']) {
+                    anyhow::bail!("No server matches '{}'", pattern);
+                }
+                anyhow::bail!("Server '{}' not found", pattern);
+            }
+            named.extend(matched);
+        }
+
+        let selected: Vec<String> = config
+            .servers
+            .iter()
+            .filter(|(name, _)| self.names.is_empty() || named.contains(name))
+            .filter(|(name, server)| self.filter.matches(name, server))
+            .map(|(name, _)| name.clone())
+            .collect();
+        if selected.is_empty() {
+            anyhow::bail!("No servers match");
+        }
+        Ok(selected)
+    }
+}
+
+/// Shell-style match: `*` is any run of characters, `?` any one.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let (pattern, text): (Vec<char>, Vec<char>) = (pattern.chars().collect(), text.chars().collect());
+    let (mut p, mut t) = (0, 0);
+    // Position after the last `*` and where in `text` it started matching
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p + 1, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_t)) => {
+                    // Let the `*` swallow one more character
+                    p = star_p;
+                    t = star_t + 1;
+                    backtrack = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum ListSort {
+    Name,
+    Runtime,
+    Type,
+    Status,
+    /// By first tag, grouping servers of a kind
+    Tag,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum ListColumn {
+    Name,
+    Type,
+    Runtime,
+    Status,
+    Tags,
+    Description,
+}
+
+impl ListColumn {
+    fn header(self) -> &'static str {
+        match self {
+            ListColumn::Name => "Name",
+            ListColumn::Type => "Type",
+            ListColumn::Runtime => "Runtime",
+            ListColumn::Status => "Status",
+            ListColumn::Tags => "Tags",
+            ListColumn::Description => "Description",
+        }
+    }
+}
+
+/// Server settings `add` and `edit` take as flags.
+#[derive(clap::Args, Default)]
+struct ServerFields {
+    /// Server type (default: implied by --url, --command or --image, else runtime)
+    #[arg(long = "type", value_enum)]
+    server_type: Option<ServerType>,
+
+    /// Runtime (node, bun, deno, npx, bunx, or one from servers.json `runtimes`)
+    #[arg(short, long)]
+    runtime: Option<String>,
+
+    /// Path to server entry point
+    #[arg(short, long)]
+    path: Option<String>,
+
+    /// Executable of a native server
+    #[arg(long)]
+    command: Option<String>,
+
+    /// Endpoint of a remote server
+    #[arg(long)]
+    url: Option<String>,
+
+    /// HTTP header of a remote server; values may use ${VAR} (repeatable)
+    #[arg(long = "header", value_name = "NAME=VALUE", value_parser = parse_header)]
+    headers: Option<Vec<(String, String)>>,
+
+    /// Image of a docker server
+    #[arg(long)]
+    image: Option<String>,
+
+    /// Option for `docker run` (repeatable)
+    #[arg(long = "docker-arg", value_name = "ARG", allow_hyphen_values = true)]
+    docker_args: Option<Vec<String>>,
+
+    /// Argument passed to the server (repeatable)
+    #[arg(long = "arg", value_name = "ARG", allow_hyphen_values = true)]
+    args: Option<Vec<String>>,
+
+    /// Description
+    #[arg(short, long)]
+    description: Option<String>,
+
+    /// Tool names (comma-separated)
+    #[arg(short, long)]
+    tools: Option<String>,
+
+    /// Environment variables required (comma-separated, NAME or NAME=ENV_NAME)
+    #[arg(short, long)]
+    env: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum ServerType {
+    Runtime,
+    Native,
+    Remote,
+    Docker,
+}
+
+impl ServerType {
+    fn of(kind: &ServerKind) -> Self {
+        match kind {
+            ServerKind::Runtime(_) => ServerType::Runtime,
+            ServerKind::Native(_) => ServerType::Native,
+            ServerKind::Remote(_) => ServerType::Remote,
+            ServerKind::Docker(_) => ServerType::Docker,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            ServerType::Runtime => "runtime",
+            ServerType::Native => "native",
+            ServerType::Remote => "remote",
+            ServerType::Docker => "docker",
+        }
+    }
+}
+
+fn parse_header(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((name, value)) if !name.trim().is_empty() => Ok((name.trim().to_string(), value.to_string())),
+        _ => Err(format!("expected NAME=VALUE, got '{}'", s)),
+    }
+}
+
+fn target_names() -> clap::builder::PossibleValuesParser {
+    TARGETS.iter().map(|t| t.name).collect::<Vec<_>>().into()
+}
+
+/// How `gen` fills the per-server env block of client configs.
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum EnvMode {
+    /// No env block
+    None,
+    /// Plaintext values from .env
+    Values,
+    /// References the client expands from its own environment
+    Placeholders,
+}
+
 #[derive(Subcommand)]
 enum EnvCommands {
     /// List environment variables
@@ -168,104 +602,599 @@ enum EnvCommands {
         key: String,
     },
 
+    /// Check that enabled servers have the variables they need
+    Check,
+
+    /// Move values from .env into the secret store set in servers.json
+    Migrate {
+        /// Variables to move (default: all)
+        keys: Vec<String>,
+
+        /// Dry run - show what would be moved
+        #[arg(short, long)]
+        dry_run: bool,
+    },
+
     /// Edit .env file in default editor
     Edit,
 }
 
+#[derive(Subcommand)]
+enum ProfileCommands {
+    /// List profiles
+    List,
+
+    /// Make a profile active
+    Use {
+        /// Profile name
+        name: String,
+    },
+
+    /// Go back to the `disabled` flags of each server
+    Clear,
+
+    /// Show the servers a profile selects (default: the active one)
+    Show {
+        /// Profile name
+        name: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum DaemonCommands {
+    /// Start the daemon and the enabled servers
+    Start {
+        /// Stay in the foreground instead of detaching
+        #[arg(long)]
+        foreground: bool,
+    },
+
+    /// Stop the daemon and the servers it runs
+    Stop,
+
+    /// Show the servers the daemon runs
+    Status,
+}
+
 // ============================================================================
 // Data Structures
 // ============================================================================
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields, title = "MCP servers", description = "Server definitions for the mcp CLI")]
 struct ServersConfig {
     #[serde(rename = "$schema", skip_serializing_if = "Option::is_none")]
     schema: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     description: Option<String>,
+    /// Runtime definitions, merged over the built-in ones
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    runtimes: BTreeMap<String, RuntimeDef>,
+    /// Clients `gen` writes by default (see `mcp gen --help`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    targets: Option<Vec<String>>,
+    /// Servers added to client configs by hand that `gen` should keep
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    unmanaged: Vec<String>,
+    /// Named server sets: server names or `tag:<tag>` queries
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    profiles: BTreeMap<String, Vec<String>>,
+    /// Active profile (`mcp profile use`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    profile: Option<String>,
+    /// Where `mcp env` keeps values (default: plaintext .env)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    secrets: Option<SecretsConfig>,
+    #[serde(default)]
     servers: BTreeMap<String, Server>,
+    /// Servers that came from the project overlay
+    #[serde(skip)]
+    project: BTreeSet<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct Server {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    runtime: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    path: Option<String>,
+impl ServersConfig {
+    fn enabled_targets(&self) -> Vec<String> {
+        match &self.targets {
+            Some(targets) => targets.clone(),
+            None => DEFAULT_TARGETS.iter().map(|t| t.to_string()).collect(),
+        }
+    }
+
+    /// Layer a project overlay over this config. Its servers, runtimes and
+    /// profiles replace same-named ones whole; `targets` and `profile` win
+    /// when set. `secrets` stays global.
+    fn merge_overlay(&mut self, overlay: ServersConfig) {
+        self.runtimes.extend(overlay.runtimes);
+        self.profiles.extend(overlay.profiles);
+        self.unmanaged.extend(overlay.unmanaged);
+        if overlay.targets.is_some() {
+            self.targets = overlay.targets;
+        }
+        if overlay.profile.is_some() {
+            self.profile = overlay.profile;
+        }
+        self.project.extend(overlay.servers.keys().cloned());
+        self.servers.extend(overlay.servers);
+    }
+
+    /// Servers a profile selects, by name or `tag:<tag>` query.
+    fn profile_servers(&self, profile: &str) -> Result<BTreeSet<&str>> {
+        let entries = self.profiles.get(profile).with_context(|| {
+            let known: Vec<_> = self.profiles.keys().map(String::as_str).collect();
+            if known.is_empty() {
+                format!("Profile '{}' not found (servers.json has no profiles)", profile)
+            } else {
+                format!("Profile '{}' not found (known: {})", profile, known.join(", "))
+            }
+        })?;
+
+        let mut selected = BTreeSet::new();
+        for entry in entries {
+            if let Some(tag) = entry.strip_prefix("tag:") {
+                selected.extend(
+                    self.servers
+                        .iter()
+                        .filter(|(_, s)| s.tags.iter().any(|t| t == tag))
+                        .map(|(name, _)| name.as_str()),
+                );
+            } else {
+                let (name, _) = self
+                    .servers
+                    .get_key_value(entry)
+                    .with_context(|| format!("Profile '{}' lists unknown server '{}'", profile, entry))?;
+                selected.insert(name.as_str());
+            }
+        }
+        Ok(selected)
+    }
+
+    /// Enable exactly the servers of `profile`, or of the active profile
+    /// when None. Only for reading: saving afterwards would persist the
+    /// profile into each server's `disabled` flag.
+    fn apply_profile(&mut self, profile: Option<&str>) -> Result<()> {
+        let Some(profile) = profile.or(self.profile.as_deref()) else {
+            return Ok(());
+        };
+        let selected: BTreeSet<String> = self
+            .profile_servers(profile)?
+            .into_iter()
+            .map(String::from)
+            .collect();
+        for (name, server) in &mut self.servers {
+            server.disabled = !selected.contains(name);
+        }
+        Ok(())
+    }
+}
+
+/// How to launch servers of one runtime. Templates may use `{path}` (the
+/// server path, joined onto `module_root` when set), `{args}` (server and
+/// extra arguments; appended at the end when absent), `{home}`, `{mcp_dir}`,
+/// and the detected `{node_bin}`, `{node_modules}`, `{bun_bin}` and
+/// `{bun_modules}` locations.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+struct RuntimeDef {
+    program: String,
+    #[serde(default)]
+    args: Vec<String>,
+    /// Directories prepended to PATH
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    path: Vec<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    args: Option<Vec<String>>,
+    module_root: Option<String>,
+}
+
+impl RuntimeDef {
+    fn new(program: &str, args: &[&str], path: &[&str], module_root: Option<&str>) -> Self {
+        Self {
+            program: program.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            path: path.iter().map(|p| p.to_string()).collect(),
+            module_root: module_root.map(String::from),
+        }
+    }
+}
+
+/// A server: how it runs, selected by `type`, and the settings every kind shares.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+struct Server {
+    #[serde(flatten)]
+    kind: ServerKind,
     #[serde(skip_serializing_if = "Option::is_none")]
     description: Option<String>,
+    /// Tools clients may call without asking
     #[serde(skip_serializing_if = "Option::is_none")]
     tools: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    env: Option<Vec<String>>,
+    env: Option<EnvSpec>,
+    /// Pass the whole environment and .env through instead of only `env`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    inherit_env: bool,
     #[serde(default)]
     disabled: bool,
+    /// Labels for `tag:<tag>` profile queries
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
     #[serde(default, skip_serializing_if = "std::ops::Not::not")]
     opencode_only: bool,
-    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
-    server_type: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    url: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    command: Option<String>,
+    /// Never share through the daemon; every client gets its own process
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    standalone: bool,
+    /// Also write JSON-RPC traffic to the server log
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    log_traffic: bool,
 }
 
-#[derive(Tabled)]
-struct ServerRow {
-    #[tabled(rename = "Name")]
-    name: String,
-    #[tabled(rename = "Runtime")]
-    runtime: String,
-    #[tabled(rename = "Status")]
-    status: String,
-    #[tabled(rename = "Description")]
-    description: String,
-}
+impl Server {
+    fn new(kind: ServerKind) -> Self {
+        Self {
+            kind,
+            description: None,
+            tools: None,
+            env: None,
+            inherit_env: false,
+            disabled: false,
+            tags: Vec::new(),
+            opencode_only: false,
+            standalone: false,
+            log_traffic: false,
+        }
+    }
 
-// ============================================================================
-// Path Management
+    /// The runtime of runtime servers, the type of the others.
+    fn kind_label(&self) -> &str {
+        match &self.kind {
+            ServerKind::Runtime(runtime) => &runtime.runtime,
+            ServerKind::Native(_) => "native",
+            ServerKind::Remote(_) => "remote",
+            ServerKind::Docker(_) => "docker",
+        }
+    }
+
+    fn remote(&self) -> Option<&RemoteServer> {
+        match &self.kind {
+            ServerKind::Remote(remote) => Some(remote),
+            ServerKind::Runtime(_) | ServerKind::Native(_) | ServerKind::Docker(_) => None,
+        }
+    }
+}
+
+/// How a server is started or reached, selected by `type`. Entries without
+/// a `type` predate it and are runtime servers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum ServerKind {
+    /// A script launched by a runtime from the registry
+    Runtime(RuntimeServer),
+    /// An executable run as is
+    Native(NativeServer),
+    /// An MCP endpoint reached over HTTP
+    Remote(RemoteServer),
+    /// A container image run with `docker run -i --rm`
+    Docker(DockerServer),
+}
+
+impl ServerKind {
+    /// Arguments for the server itself.
+    fn args(&self) -> &[String] {
+        match self {
+            ServerKind::Runtime(runtime) => &runtime.args,
+            ServerKind::Native(native) => &native.args,
+            ServerKind::Docker(docker) => &docker.args,
+            ServerKind::Remote(_) => &[],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+struct RuntimeServer {
+    /// Runtime from the registry
+    #[serde(default = "default_runtime")]
+    runtime: String,
+    /// Entry point, relative to the runtime's module root
+    path: String,
+    /// Package version or range `mcp install` resolves (default: latest)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    args: Vec<String>,
+}
+
+fn default_runtime() -> String {
+    "node".to_string()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+struct NativeServer {
+    /// Executable, looked up on PATH
+    command: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    args: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+struct RemoteServer {
+    url: String,
+    /// Wire protocol; guessed from the URL when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    transport: Option<Transport>,
+    /// HTTP headers; `${VAR}` expands from .env
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    headers: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    oauth: Option<OAuthConfig>,
+}
+
+impl RemoteServer {
+    /// The transport, defaulting to legacy SSE for `.../sse` URLs.
+    fn transport(&self) -> Transport {
+        self.transport.unwrap_or_else(|| {
+            if self.url.trim_end_matches('/').ends_with("/sse") {
+                Transport::Sse
+            } else {
+                Transport::Http
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+struct DockerServer {
+    image: String,
+    /// `docker run` options placed before the image (volumes, network, ...)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    docker_args: Vec<String>,
+    /// Arguments for the image's entry point
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    args: Vec<String>,
+}
+
+/// Environment variables a server needs: either a list of names, or a map
+/// from the name the server sees to the name it is stored under in .env.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum EnvSpec {
+    List(Vec<String>),
+    Map(BTreeMap<String, String>),
+}
+
+impl EnvSpec {
+    /// `(server variable, .env variable)` pairs.
+    fn bindings(&self) -> Vec<(&str, &str)> {
+        match self {
+            EnvSpec::List(names) => names.iter().map(|n| (n.as_str(), n.as_str())).collect(),
+            EnvSpec::Map(map) => map.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect(),
+        }
+    }
+}
+
+impl std::fmt::Display for EnvSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let names: Vec<_> = self
+            .bindings()
+            .into_iter()
+            .map(|(name, source)| {
+                if name == source {
+                    name.to_string()
+                } else {
+                    format!("{} <- {}", name, source)
+                }
+            })
+            .collect();
+        write!(f, "{}", names.join(", "))
+    }
+}
+
+/// Where variable values are kept, selected by `backend`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "backend", rename_all = "lowercase")]
+#[schemars(deny_unknown_fields)]
+enum SecretsConfig {
+    /// Plaintext in .env
+    Env,
+    /// An age-encrypted file, unlocked by a key file (no prompts)
+    Age {
+        /// Encrypted store (default: secrets.age in the MCP directory)
+        #[serde(skip_serializing_if = "Option::is_none")]
+        file: Option<PathBuf>,
+        /// age identity, created on first use (default: age.key in the MCP directory)
+        #[serde(skip_serializing_if = "Option::is_none")]
+        identity: Option<PathBuf>,
+    },
+    /// The OS credential store: Credential Manager, Keychain or Secret Service
+    Keyring {
+        /// Service the entries are filed under (default: mcp)
+        #[serde(skip_serializing_if = "Option::is_none")]
+        service: Option<String>,
+    },
+}
+
+/// Wire protocol of a remote server.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+enum Transport {
+    /// Streamable HTTP: one endpoint, POST per message
+    Http,
+    /// Legacy HTTP+SSE: GET an event stream, POST to the endpoint it names
+    Sse,
+}
+
+impl Transport {
+    fn as_str(self) -> &'static str {
+        match self {
+            Transport::Http => "http",
+            Transport::Sse => "sse",
+        }
+    }
+}
+
+/// OAuth client settings for a remote server. Clients that support OAuth get
+/// these in their config; `mcp run` uses the client-credentials grant when
+/// `token_url` is set.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+struct OAuthConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    client_id: Option<String>,
+    /// .env variable holding the client secret
+    #[serde(skip_serializing_if = "Option::is_none")]
+    client_secret_env: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scopes: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token_url: Option<String>,
+}
+
+#[derive(Tabled)]
+struct DaemonRow {
+    #[tabled(rename = "Name")]
+    name: String,
+    #[tabled(rename = "Status")]
+    status: String,
+    #[tabled(rename = "PID")]
+    pid: String,
+    #[tabled(rename = "Uptime")]
+    uptime: String,
+    #[tabled(rename = "Restarts")]
+    restarts: u32,
+    #[tabled(rename = "Clients")]
+    clients: usize,
+}
+
+// ============================================================================
+// Path Management
 // ============================================================================
 
 struct McpPaths {
+    home: PathBuf,
     mcp_dir: PathBuf,
+    /// Whether `--mcp-dir` was given, so generated launchers must pass it on
+    custom_mcp_dir: bool,
     servers_json: PathBuf,
+    /// `.mcp/servers.json` overlay of the project around the current directory
+    project_json: Option<PathBuf>,
+    /// Resolved package versions, next to servers.json
+    lock_json: PathBuf,
     env_file: PathBuf,
-    #[allow(dead_code)]
-    node_version: PathBuf,
-    cursor_config: PathBuf,
-    opencode_config: PathBuf,
-    fnm_node_modules: PathBuf,
-    bun_node_modules: PathBuf,
+    logs_dir: PathBuf,
+    /// Packages `mcp install` put in place
+    installed_json: PathBuf,
+    /// Port, pid and token of the running daemon
+    daemon_file: PathBuf,
+    /// The daemon's own output, apart from server logs so no server name
+    /// can clash with it
+    daemon_log: PathBuf,
+    node_roots: Vec<ModuleRoot>,
+    bun_root: ModuleRoot,
 }
 
 impl McpPaths {
-    fn new(mcp_dir: Option<PathBuf>) -> Result<Self> {
+    fn new(mcp_dir: Option<PathBuf>, project_dir: Option<PathBuf>) -> Result<Self> {
         let home = dirs::home_dir().context("Could not determine home directory")?;
+        let custom_mcp_dir = mcp_dir.is_some();
         let mcp_dir = mcp_dir.unwrap_or_else(|| home.join(".mcp"));
 
+        let servers_json = mcp_dir.join("servers.json");
+        let project_json = match project_dir {
+            Some(dir) => {
+                let overlay = dir.join(".mcp").join("servers.json");
+                if !overlay.is_file() {
+                    anyhow::bail!("No .mcp/servers.json in {}", dir.display());
+                }
+                Some(overlay)
+            }
+            None => std::env::current_dir()
+                .ok()
+                .and_then(|cwd| find_project_overlay(&cwd, &servers_json)),
+        };
+
         Ok(Self {
-            servers_json: mcp_dir.join("servers.json"),
+            project_json,
+            servers_json,
+            lock_json: mcp_dir.join("servers.lock.json"),
             env_file: mcp_dir.join(".env"),
-            node_version: mcp_dir.join(".node-version"),
-            cursor_config: home.join(".cursor").join("mcp.json"),
-            opencode_config: home.join(".config").join("opencode").join("opencode.json"),
-            fnm_node_modules: home
-                .join("AppData")
-                .join("Roaming")
-                .join("fnm")
-                .join("aliases")
-                .join("default")
-                .join("node_modules"),
-            bun_node_modules: home
-                .join(".bun")
-                .join("install")
-                .join("global")
-                .join("node_modules"),
+            logs_dir: mcp_dir.join("logs"),
+            installed_json: mcp_dir.join("installed.json"),
+            daemon_file: mcp_dir.join("daemon.json"),
+            daemon_log: mcp_dir.join("daemon.log"),
+            node_roots: detect_node_roots(&home, &mcp_dir.join(".node-version")),
+            bun_root: detect_bun_root(&home),
+            custom_mcp_dir,
             mcp_dir,
+            home,
         })
     }
 
+    /// Per-user config directory (AppData\\Roaming, Library/Application
+    /// Support or ~/.config)
+    fn config_dir(&self) -> PathBuf {
+        dirs::config_dir().unwrap_or_else(|| self.home.join(".config"))
+    }
+
+    /// Command and arguments a client uses to launch `name` through `mcp run`.
+    /// The command is this binary's absolute path, since GUI clients often
+    /// start without the shell's PATH. Servers from a project overlay name
+    /// the project, as clients may start them from any directory.
+    fn launcher(&self, name: &str, project_root: Option<&Path>) -> (String, Vec<String>) {
+        let mut args = Vec::new();
+        if self.custom_mcp_dir {
+            args.push("--mcp-dir".to_string());
+            args.push(self.mcp_dir.to_string_lossy().to_string());
+        }
+        if let Some(root) = project_root {
+            args.push("--project-dir".to_string());
+            args.push(root.to_string_lossy().to_string());
+        }
+        args.push("run".to_string());
+        args.push(name.to_string());
+        let exe = std::env::current_exe()
+            .map(|exe| exe.to_string_lossy().to_string())
+            .unwrap_or_else(|_| "mcp".to_string());
+        (exe, args)
+    }
+
+    /// Find the first Node module root that has `path` installed.
+    fn find_node_script(&self, path: &str) -> Result<(&ModuleRoot, PathBuf)> {
+        self.node_roots
+            .iter()
+            .map(|root| (root, root.script(path)))
+            .find(|(_, script)| script.exists())
+            .with_context(|| {
+                let searched: Vec<_> = self
+                    .node_roots
+                    .iter()
+                    .map(|root| format!("  {} ({})", root.node_modules.display(), root.manager))
+                    .collect();
+                format!(
+                    "'{}' is not installed in any global node_modules. Searched:\n{}",
+                    path,
+                    searched.join("\n")
+                )
+            })
+    }
+
+    /// Directory holding the project's `.mcp` overlay directory.
+    fn project_root(&self) -> Option<&Path> {
+        self.project_json.as_deref()?.parent()?.parent()
+    }
+
+    /// Bun's executable, from its install directory or else PATH.
+    fn bun_binary(&self) -> PathBuf {
+        let bun = self.bun_root.bin_dir.join(if cfg!(windows) { "bun.exe" } else { "bun" });
+        if bun.exists() {
+            bun
+        } else {
+            "bun".into()
+        }
+    }
+
+    /// `~/.mcp/logs/<name>.log`
+    fn server_log(&self, name: &str) -> PathBuf {
+        self.logs_dir.join(format!("{}.log", name))
+    }
+
     fn ensure_mcp_dir(&self) -> Result<()> {
         if !self.mcp_dir.exists() {
             fs::create_dir_all(&self.mcp_dir)?;
@@ -274,667 +1203,8122 @@ impl McpPaths {
     }
 }
 
+/// The nearest `.mcp/servers.json` from `start` upwards, stopping at the
+/// global one.
+fn find_project_overlay(start: &Path, global: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(".mcp").join("servers.json"))
+        .take_while(|candidate| candidate != global)
+        .find(|candidate| candidate.is_file())
+}
+
 // ============================================================================
-// Config Loading
+// Runtime Resolution
 // ============================================================================
 
-fn load_servers(paths: &McpPaths) -> Result<ServersConfig> {
-    let content = fs::read_to_string(&paths.servers_json)
-        .with_context(|| format!("Failed to read {}", paths.servers_json.display()))?;
-    serde_json::from_str(&content).context("Failed to parse servers.json")
+/// Where a Node-compatible runtime keeps its global packages.
+struct ModuleRoot {
+    /// Version manager or runtime that owns this root (fnm, volta, nvm, ...)
+    manager: &'static str,
+    /// Directory holding the runtime executables, prepended to PATH
+    bin_dir: PathBuf,
+    /// Global node_modules directory
+    node_modules: PathBuf,
+    /// Volta installs every global package under its own prefix
+    per_package: bool,
 }
 
-fn save_servers(paths: &McpPaths, config: &ServersConfig) -> Result<()> {
-    let content = serde_json::to_string_pretty(config)?;
-    fs::write(&paths.servers_json, content)?;
-    Ok(())
-}
+impl ModuleRoot {
+    /// Layout of a Node install prefix: executables and node_modules sit
+    /// directly in the prefix on Windows, under bin/ and lib/ elsewhere.
+    fn node_prefix(manager: &'static str, prefix: PathBuf) -> Self {
+        let (bin_dir, node_modules) = if cfg!(windows) {
+            (prefix.clone(), prefix.join("node_modules"))
+        } else {
+            (prefix.join("bin"), prefix.join("lib").join("node_modules"))
+        };
+        Self {
+            manager,
+            bin_dir,
+            node_modules,
+            per_package: false,
+        }
+    }
 
-fn load_env(paths: &McpPaths) -> BTreeMap<String, String> {
-    let mut env = BTreeMap::new();
-    if let Ok(content) = fs::read_to_string(&paths.env_file) {
-        for line in content.lines() {
-            let line = line.trim();
-            if line.is_empty() || line.starts_with('#') {
-                continue;
-            }
-            if let Some((key, value)) = line.split_once('=') {
-                env.insert(key.trim().to_string(), value.trim().to_string());
-            }
+    fn script(&self, path: &str) -> PathBuf {
+        if !self.per_package {
+            return self.node_modules.join(path);
         }
+        Self::node_prefix(self.manager, self.node_modules.join(package_name(path)))
+            .node_modules
+            .join(path)
     }
-    env
 }
 
-fn save_env(paths: &McpPaths, env: &BTreeMap<String, String>) -> Result<()> {
-    let mut content = String::from("# MCP Server Environment Variables\n\n");
-    for (key, value) in env {
-        content.push_str(&format!("{}={}\n", key, value));
+/// npm package name of an entry point (`@scope/pkg/dist/index.js` -> `@scope/pkg`).
+fn package_name(path: &str) -> &str {
+    let segments = if path.starts_with('@') { 2 } else { 1 };
+    match path.match_indices(['/', '\\']).nth(segments - 1) {
+        Some((i, _)) => &path[..i],
+        None => path,
     }
-    fs::write(&paths.env_file, content)?;
-    Ok(())
 }
 
-// ============================================================================
-// Commands
-// ============================================================================
-
-fn cmd_list(paths: &McpPaths, enabled: bool, disabled: bool, json: bool) -> Result<()> {
-    let config = load_servers(paths)?;
-
-    let servers: Vec<_> = config
-        .servers
-        .iter()
-        .filter(|(_, s)| {
-            if enabled {
-                !s.disabled
-            } else if disabled {
-                s.disabled
-            } else {
-                true
+/// Candidate Node module roots in lookup order: the fnm or nvm install
+/// matching the `.node-version` pin, fnm, Volta, nvm, then whatever `node`
+/// is on PATH.
+fn detect_node_roots(home: &Path, node_version: &Path) -> Vec<ModuleRoot> {
+    let mut roots = Vec::new();
+
+    let fnm_dir = std::env::var_os("FNM_DIR")
+        .map(PathBuf::from)
+        .or_else(|| Some(home.join(".fnm")).filter(|legacy| legacy.exists()))
+        .or_else(|| dirs::data_dir().map(|data| data.join("fnm")));
+    let nvm_dir = std::env::var_os("NVM_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| home.join(".nvm"));
+
+    let pin = fs::read_to_string(node_version).unwrap_or_default();
+    let pin = pin.trim();
+    if !pin.is_empty() {
+        if let Some(fnm_dir) = &fnm_dir {
+            let versions = fnm_dir.join("node-versions");
+            if let Some(version) = installed_version(&versions, pin) {
+                roots.push(ModuleRoot::node_prefix(
+                    "fnm",
+                    versions.join(version).join("installation"),
+                ));
             }
-        })
-        .collect();
-
-    if json {
-        let map: BTreeMap<_, _> = servers.into_iter().collect();
-        println!("{}", serde_json::to_string_pretty(&map)?);
-        return Ok(());
+        }
+        let versions = nvm_dir.join("versions").join("node");
+        if let Some(version) = installed_version(&versions, pin) {
+            roots.push(ModuleRoot::node_prefix("nvm", versions.join(version)));
+        }
     }
 
-    let rows: Vec<ServerRow> = servers
-        .iter()
-        .map(|(name, server)| {
-            let runtime = if server.server_type.as_deref() == Some("remote") {
-                "remote".to_string()
-            } else if server.server_type.as_deref() == Some("native") {
-                "native".to_string()
-            } else {
-                server.runtime.clone().unwrap_or_else(|| "node".to_string())
-            };
+    if let Some(fnm_dir) = fnm_dir {
+        roots.push(ModuleRoot::node_prefix(
+            "fnm",
+            fnm_dir.join("aliases").join("default"),
+        ));
+    }
 
-            let status = if server.disabled {
-                "disabled".red().to_string()
-            } else {
-                "enabled".green().to_string()
-            };
+    let volta_dir = std::env::var_os("VOLTA_HOME").map(PathBuf::from).or_else(|| {
+        if cfg!(windows) {
+            dirs::data_local_dir().map(|local| local.join("Volta"))
+        } else {
+            Some(home.join(".volta"))
+        }
+    });
+    if let Some(volta_dir) = volta_dir {
+        roots.push(ModuleRoot {
+            manager: "volta",
+            bin_dir: volta_dir.join("bin"),
+            node_modules: volta_dir.join("tools").join("image").join("packages"),
+            per_package: true,
+        });
+    }
 
-            ServerRow {
-                name: name.to_string(),
-                runtime,
-                status,
-                description: server
-                    .description
-                    .clone()
-                    .unwrap_or_default()
-                    .chars()
-                    .take(40)
-                    .collect(),
-            }
-        })
-        .collect();
+    if let Some(symlink) = std::env::var_os("NVM_SYMLINK") {
+        // nvm-windows points a symlink at the active version
+        roots.push(ModuleRoot::node_prefix("nvm", PathBuf::from(symlink)));
+    } else if let Some(version) = nvm_default_version(&nvm_dir) {
+        roots.push(ModuleRoot::node_prefix(
+            "nvm",
+            nvm_dir.join("versions").join("node").join(version),
+        ));
+    }
 
-    if rows.is_empty() {
-        println!("{}", "No servers found.".yellow());
-    } else {
-        let table = Table::new(rows).to_string();
-        println!("{}", table);
-        println!(
-            "\n{} servers total",
-            config.servers.len().to_string().cyan()
-        );
+    if let Some(node) = find_in_path("node") {
+        let bin_dir = node.parent().map(Path::to_path_buf).unwrap_or_default();
+        let prefix = if cfg!(windows) {
+            bin_dir
+        } else {
+            bin_dir.parent().map(Path::to_path_buf).unwrap_or(bin_dir)
+        };
+        roots.push(ModuleRoot::node_prefix("system", prefix));
     }
 
-    Ok(())
+    roots
 }
 
-fn cmd_run(paths: &McpPaths, name: &str, extra_args: &[String]) -> Result<()> {
-    let config = load_servers(paths)?;
-    let server = config
-        .servers
-        .get(name)
-        .with_context(|| format!("Server '{}' not found", name))?;
-
-    // Load environment variables
-    let env_vars = load_env(paths);
-
-    // Determine how to run the server
-    if server.server_type.as_deref() == Some("remote") {
-        anyhow::bail!("Cannot run remote server '{}' - it connects via URL", name);
+fn detect_bun_root(home: &Path) -> ModuleRoot {
+    let bun_dir = std::env::var_os("BUN_INSTALL")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| home.join(".bun"));
+    ModuleRoot {
+        manager: "bun",
+        bin_dir: bun_dir.join("bin"),
+        node_modules: bun_dir.join("install").join("global").join("node_modules"),
+        per_package: false,
     }
+}
 
-    if server.server_type.as_deref() == Some("native") {
-        // Native binary
-        let cmd = server
-            .command
-            .as_ref()
-            .context("Native server missing 'command'")?;
-        let args = server.args.clone().unwrap_or_default();
+/// Resolve nvm's `default` alias (`20`, `v20.11.0`, `lts/*`, ...) to an
+/// installed version directory name.
+fn nvm_default_version(nvm_dir: &Path) -> Option<String> {
+    let alias = fs::read_to_string(nvm_dir.join("alias").join("default")).ok()?;
+    installed_version(&nvm_dir.join("versions").join("node"), alias.trim())
+}
 
-        let mut command = Command::new(cmd);
-        command.args(&args).args(extra_args);
+/// Newest version directory in `versions_dir` matching `wanted` (`20`,
+/// `v20.11.0`, or a named alias such as `lts/*`, which matches any).
+fn installed_version(versions_dir: &Path, wanted: &str) -> Option<String> {
+    let wanted = wanted.trim_start_matches('v');
+    let mut versions: Vec<String> = fs::read_dir(versions_dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .collect();
+    versions.sort_by_key(|v| version_key(v));
 
-        for (k, v) in &env_vars {
-            command.env(k, v);
-        }
+    // Named aliases (lts/*, node, stable) resolve to the newest install
+    let numeric = wanted.starts_with(|c: char| c.is_ascii_digit());
+    versions.into_iter().rev().find(|v| {
+        let v = v.trim_start_matches('v');
+        !numeric || v == wanted || v.starts_with(&format!("{}.", wanted))
+    })
+}
 
-        let status = command.status()?;
-        std::process::exit(status.code().unwrap_or(1));
-    }
+fn version_key(version: &str) -> Vec<u64> {
+    version
+        .trim_start_matches('v')
+        .split('.')
+        .map(|part| part.parse().unwrap_or(0))
+        .collect()
+}
 
-    // Runtime-based server (node, bun, deno, npx, bunx)
-    let runtime = server.runtime.as_deref().unwrap_or("node");
-    let path = server.path.as_ref().context("Server missing 'path'")?;
-    let server_args = server.args.clone().unwrap_or_default();
+fn find_in_path(program: &str) -> Option<PathBuf> {
+    let extensions: &[&str] = if cfg!(windows) { &["exe", "cmd"] } else { &[""] };
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path).find_map(|dir| {
+        extensions
+            .iter()
+            .map(|ext| dir.join(program).with_extension(ext))
+            .find(|candidate| candidate.is_file())
+    })
+}
 
-    let (program, args) = match runtime {
-        "node" => {
-            let script = paths.fnm_node_modules.join(path);
-            ("node".to_string(), vec![script.to_string_lossy().to_string()])
-        }
-        "bun" => {
-            let script = paths.bun_node_modules.join(path);
-            (
-                "bun".to_string(),
-                vec!["run".to_string(), script.to_string_lossy().to_string()],
-            )
-        }
-        "deno" => (
+/// PATH with `dirs` prepended, joined with the platform separator.
+fn prepend_path(dirs: &[PathBuf]) -> Result<std::ffi::OsString> {
+    let current = std::env::var_os("PATH").unwrap_or_default();
+    let joined = dirs
+        .iter()
+        .cloned()
+        .chain(std::env::split_paths(&current));
+    std::env::join_paths(joined).context("Invalid PATH entry")
+}
+
+/// Built-in runtimes, overridden by the `runtimes` section of servers.json.
+fn runtime_registry(config: &ServersConfig) -> BTreeMap<String, RuntimeDef> {
+    let mut registry = BTreeMap::from([
+        (
+            "node".to_string(),
+            RuntimeDef::new("node", &["{path}", "{args}"], &["{node_bin}"], Some("{node_modules}")),
+        ),
+        (
+            "bun".to_string(),
+            RuntimeDef::new("bun", &["run", "{path}", "{args}"], &["{bun_bin}"], Some("{bun_modules}")),
+        ),
+        (
             "deno".to_string(),
-            vec!["run".to_string(), "--allow-all".to_string(), path.clone()],
+            RuntimeDef::new("deno", &["run", "--allow-all", "{path}", "{args}"], &[], None),
         ),
-        "npx" => ("npx".to_string(), vec!["-y".to_string(), path.clone()]),
-        "bunx" => ("bunx".to_string(), vec![path.clone()]),
-        _ => anyhow::bail!("Unknown runtime: {}", runtime),
-    };
+        (
+            "npx".to_string(),
+            RuntimeDef::new("npx", &["-y", "{path}", "{args}"], &["{node_bin}"], None),
+        ),
+        (
+            "bunx".to_string(),
+            RuntimeDef::new("bunx", &["{path}", "{args}"], &["{bun_bin}"], None),
+        ),
+    ]);
+    registry.extend(config.runtimes.clone());
+    registry
+}
 
-    // For node runtime, we need to set up fnm PATH
-    let mut command = Command::new(&program);
-    command
-        .args(&args)
-        .args(&server_args)
-        .args(extra_args)
-        .stdin(Stdio::inherit())
-        .stdout(Stdio::inherit())
-        .stderr(Stdio::inherit());
+fn lookup_runtime<'a>(
+    registry: &'a BTreeMap<String, RuntimeDef>,
+    runtime: &str,
+) -> Result<&'a RuntimeDef> {
+    registry.get(runtime).with_context(|| {
+        let known: Vec<_> = registry.keys().map(String::as_str).collect();
+        format!(
+            "Unknown runtime '{}' (available: {})",
+            runtime,
+            known.join(", ")
+        )
+    })
+}
 
-    // Set environment variables
-    for (k, v) in &env_vars {
-        command.env(k, v);
-    }
+/// Program, arguments and PATH additions for a runtime-based server.
+struct RuntimeLaunch {
+    program: String,
+    args: Vec<String>,
+    path_dirs: Vec<PathBuf>,
+}
 
-    // For node, prepend fnm to PATH
-    if runtime == "node" || runtime == "npx" {
-        let fnm_path = paths
-            .fnm_node_modules
-            .parent()
-            .unwrap()
+fn resolve_runtime(
+    paths: &McpPaths,
+    def: &RuntimeDef,
+    path: &str,
+    args: &[String],
+) -> Result<RuntimeLaunch> {
+    // `{node_modules}` as a module root means "whichever Node install has it"
+    let mut node_root = None;
+    let script = match def.module_root.as_deref() {
+        Some("{node_modules}") => {
+            let (root, script) = paths.find_node_script(path)?;
+            node_root = Some(root);
+            script.to_string_lossy().to_string()
+        }
+        Some(root) => PathBuf::from(expand_template(paths, node_root, root))
+            .join(path)
             .to_string_lossy()
-            .to_string();
-        let current_path = std::env::var("PATH").unwrap_or_default();
-        command.env("PATH", format!("{};{}", fnm_path, current_path));
+            .to_string(),
+        None => path.to_string(),
+    };
+    let node_root = node_root.or_else(|| {
+        paths
+            .node_roots
+            .iter()
+            .find(|root| root.bin_dir.exists())
+    });
+
+    let mut launch_args = Vec::new();
+    for arg in &def.args {
+        if arg == "{args}" {
+            launch_args.extend(args.iter().cloned());
+        } else {
+            launch_args.push(expand_template(paths, node_root, &arg.replace("{path}", &script)));
+        }
+    }
+    if !def.args.iter().any(|arg| arg == "{args}") {
+        launch_args.extend(args.iter().cloned());
     }
 
-    let status = command.status()?;
-    std::process::exit(status.code().unwrap_or(1));
+    let path_dirs = def
+        .path
+        .iter()
+        .map(|dir| expand_template(paths, node_root, dir))
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .collect();
+
+    Ok(RuntimeLaunch {
+        program: expand_template(paths, node_root, &def.program),
+        args: launch_args,
+        path_dirs,
+    })
 }
 
-fn cmd_gen(paths: &McpPaths, cursor_only: bool, opencode_only: bool, dry_run: bool) -> Result<()> {
-    let config = load_servers(paths)?;
+fn expand_template(paths: &McpPaths, node_root: Option<&ModuleRoot>, template: &str) -> String {
+    let display = |path: &Path| path.to_string_lossy().to_string();
+    let node_bin = node_root.map(|root| display(&root.bin_dir)).unwrap_or_default();
+    let node_modules = node_root
+        .map(|root| display(&root.node_modules))
+        .unwrap_or_default();
+
+    template
+        .replace("{home}", &display(&paths.home))
+        .replace("{mcp_dir}", &display(&paths.mcp_dir))
+        .replace("{node_bin}", &node_bin)
+        .replace("{node_modules}", &node_modules)
+        .replace("{bun_bin}", &display(&paths.bun_root.bin_dir))
+        .replace("{bun_modules}", &display(&paths.bun_root.node_modules))
+}
 
-    let gen_cursor = !opencode_only;
-    let gen_opencode = !cursor_only;
+// ============================================================================
+// Config Validation
+// ============================================================================
 
-    if gen_cursor {
-        let cursor_config = generate_cursor_config(&config, paths);
-        let json = serde_json::to_string_pretty(&cursor_config)?;
+/// A mistake in servers.json, located by its JSON path (`servers.db.url`).
+struct ConfigError {
+    path: String,
+    message: String,
+}
 
-        if dry_run {
-            println!("{}", "=== Cursor Config ===".cyan());
-            println!("{}", json);
-        } else {
-            if let Some(parent) = paths.cursor_config.parent() {
-                fs::create_dir_all(parent)?;
-            }
-            fs::write(&paths.cursor_config, &json)?;
-            println!(
-                "{} {}",
-                "Wrote:".green(),
-                paths.cursor_config.display()
-            );
+impl ConfigError {
+    fn new(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            message: message.into(),
         }
     }
+}
 
-    if gen_opencode {
-        let opencode_config = generate_opencode_config(&config, paths);
-        let json = serde_json::to_string_pretty(&opencode_config)?;
-
-        if dry_run {
-            println!("{}", "=== OpenCode Config ===".cyan());
-            println!("{}", json);
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
         } else {
-            if let Some(parent) = paths.opencode_config.parent() {
-                fs::create_dir_all(parent)?;
-            }
-            fs::write(&paths.opencode_config, &json)?;
-            println!(
-                "{} {}",
-                "Wrote:".green(),
-                paths.opencode_config.display()
-            );
+            write!(f, "{}: {}", self.path, self.message)
         }
     }
+}
 
-    if !dry_run {
-        println!("{}", "Done!".green());
-    }
-
-    Ok(())
+fn invalid_config(file: &Path, errors: &[ConfigError]) -> anyhow::Error {
+    let lines: Vec<_> = errors.iter().map(|e| format!("  {}", e)).collect();
+    anyhow::anyhow!("{} is invalid:\n{}", file.display(), lines.join("\n"))
 }
 
-fn generate_cursor_config(
-    config: &ServersConfig,
-    paths: &McpPaths,
-) -> serde_json::Value {
-    let mut mcp_servers = serde_json::Map::new();
+/// JSON Schema for servers.json, generated from `ServersConfig`.
+fn servers_schema() -> serde_json::Value {
+    serde_json::to_value(schemars::schema_for!(ServersConfig)).unwrap_or_default()
+}
 
-    for (name, server) in &config.servers {
-        // Skip OpenCode-only servers
-        if server.opencode_only {
-            continue;
+/// Parse servers.json, reporting keys the schema does not know and values
+/// of the wrong type by their path. `then` checks whatever parsed, so all
+/// mistakes are reported at once.
+fn parse_servers(
+    content: &str,
+    then: impl FnOnce(ServersConfig) -> Result<ServersConfig, Vec<ConfigError>>,
+) -> Result<ServersConfig, Vec<ConfigError>> {
+    let mut value: serde_json::Value =
+        serde_json::from_str(content).map_err(|e| vec![ConfigError::new("", e.to_string())])?;
+    upgrade_legacy_servers(&mut value);
+
+    let schema = servers_schema();
+    let mut errors = Vec::new();
+    unknown_keys(&value, &schema, &schema, "", &mut errors);
+
+    match serde_path_to_error::deserialize(&value).map(then) {
+        Ok(Ok(config)) if errors.is_empty() => Ok(config),
+        Ok(Ok(_)) => Err(errors),
+        Ok(Err(more)) => {
+            errors.extend(more);
+            Err(errors)
         }
-
-        let mut entry = serde_json::Map::new();
-        entry.insert("disabled".to_string(), serde_json::json!(server.disabled));
-
-        if server.server_type.as_deref() == Some("remote") {
-            entry.insert("url".to_string(), serde_json::json!(server.url));
-        } else if server.server_type.as_deref() == Some("native") {
-            entry.insert("command".to_string(), serde_json::json!(server.command));
-            entry.insert("args".to_string(), serde_json::json!(server.args.clone().unwrap_or_default()));
-        } else {
-            let runtime = server.runtime.as_deref().unwrap_or("node");
-            let path = server.path.as_deref().unwrap_or("");
-            let spec = format!("{}:{}", runtime, path);
-            
-            let mut args = vec![
-                "/c".to_string(),
-                paths.mcp_dir.join("mcp-run.cmd").to_string_lossy().to_string(),
-                spec,
-            ];
-            if let Some(extra) = &server.args {
-                args.extend(extra.clone());
-            }
-
-            entry.insert("command".to_string(), serde_json::json!("cmd.exe"));
-            entry.insert("args".to_string(), serde_json::json!(args));
+        Err(e) => {
+            errors.push(ConfigError::new(e.path().to_string(), e.inner().to_string()));
+            Err(errors)
         }
-
-        let tools = server.tools.clone().unwrap_or_default();
-        entry.insert("alwaysAllow".to_string(), serde_json::json!(tools));
-
-        mcp_servers.insert(name.clone(), serde_json::Value::Object(entry));
     }
+}
 
-    serde_json::json!({ "mcpServers": mcp_servers })
+/// Give servers written before `type` existed their implied type, so old
+/// files keep loading (`mcp migrate` rewrites them).
+fn upgrade_legacy_servers(value: &mut serde_json::Value) -> usize {
+    let Some(servers) = value.get_mut("servers").and_then(|s| s.as_object_mut()) else {
+        return 0;
+    };
+    let mut upgraded = 0;
+    for server in servers.values_mut().filter_map(|s| s.as_object_mut()) {
+        if !server.contains_key("type") {
+            server.insert("type".to_string(), serde_json::json!("runtime"));
+            upgraded += 1;
+        }
+    }
+    upgraded
 }
 
-fn generate_opencode_config(
-    config: &ServersConfig,
-    paths: &McpPaths,
-) -> serde_json::Value {
-    let mut mcp = serde_json::Map::new();
-
-    for (name, server) in &config.servers {
-        let mut entry = serde_json::Map::new();
-
-        if server.server_type.as_deref() == Some("remote") {
-            entry.insert("type".to_string(), serde_json::json!("remote"));
-            entry.insert("url".to_string(), serde_json::json!(server.url));
-        } else if server.server_type.as_deref() == Some("native") {
-            entry.insert("type".to_string(), serde_json::json!("local"));
-            let mut cmd = vec![server.command.clone().unwrap_or_default()];
-            cmd.extend(server.args.clone().unwrap_or_default());
-            entry.insert("command".to_string(), serde_json::json!(cmd));
+/// Collect keys of `value` that `schema` closes off with
+/// `additionalProperties` or `unevaluatedProperties: false`.
+fn unknown_keys(
+    value: &serde_json::Value,
+    schema: &serde_json::Value,
+    root: &serde_json::Value,
+    path: &str,
+    errors: &mut Vec<ConfigError>,
+) {
+    let serde_json::Value::Object(map) = value else {
+        return;
+    };
+    let mut shape = ObjectShape::default();
+    shape.collect(value, schema, root);
+    if shape.unresolved {
+        // e.g. an unknown `type`; serde reports that instead
+        return;
+    }
+    let at = |key: &str| {
+        if path.is_empty() {
+            key.to_string()
         } else {
-            let runtime = server.runtime.as_deref().unwrap_or("node");
-            let path = server.path.as_deref().unwrap_or("");
-            let spec = format!("{}:{}", runtime, path);
-
-            let mut cmd = vec![
-                "cmd.exe".to_string(),
-                "/c".to_string(),
-                paths.mcp_dir.join("mcp-run.cmd").to_string_lossy().to_string(),
-                spec,
-            ];
-            if let Some(extra) = &server.args {
-                cmd.extend(extra.clone());
-            }
-
-            entry.insert("type".to_string(), serde_json::json!("local"));
-            entry.insert("command".to_string(), serde_json::json!(cmd));
+            format!("{}.{}", path, key)
+        }
+    };
+    for (key, item) in map {
+        if let Some(property) = shape.properties.get(key.as_str()) {
+            unknown_keys(item, property, root, &at(key), errors);
+        } else if let Some(additional) = shape.additional {
+            unknown_keys(item, additional, root, &at(key), errors);
+        } else if shape.closed {
+            let message = match &shape.tag {
+                Some(tag) if shape.others.iter().any(|other| schema_defines(other, key, root)) => {
+                    format!("not used by {} servers", tag)
+                }
+                _ => "unknown field".to_string(),
+            };
+            errors.push(ConfigError::new(at(key), message));
         }
-
-        mcp.insert(name.clone(), serde_json::Value::Object(entry));
     }
-
-    serde_json::json!({
-        "mcp": mcp,
-        "$schema": "https://opencode.ai/config.json"
-    })
 }
 
-fn cmd_add(
-    paths: &McpPaths,
-    name: String,
-    runtime: String,
-    path: String,
-    description: Option<String>,
-    tools: Option<String>,
-    env: Option<String>,
-    disabled: bool,
-    opencode_only: bool,
-) -> Result<()> {
-    let mut config = load_servers(paths)?;
+/// What a schema allows in one object, after following `$ref` and picking
+/// the `anyOf`/`oneOf` branches that fit the value.
+#[derive(Default)]
+struct ObjectShape<'a> {
+    properties: BTreeMap<&'a str, &'a serde_json::Value>,
+    additional: Option<&'a serde_json::Value>,
+    closed: bool,
+    /// `const` of the tag that selected a branch
+    tag: Option<String>,
+    /// Branches that did not fit
+    others: Vec<&'a serde_json::Value>,
+    unresolved: bool,
+}
 
-    if config.servers.contains_key(&name) {
-        anyhow::bail!("Server '{}' already exists", name);
+impl<'a> ObjectShape<'a> {
+    fn collect(&mut self, value: &serde_json::Value, schema: &'a serde_json::Value, root: &'a serde_json::Value) {
+        if let Some(target) = resolve_ref(schema, root) {
+            self.collect(value, target, root);
+        }
+        if let Some(properties) = schema.get("properties").and_then(|p| p.as_object()) {
+            self.properties.extend(properties.iter().map(|(k, v)| (k.as_str(), v)));
+        }
+        for key in ["additionalProperties", "unevaluatedProperties"] {
+            match schema.get(key) {
+                Some(serde_json::Value::Bool(false)) => self.closed = true,
+                Some(extra @ serde_json::Value::Object(_)) => self.additional = Some(extra),
+                _ => {}
+            }
+        }
+        // Optional values, untagged enums, and tagged enums keyed on a `const`
+        for branches in ["anyOf", "oneOf"].iter().filter_map(|key| schema.get(*key)?.as_array()) {
+            let mut matched = false;
+            for branch in branches {
+                match branch_tag(value, branch) {
+                    Ok(tag) => {
+                        matched = true;
+                        if tag.is_some() {
+                            self.tag = tag;
+                        }
+                        self.collect(value, branch, root);
+                    }
+                    Err(()) => self.others.push(branch),
+                }
+            }
+            self.unresolved |= !matched;
+        }
     }
+}
 
-    let server = Server {
-        runtime: Some(runtime),
-        path: Some(path),
-        args: None,
-        description,
-        tools: tools.map(|t| t.split(',').map(|s| s.trim().to_string()).collect()),
-        env: env.map(|e| e.split(',').map(|s| s.trim().to_string()).collect()),
-        disabled,
-        opencode_only,
-        server_type: None,
-        url: None,
-        command: None,
-    };
-
-    config.servers.insert(name.clone(), server);
-    save_servers(paths, &config)?;
-
-    println!("{} Added server '{}'", "✓".green(), name.cyan());
-    Ok(())
+/// Whether `value` fits `branch` by its `const` properties, and the tag
+/// value that selected it.
+fn branch_tag(value: &serde_json::Value, branch: &serde_json::Value) -> Result<Option<String>, ()> {
+    let mut tag = None;
+    let consts = branch
+        .get("properties")
+        .and_then(|p| p.as_object())
+        .into_iter()
+        .flatten()
+        .filter_map(|(key, property)| Some((key, property.get("const")?)));
+    for (key, expected) in consts {
+        if value.get(key) != Some(expected) {
+            return Err(());
+        }
+        tag = expected.as_str().map(String::from);
+    }
+    Ok(tag)
 }
 
-fn cmd_remove(paths: &McpPaths, name: &str, force: bool) -> Result<()> {
-    let mut config = load_servers(paths)?;
+fn resolve_ref<'a>(schema: &serde_json::Value, root: &'a serde_json::Value) -> Option<&'a serde_json::Value> {
+    schema
+        .get("$ref")
+        .and_then(|r| r.as_str())
+        .and_then(|r| r.strip_prefix('#'))
+        .and_then(|pointer| root.pointer(pointer))
+}
 
-    if !config.servers.contains_key(name) {
-        anyhow::bail!("Server '{}' not found", name);
-    }
+/// Whether `schema` (or what it references) has a `key` property.
+fn schema_defines(schema: &serde_json::Value, key: &str, root: &serde_json::Value) -> bool {
+    schema.get("properties").and_then(|p| p.get(key)).is_some()
+        || resolve_ref(schema, root).is_some_and(|target| schema_defines(target, key, root))
+}
 
-    if !force {
-        eprint!(
-            "Remove server '{}'? [y/N] ",
-            name.cyan()
-        );
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input)?;
-        if !input.trim().eq_ignore_ascii_case("y") {
-            println!("Cancelled.");
-            return Ok(());
+impl ServersConfig {
+    /// The config, or every rule it breaks.
+    fn validated(self) -> Result<Self, Vec<ConfigError>> {
+        let errors = self.validate();
+        if errors.is_empty() {
+            Ok(self)
+        } else {
+            Err(errors)
         }
     }
 
-    config.servers.remove(name);
-    save_servers(paths, &config)?;
+    /// Rules the schema cannot express: references between sections and to
+    /// runtimes and clients, and values that must be URLs or non-empty.
+    fn validate(&self) -> Vec<ConfigError> {
+        let mut errors = Vec::new();
+        let registry = runtime_registry(self);
 
-    println!("{} Removed server '{}'", "✓".green(), name);
-    Ok(())
-}
+        for (i, target) in self.targets.iter().flatten().enumerate() {
+            if let Err(e) = find_target(target) {
+                errors.push(ConfigError::new(format!("targets.{}", i), e.to_string()));
+            }
+        }
+        for (name, def) in &self.runtimes {
+            if def.program.is_empty() {
+                errors.push(ConfigError::new(format!("runtimes.{}.program", name), "must not be empty"));
+            }
+        }
+        for (profile, entries) in &self.profiles {
+            for (i, entry) in entries.iter().enumerate() {
+                if !entry.starts_with("tag:") && !self.servers.contains_key(entry) {
+                    errors.push(ConfigError::new(
+                        format!("profiles.{}.{}", profile, i),
+                        format!("unknown server '{}'", entry),
+                    ));
+                }
+            }
+        }
+        if let Some(profile) = self.profile.as_deref().filter(|p| !self.profiles.contains_key(*p)) {
+            errors.push(ConfigError::new("profile", format!("unknown profile '{}'", profile)));
+        }
 
-fn cmd_enable(paths: &McpPaths, name: &str) -> Result<()> {
-    let mut config = load_servers(paths)?;
+        for (name, server) in &self.servers {
+            let at = |field: &str| format!("servers.{}.{}", name, field);
+            match &server.kind {
+                ServerKind::Runtime(runtime) => {
+                    if let Err(e) = lookup_runtime(&registry, &runtime.runtime) {
+                        errors.push(ConfigError::new(at("runtime"), e.to_string()));
+                    }
+                }
+                ServerKind::Native(native) => {
+                    if native.command.is_empty() {
+                        errors.push(ConfigError::new(at("command"), "must not be empty"));
+                    }
+                }
+                ServerKind::Remote(remote) => {
+                    if !remote.url.starts_with("http://") && !remote.url.starts_with("https://") {
+                        errors.push(ConfigError::new(at("url"), "must be an http:// or https:// URL"));
+                    }
+                    if let Some(oauth) = &remote.oauth {
+                        if oauth.token_url.is_some() && oauth.client_id.is_none() {
+                            errors.push(ConfigError::new(at("oauth.client_id"), "required with token_url"));
+                        }
+                    }
+                }
+                ServerKind::Docker(docker) => {
+                    if docker.image.is_empty() {
+                        errors.push(ConfigError::new(at("image"), "must not be empty"));
+                    }
+                }
+            }
+        }
+        errors
+    }
+}
 
-    let server = config
-        .servers
-        .get_mut(name)
-        .with_context(|| format!("Server '{}' not found", name))?;
+// ============================================================================
+// Package Installation
+// ============================================================================
 
-    server.disabled = false;
-    save_servers(paths, &config)?;
+/// A package `mcp install` put in place, recorded in installed.json.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct InstalledPackage {
+    version: String,
+    /// Module root it went into (fnm, volta, bun, ...)
+    manager: String,
+    installed: String,
+}
 
-    println!("{} Enabled server '{}'", "✓".green(), name.cyan());
-    Ok(())
+/// servers.lock.json: what `mcp install` resolved for each server.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ServersLock {
+    #[serde(rename = "lockfileVersion")]
+    lockfile_version: u32,
+    #[serde(default)]
+    servers: BTreeMap<String, LockedPackage>,
 }
 
-fn cmd_disable(paths: &McpPaths, name: &str) -> Result<()> {
-    let mut config = load_servers(paths)?;
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct LockedPackage {
+    package: String,
+    /// The server's `version` when this was resolved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    requested: Option<String>,
+    version: String,
+    integrity: String,
+}
 
-    let server = config
-        .servers
-        .get_mut(name)
-        .with_context(|| format!("Server '{}' not found", name))?;
+impl ServersLock {
+    /// The lock entry for a server, unless its package or requested
+    /// version changed since it was resolved.
+    fn entry(&self, name: &str, package: &ServerPackage) -> Option<&LockedPackage> {
+        self.servers
+            .get(name)
+            .filter(|locked| locked.package == package.package && locked.requested.as_deref() == package.requested)
+    }
+}
 
-    server.disabled = true;
-    save_servers(paths, &config)?;
+/// The npm package behind a server whose runtime loads scripts from a
+/// module root (node, bun).
+struct ServerPackage<'a> {
+    runtime: &'a str,
+    def: &'a RuntimeDef,
+    package: &'a str,
+    /// Entry point relative to node_modules
+    path: &'a str,
+    /// The server's `version`
+    requested: Option<&'a str>,
+}
 
-    println!("{} Disabled server '{}'", "✓".green(), name.cyan());
-    Ok(())
+fn server_package<'a>(
+    server: &'a Server,
+    registry: &'a BTreeMap<String, RuntimeDef>,
+) -> Option<ServerPackage<'a>> {
+    let runtime = match &server.kind {
+        ServerKind::Runtime(runtime) => runtime,
+        ServerKind::Native(_) | ServerKind::Remote(_) | ServerKind::Docker(_) => return None,
+    };
+    let def = registry.get(&runtime.runtime).filter(|def| def.module_root.is_some())?;
+    Some(ServerPackage {
+        runtime: &runtime.runtime,
+        def,
+        package: package_name(&runtime.path),
+        path: &runtime.path,
+        requested: runtime.version.as_deref(),
+    })
 }
 
-fn cmd_show(paths: &McpPaths, name: &str, json: bool) -> Result<()> {
-    let config = load_servers(paths)?;
+/// `relative` inside a runtime's module root, if it exists.
+fn installed_file(paths: &McpPaths, def: &RuntimeDef, relative: &str) -> Option<PathBuf> {
+    match def.module_root.as_deref()? {
+        "{node_modules}" => paths.find_node_script(relative).ok().map(|(_, file)| file),
+        root => Some(PathBuf::from(expand_template(paths, None, root)).join(relative))
+            .filter(|file| file.exists()),
+    }
+}
 
-    let server = config
-        .servers
-        .get(name)
-        .with_context(|| format!("Server '{}' not found", name))?;
+impl ServerPackage<'_> {
+    fn entry_point(&self, paths: &McpPaths) -> Option<PathBuf> {
+        installed_file(paths, self.def, self.path)
+    }
 
-    if json {
-        println!("{}", serde_json::to_string_pretty(server)?);
-    } else {
-        println!("{}: {}", "Name".cyan(), name);
-        if let Some(desc) = &server.description {
-            println!("{}: {}", "Description".cyan(), desc);
-        }
+    fn manifest(&self, paths: &McpPaths) -> Option<PathBuf> {
+        installed_file(paths, self.def, &format!("{}/package.json", self.package))
+    }
 
-        let runtime = if server.server_type.as_deref() == Some("remote") {
-            "remote"
-        } else if server.server_type.as_deref() == Some("native") {
-            "native"
-        } else {
-            server.runtime.as_deref().unwrap_or("node")
-        };
-        println!("{}: {}", "Runtime".cyan(), runtime);
+    fn installed_version(&self, paths: &McpPaths) -> Option<String> {
+        let manifest = fs::read_to_string(self.manifest(paths)?).ok()?;
+        let manifest: serde_json::Value = serde_json::from_str(&manifest).ok()?;
+        manifest.get("version")?.as_str().map(String::from)
+    }
 
-        if let Some(path) = &server.path {
-            println!("{}: {}", "Path".cyan(), path);
-        }
-        if let Some(url) = &server.url {
-            println!("{}: {}", "URL".cyan(), url);
-        }
-        if let Some(cmd) = &server.command {
-            println!("{}: {}", "Command".cyan(), cmd);
-        }
-        if let Some(args) = &server.args {
-            println!("{}: {:?}", "Args".cyan(), args);
-        }
+    /// The Node install whose global packages this package goes into: the
+    /// one that already has it, else the first present.
+    fn node_root<'p>(&self, paths: &'p McpPaths) -> Result<&'p ModuleRoot> {
+        paths
+            .node_roots
+            .iter()
+            .find(|root| root.script(self.path).exists())
+            .or_else(|| paths.node_roots.iter().find(|root| root.bin_dir.exists()))
+            .context("No Node installation found (looked for fnm, Volta, nvm and node on PATH)")
+    }
 
-        println!(
-            "{}: {}",
-            "Status".cyan(),
-            if server.disabled {
-                "disabled".red()
-            } else {
-                "enabled".green()
+    /// Package manager invocation installing `spec` (`pkg@version`) into the
+    /// module root, along with the root's manager name. npm and bun install
+    /// the verified `tarball` when given; Volta only takes registry specs.
+    fn install_command(&self, paths: &McpPaths, spec: &str, tarball: Option<&Path>) -> Result<(Command, &'static str)> {
+        let source = tarball.map_or_else(|| spec.into(), |tarball| tarball.as_os_str().to_owned());
+        match self.def.module_root.as_deref() {
+            Some("{node_modules}") => {
+                let root = self.node_root(paths)?;
+                let mut command = if root.manager == "volta" {
+                    let mut command = Command::new(find_in_path("volta").unwrap_or_else(|| "volta".into()));
+                    command.args(["install", spec]);
+                    command
+                } else {
+                    let npm = if cfg!(windows) { "npm.cmd" } else { "npm" };
+                    let mut command = Command::new(root.bin_dir.join(npm));
+                    command.args(["install", "--global"]).arg(source);
+                    command
+                };
+                command.env("PATH", prepend_path(std::slice::from_ref(&root.bin_dir))?);
+                Ok((command, root.manager))
             }
-        );
-
-        if server.opencode_only {
-            println!("{}: yes", "OpenCode Only".cyan());
+            Some("{bun_modules}") => {
+                let mut command = Command::new(paths.bun_binary());
+                command.args(["add", "--global"]).arg(source);
+                Ok((command, paths.bun_root.manager))
+            }
+            _ => anyhow::bail!(
+                "Runtime '{}' has no package manager mcp can drive; install {} yourself",
+                self.runtime,
+                self.package
+            ),
         }
+    }
 
-        if let Some(tools) = &server.tools {
-            println!("{}: {}", "Tools".cyan(), tools.join(", "));
-        }
-        if let Some(env) = &server.env {
-            println!("{}: {}", "Env Vars".cyan(), env.join(", "));
+    /// Package manager invocation removing the package from its module root.
+    fn uninstall_command(&self, paths: &McpPaths) -> Result<Command> {
+        match self.def.module_root.as_deref() {
+            Some("{node_modules}") => {
+                let root = self.node_root(paths)?;
+                let mut command = if root.manager == "volta" {
+                    let mut command = Command::new(find_in_path("volta").unwrap_or_else(|| "volta".into()));
+                    command.args(["uninstall", self.package]);
+                    command
+                } else {
+                    let npm = if cfg!(windows) { "npm.cmd" } else { "npm" };
+                    let mut command = Command::new(root.bin_dir.join(npm));
+                    command.args(["uninstall", "--global", self.package]);
+                    command
+                };
+                command.env("PATH", prepend_path(std::slice::from_ref(&root.bin_dir))?);
+                Ok(command)
+            }
+            Some("{bun_modules}") => {
+                let mut command = Command::new(paths.bun_binary());
+                command.args(["remove", "--global", self.package]);
+                Ok(command)
+            }
+            _ => anyhow::bail!("Runtime '{}' has no package manager mcp can drive", self.runtime),
         }
     }
+}
 
-    Ok(())
+/// A published version and its `dist.integrity` from the npm registry.
+#[derive(Debug)]
+struct PackageDist {
+    version: String,
+    /// `sha512-<base64>` SRI hash of the version's tarball
+    integrity: String,
 }
 
-fn cmd_env(paths: &McpPaths, action: EnvCommands) -> Result<()> {
-    match action {
-        EnvCommands::List => {
-            let env = load_env(paths);
-            if env.is_empty() {
-                println!("{}", "No environment variables set.".yellow());
-            } else {
-                for (key, value) in &env {
-                    // Mask sensitive values
-                    let display_value = if key.to_lowercase().contains("key")
-                        || key.to_lowercase().contains("secret")
-                        || key.to_lowercase().contains("token")
-                    {
-                        format!("{}...", &value.chars().take(8).collect::<String>())
-                    } else {
-                        value.clone()
-                    };
-                    println!("{}={}", key.cyan(), display_value);
-                }
+/// npm, which reads registry metadata and fetches tarballs for every module
+/// root: the first Node install's, else the one on PATH.
+fn npm_command(paths: &McpPaths) -> Command {
+    let npm = if cfg!(windows) { "npm.cmd" } else { "npm" };
+    match paths.node_roots.iter().find(|root| root.bin_dir.join(npm).is_file()) {
+        Some(root) => {
+            let mut command = Command::new(root.bin_dir.join(npm));
+            if let Ok(path) = prepend_path(std::slice::from_ref(&root.bin_dir)) {
+                command.env("PATH", path);
             }
+            command
         }
-        EnvCommands::Set { key, value } => {
-            let mut env = load_env(paths);
-            env.insert(key.clone(), value);
-            save_env(paths, &env)?;
-            println!("{} Set {}", "✓".green(), key.cyan());
-        }
-        EnvCommands::Remove { key } => {
-            let mut env = load_env(paths);
-            if env.remove(&key).is_some() {
-                save_env(paths, &env)?;
-                println!("{} Removed {}", "✓".green(), key);
-            } else {
-                println!("{} '{}' not found", "!".yellow(), key);
-            }
-        }
-        EnvCommands::Edit => {
-            paths.ensure_mcp_dir()?;
-            if !paths.env_file.exists() {
-                fs::write(&paths.env_file, "# MCP Environment Variables\n")?;
-            }
+        None => Command::new(find_in_path("npm").unwrap_or_else(|| npm.into())),
+    }
+}
 
-            let editor = std::env::var("EDITOR").unwrap_or_else(|_| "notepad".to_string());
-            Command::new(&editor)
-                .arg(&paths.env_file)
-                .status()?;
-        }
+/// Run an npm command, returning its stdout.
+fn run_npm(mut command: Command) -> Result<Vec<u8>> {
+    let output = command
+        .stdin(Stdio::null())
+        .output()
+        .context("Failed to run npm, which mcp uses to read the package registry")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let errors: Vec<&str> = stderr
+            .lines()
+            .filter(|line| line.starts_with("npm error") || line.starts_with("npm ERR!"))
+            .filter(|line| !line.contains("complete log"))
+            .collect();
+        let message = if errors.is_empty() { stderr.trim().to_string() } else { errors.join("\n") };
+        anyhow::bail!("npm failed: {}", message);
     }
+    Ok(output.stdout)
+}
 
-    Ok(())
+/// The version npm installs for `spec` (`pkg`, `pkg@version`, `pkg@range`)
+/// and its registry integrity, without installing anything.
+fn registry_dist(paths: &McpPaths, spec: &str) -> Result<PackageDist> {
+    let mut command = npm_command(paths);
+    command.args(["view", spec, "version", "dist.integrity", "--json"]);
+    let stdout = run_npm(command).with_context(|| format!("Could not look up {}", spec))?;
+    parse_npm_view(spec, &stdout)
 }
 
-fn cmd_info(paths: &McpPaths) -> Result<()> {
-    println!("{}", "MCP Configuration".cyan().bold());
-    println!();
-    println!("{}: {}", "MCP Directory".cyan(), paths.mcp_dir.display());
-    println!("{}: {}", "Servers Config".cyan(), paths.servers_json.display());
-    println!("{}: {}", "Environment".cyan(), paths.env_file.display());
-    println!();
-    println!("{}", "Output Paths".cyan().bold());
-    println!("{}: {}", "Cursor Config".cyan(), paths.cursor_config.display());
-    println!("{}: {}", "OpenCode Config".cyan(), paths.opencode_config.display());
-    println!();
-    println!("{}", "Runtime Paths".cyan().bold());
-    println!("{}: {}", "fnm node_modules".cyan(), paths.fnm_node_modules.display());
-    println!("{}: {}", "bun node_modules".cyan(), paths.bun_node_modules.display());
+/// Read `npm view <spec> version dist.integrity --json` output.
+fn parse_npm_view(spec: &str, stdout: &[u8]) -> Result<PackageDist> {
+    let view: serde_json::Value = if stdout.iter().all(u8::is_ascii_whitespace) {
+        serde_json::Value::Null
+    } else {
+        serde_json::from_slice(stdout).context("Unexpected npm view output")?
+    };
+    // A range matching several versions lists each, lowest first
+    let view = match view {
+        serde_json::Value::Array(mut versions) => versions.pop().unwrap_or_default(),
+        view => view,
+    };
 
-    // Check if paths exist
-    println!();
-    println!("{}", "Status".cyan().bold());
+    let version = view
+        .get("version")
+        .and_then(|v| v.as_str())
+        .with_context(|| format!("No published version matches {}", spec))?;
+    let integrity = view
+        .get("dist.integrity")
+        .and_then(|i| i.as_str())
+        .and_then(|i| i.split_whitespace().find(|hash| hash.starts_with("sha512-")))
+        .with_context(|| {
+            // The name without the spec's version (`@scope/pkg@1` -> `@scope/pkg`)
+            let name = spec.get(1..).and_then(|rest| rest.find('@')).map_or(spec, |i| &spec[..i + 1]);
+            format!("{}@{} has no sha512 integrity in the registry", name, version)
+        })?;
+    Ok(PackageDist {
+        version: version.to_string(),
+        integrity: integrity.to_string(),
+    })
+}
 
-    let check = |path: &PathBuf, name: &str| {
-        if path.exists() {
-            println!("  {} {}", "✓".green(), name);
-        } else {
-            println!("  {} {} (missing)", "✗".red(), name);
-        }
-    };
+/// Download the tarball for `spec` into the empty directory `dir`.
+fn fetch_tarball(paths: &McpPaths, spec: &str, dir: &Path) -> Result<PathBuf> {
+    let mut command = npm_command(paths);
+    // npm checks the tarball too, and would otherwise retry a corrupt one
+    // with backoff for minutes
+    command
+        .args(["pack", spec, "--fetch-retries=1", "--fetch-retry-mintimeout=2000", "--pack-destination"])
+        .arg(dir);
+    run_npm(command).with_context(|| format!("Could not download {}", spec))?;
+    fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .find(|path| path.extension().is_some_and(|ext| ext == "tgz"))
+        .with_context(|| format!("npm pack left no tarball for {}", spec))
+}
 
-    check(&paths.servers_json, "servers.json");
-    check(&paths.env_file, ".env");
-    check(&paths.cursor_config, "Cursor config");
-    check(&paths.opencode_config, "OpenCode config");
-    check(&paths.fnm_node_modules, "fnm node_modules");
-    check(&paths.bun_node_modules, "bun node_modules");
+/// `sha512-<base64>` of a file, as in npm's `dist.integrity`.
+fn file_integrity(path: &Path) -> Result<String> {
+    let contents = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(format!(
+        "sha512-{}",
+        base64::engine::general_purpose::STANDARD.encode(Sha512::digest(&contents))
+    ))
+}
+
+fn load_installed(paths: &McpPaths) -> BTreeMap<String, InstalledPackage> {
+    fs::read_to_string(&paths.installed_json)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
 
+fn save_installed(paths: &McpPaths, installed: &BTreeMap<String, InstalledPackage>) -> Result<()> {
+    paths.ensure_mcp_dir()?;
+    fs::write(&paths.installed_json, serde_json::to_string_pretty(installed)?)?;
     Ok(())
 }
 
-fn cmd_completions(shell: Shell) -> Result<()> {
-    let mut cmd = Cli::command();
-    generate(shell, &mut cmd, "mcp", &mut io::stdout());
+/// Current servers.lock.json format: integrity is the registry's
+/// `dist.integrity`. Version 1 hashed the installed files instead.
+const LOCKFILE_VERSION: u32 = 2;
+
+fn load_lock(paths: &McpPaths) -> Result<ServersLock> {
+    if !paths.lock_json.exists() {
+        return Ok(ServersLock::default());
+    }
+    let content = fs::read_to_string(&paths.lock_json)
+        .with_context(|| format!("Failed to read {}", paths.lock_json.display()))?;
+    let mut lock: ServersLock = serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse {}", paths.lock_json.display()))?;
+    // Old hashes cannot be checked against the registry; the next install
+    // pins the registry's integrity for the same versions
+    if lock.lockfile_version < LOCKFILE_VERSION {
+        for locked in lock.servers.values_mut() {
+            locked.integrity.clear();
+        }
+    }
+    Ok(lock)
+}
+
+fn save_lock(paths: &McpPaths, lock: &mut ServersLock) -> Result<()> {
+    paths.ensure_mcp_dir()?;
+    lock.lockfile_version = LOCKFILE_VERSION;
+    fs::write(&paths.lock_json, serde_json::to_string_pretty(lock)? + "\n")?;
     Ok(())
 }
 
-// ============================================================================
-// Main
-// ============================================================================
+/// Record what is now installed for `names` in the lockfile.
+fn lock_package(
+    lock: &mut ServersLock,
+    package: &ServerPackage,
+    names: &[&str],
+    version: &str,
+    integrity: &str,
+) {
+    for name in names {
+        lock.servers.insert(
+            name.to_string(),
+            LockedPackage {
+                package: package.package.to_string(),
+                requested: package.requested.map(String::from),
+                version: version.to_string(),
+                integrity: integrity.to_string(),
+            },
+        );
+    }
+}
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
-    let paths = McpPaths::new(cli.mcp_dir)?;
+/// Install `spec` for the servers in `names` and record it, returning the
+/// version now in place and its integrity (None on a dry run). The version's
+/// tarball is checked against the registry's `dist.integrity`, and against
+/// `expected` from the lockfile, before anything is installed; an install
+/// that leaves another version in place is rolled back.
+fn install_package(
+    paths: &McpPaths,
+    package: &ServerPackage,
+    spec: &str,
+    names: &[&str],
+    expected: Option<&str>,
+    dry_run: bool,
+) -> Result<Option<PackageDist>> {
+    let (_, manager) = package.install_command(paths, spec, None)?;
+    println!("{} {} ({}) into {}", "→".cyan(), spec.cyan(), names.join(", "), manager);
+    if dry_run {
+        return Ok(None);
+    }
 
-    match cli.command {
-        Commands::List { enabled, disabled, json } => {
-            cmd_list(&paths, enabled, disabled, json)
-        }
-        Commands::Run { name, args } => {
-            cmd_run(&paths, &name, &args)
-        }
-        Commands::Gen { cursor, opencode, dry_run } => {
-            cmd_gen(&paths, cursor, opencode, dry_run)
+    let dist = registry_dist(paths, spec)?;
+    if let Some(expected) = expected.filter(|expected| *expected != dist.integrity) {
+        anyhow::bail!(
+            "{}@{} does not match servers.lock.json (expected {}, registry has {})",
+            package.package,
+            dist.version,
+            expected,
+            dist.integrity
+        );
+    }
+
+    let dir = std::env::temp_dir().join(format!("mcp-install-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&dir)?;
+    let result = install_verified(paths, package, &dist, &dir);
+    let _ = fs::remove_dir_all(&dir);
+    result?;
+
+    if package.entry_point(paths).is_none() {
+        println!(
+            "  {} installed, but {} was not found - check the server's path",
+            "!".yellow(),
+            package.path
+        );
+    }
+    let mut installed = load_installed(paths);
+    installed.insert(
+        package.package.to_string(),
+        InstalledPackage {
+            version: dist.version.clone(),
+            manager: manager.to_string(),
+            installed: chrono::Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        },
+    );
+    save_installed(paths, &installed)?;
+    Ok(Some(dist))
+}
+
+/// Fetch `dist`'s tarball into `dir`, check it and install it.
+fn install_verified(paths: &McpPaths, package: &ServerPackage, dist: &PackageDist, dir: &Path) -> Result<()> {
+    let spec = format!("{}@{}", package.package, dist.version);
+    let tarball = fetch_tarball(paths, &spec, dir)?;
+    let integrity = file_integrity(&tarball)?;
+    if integrity != dist.integrity {
+        anyhow::bail!(
+            "Downloaded {} does not match the registry (expected {}, got {})",
+            spec,
+            dist.integrity,
+            integrity
+        );
+    }
+
+    let previous = package.installed_version(paths);
+    let (mut command, _) = package.install_command(paths, &spec, Some(&tarball))?;
+    let status = command
+        .status()
+        .with_context(|| format!("Failed to run {}", command.get_program().to_string_lossy()))?;
+    if !status.success() {
+        anyhow::bail!("Installing {} failed ({})", spec, status);
+    }
+
+    match package.installed_version(paths) {
+        Some(version) if version == dist.version => Ok(()),
+        found => {
+            roll_back(paths, package, previous.as_deref());
+            anyhow::bail!(
+                "Installing {} left {} in place; rolled back",
+                spec,
+                found.as_deref().unwrap_or("nothing")
+            )
         }
-        Commands::Add {
-            name,
-            runtime,
-            path,
-            description,
-            tools,
-            env,
-            disabled,
-            opencode_only,
-        } => cmd_add(
-            &paths,
+    }
+}
+
+/// Undo an install that went wrong: reinstall the `previous` version, or
+/// remove the package if there was none.
+fn roll_back(paths: &McpPaths, package: &ServerPackage, previous: Option<&str>) {
+    let command = match previous {
+        Some(previous) => package
+            .install_command(paths, &format!("{}@{}", package.package, previous), None)
+            .map(|(command, _)| command),
+        None => package.uninstall_command(paths),
+    };
+    let restored = command.and_then(|mut command| Ok(command.status()?.success()));
+    if !matches!(restored, Ok(true)) {
+        eprintln!("{} Could not roll back {}; reinstall it by hand", "!".yellow(), package.package);
+    }
+}
+
+/// Packages of the named servers (every enabled server when `names` is
+/// empty), each listed once with the servers that use it.
+fn select_packages<'a>(
+    config: &'a ServersConfig,
+    registry: &'a BTreeMap<String, RuntimeDef>,
+    names: &[String],
+) -> Result<Vec<(ServerPackage<'a>, Vec<&'a str>)>> {
+    let servers: Vec<(&String, &Server)> = if names.is_empty() {
+        config.servers.iter().filter(|(_, s)| !s.disabled).collect()
+    } else {
+        names
+            .iter()
+            .map(|name| {
+                config
+                    .servers
+                    .get_key_value(name)
+                    .with_context(|| format!("Server '{}' not found", name))
+            })
+            .collect::<Result<_>>()?
+    };
+
+    let mut packages: Vec<(ServerPackage, Vec<&str>)> = Vec::new();
+    for (name, server) in servers {
+        let Some(package) = server_package(server, registry) else {
+            if !names.is_empty() {
+                println!("{} {} has no package to install", "-".dimmed(), name);
+            }
+            continue;
+        };
+        match packages.iter_mut().find(|(p, _)| p.package == package.package) {
+            Some((_, users)) => users.push(name),
+            None => packages.push((package, vec![name])),
+        }
+    }
+    Ok(packages)
+}
+
+// ============================================================================
+// Config Loading
+// ============================================================================
+
+fn load_servers(paths: &McpPaths) -> Result<ServersConfig> {
+    let content = fs::read_to_string(&paths.servers_json)
+        .with_context(|| format!("Failed to read {}", paths.servers_json.display()))?;
+    parse_servers(&content, ServersConfig::validated).map_err(|errors| invalid_config(&paths.servers_json, &errors))
+}
+
+/// servers.json with the project overlay merged over it. For reading only;
+/// commands that edit servers.json use `load_servers`.
+fn load_merged_servers(paths: &McpPaths) -> Result<ServersConfig> {
+    let mut config = load_servers(paths)?;
+    if let Some(project_json) = &paths.project_json {
+        let content = fs::read_to_string(project_json)
+            .with_context(|| format!("Failed to read {}", project_json.display()))?;
+        // Checked merged, since an overlay may use the global runtimes
+        // and servers
+        config = parse_servers(&content, |overlay| {
+            config.merge_overlay(overlay);
+            config.validated()
+        })
+        .map_err(|errors| invalid_config(project_json, &errors))?;
+    }
+    Ok(config)
+}
+
+/// The merged config with a profile applied (see `ServersConfig::apply_profile`).
+fn load_active_servers(paths: &McpPaths, profile: Option<&str>) -> Result<ServersConfig> {
+    let mut config = load_merged_servers(paths)?;
+    config.apply_profile(profile)?;
+    Ok(config)
+}
+
+/// Write servers.json through a temporary file, so readers never see it
+/// half written.
+fn save_servers(paths: &McpPaths, config: &ServersConfig) -> Result<()> {
+    let content = serde_json::to_string_pretty(config)?;
+    let mut temp = paths.servers_json.as_os_str().to_owned();
+    temp.push(".tmp");
+    fs::write(&temp, content)?;
+    fs::rename(&temp, &paths.servers_json)
+        .with_context(|| format!("Failed to write {}", paths.servers_json.display()))
+}
+
+/// Variable values from .env and the configured secret store.
+fn load_env(paths: &McpPaths, config: &ServersConfig) -> Result<BTreeMap<String, String>> {
+    Ok(merge_env(EnvFile::load(&paths.env_file)?.vars(), secret_store(paths, config).load()?))
+}
+
+/// Layer secret store values over .env. The store wins over leftovers in
+/// .env, but an empty store value does not hide a .env one.
+fn merge_env(dotenv: BTreeMap<String, String>, stored: BTreeMap<String, String>) -> BTreeMap<String, String> {
+    let mut env = dotenv;
+    for (key, value) in stored {
+        if !value.is_empty() || !env.contains_key(&key) {
+            env.insert(key, value);
+        }
+    }
+    env
+}
+
+/// Variables passed through from the parent environment to every server.
+/// Matched case-insensitively, as on Windows.
+const ENV_ALLOWLIST: &[&str] = &[
+    "PATH", "PATHEXT", "HOME", "USER", "USERNAME", "LOGNAME", "SHELL", "TERM",
+    "LANG", "LC_ALL", "LC_CTYPE", "TZ", "TMPDIR", "TEMP", "TMP",
+    "USERPROFILE", "HOMEDRIVE", "HOMEPATH", "APPDATA", "LOCALAPPDATA", "PROGRAMDATA",
+    "ProgramFiles", "ProgramFiles(x86)", "SystemRoot", "SystemDrive", "windir", "COMSPEC",
+    "XDG_RUNTIME_DIR", "XDG_CONFIG_HOME", "XDG_DATA_HOME", "XDG_CACHE_HOME",
+    "DISPLAY", "WAYLAND_DISPLAY", "DBUS_SESSION_BUS_ADDRESS",
+    "HTTP_PROXY", "HTTPS_PROXY", "NO_PROXY",
+    "FNM_DIR", "NVM_DIR", "NVM_SYMLINK", "VOLTA_HOME", "BUN_INSTALL", "DENO_DIR",
+];
+
+/// Apply a server's environment to `command`: the allowlisted parent
+/// variables (or with `inherit_env`, the whole environment and .env) plus
+/// its declared `env`, resolved from .env and then the parent environment.
+/// Fails if a declared variable is unset or empty.
+fn apply_server_env(
+    command: &mut Command,
+    name: &str,
+    server: &Server,
+    env_vars: &BTreeMap<String, String>,
+) -> Result<()> {
+    if server.inherit_env {
+        command.envs(env_vars);
+    } else {
+        command.env_clear();
+        for (key, value) in std::env::vars_os() {
+            let key_str = key.to_string_lossy();
+            if ENV_ALLOWLIST.iter().any(|allowed| allowed.eq_ignore_ascii_case(&key_str)) {
+                command.env(key, value);
+            }
+        }
+    }
+
+    let mut missing = Vec::new();
+    for (target, source) in server.env.iter().flat_map(EnvSpec::bindings) {
+        match lookup_env_var(env_vars, source) {
+            Some(value) => {
+                command.env(target, value);
+            }
+            None => missing.push(source),
+        }
+    }
+
+    if !missing.is_empty() {
+        anyhow::bail!(
+            "Server '{}' requires {} which {} not set. Set with: mcp env set {} <value>",
             name,
-            runtime,
-            path,
-            description,
-            tools,
-            env,
-            disabled,
-            opencode_only,
-        ),
-        Commands::Remove { name, force } => {
-            cmd_remove(&paths, &name, force)
+            missing.join(", "),
+            if missing.len() == 1 { "is" } else { "are" },
+            missing[0]
+        );
+    }
+    Ok(())
+}
+
+/// Replace `${VAR}` references in `value` using `lookup`. Names `lookup`
+/// cannot resolve are left in place and added to `missing`.
+fn expand_env_refs(value: &str, lookup: impl Fn(&str) -> Option<String>, missing: &mut Vec<String>) -> String {
+    let mut out = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        let Some(len) = rest[start + 2..].find('}') else {
+            break;
+        };
+        let name = &rest[start + 2..start + 2 + len];
+        out.push_str(&rest[..start]);
+        match lookup(name) {
+            Some(resolved) => out.push_str(&resolved),
+            None => {
+                out.push_str(&rest[start..start + 3 + len]);
+                if !missing.iter().any(|m| m == name) {
+                    missing.push(name.to_string());
+                }
+            }
         }
-        Commands::Enable { name } => {
-            cmd_enable(&paths, &name)
+        rest = &rest[start + 3 + len..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Resolve a variable the way servers see it: the secret store and .env (as
+/// merged by `load_env`), then the process environment. Empty values count
+/// as unset, so they fall through to the next source.
+fn lookup_env_var(env_vars: &BTreeMap<String, String>, name: &str) -> Option<String> {
+    env_vars
+        .get(name)
+        .filter(|v| !v.is_empty())
+        .cloned()
+        .or_else(|| std::env::var(name).ok().filter(|v| !v.is_empty()))
+}
+
+/// A remote server's headers with `${VAR}` references expanded.
+fn resolve_headers(
+    name: &str,
+    server: &RemoteServer,
+    env_vars: &BTreeMap<String, String>,
+) -> Result<Vec<(String, String)>> {
+    let mut missing = Vec::new();
+    let headers: Vec<_> = server
+        .headers
+        .iter()
+        .map(|(key, value)| {
+            let value = expand_env_refs(value, |var| lookup_env_var(env_vars, var), &mut missing);
+            (key.clone(), value)
+        })
+        .collect();
+    if !missing.is_empty() {
+        anyhow::bail!(
+            "Server '{}' headers use {} which {} not set. Set with: mcp env set {} <value>",
+            name,
+            missing.join(", "),
+            if missing.len() == 1 { "is" } else { "are" },
+            missing[0]
+        );
+    }
+    Ok(headers)
+}
+
+// ============================================================================
+// .env Files
+// ============================================================================
+
+/// What a new .env file starts with.
+const ENV_FILE_HEADER: &str = "# MCP Server Environment Variables\n";
+
+/// A .env file kept as written. Changes re-render only the entries they
+/// touch; comments, blank lines, ordering and quoting elsewhere survive.
+///
+/// Lines are `KEY=value`, optionally prefixed with `export`. Values may be
+/// bare (a ` #` starts a comment), single-quoted (literal) or double-quoted
+/// (`\n`, `\t`, `\"` and `\\` escapes); quoted values may span lines, and
+/// bare ones continue onto the next line after a trailing `\`.
+struct EnvFile {
+    entries: Vec<EnvEntry>,
+    newline: &'static str,
+}
+
+enum EnvEntry {
+    /// Blank lines and comments
+    Text(String),
+    Var { lines: Vec<String>, var: EnvVar },
+    /// Lines that are not `KEY=value`, kept so saving does not lose them
+    Invalid(String),
+}
+
+struct EnvVar {
+    key: String,
+    value: String,
+    export: bool,
+    quote: Option<char>,
+    /// Trailing comment, with the whitespace before it
+    comment: String,
+}
+
+impl EnvFile {
+    /// The file at `path`, or a new one if it does not exist.
+    fn load(path: &Path) -> Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(content) => Ok(Self::parse(&content)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::parse(ENV_FILE_HEADER)),
+            Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
         }
-        Commands::Disable { name } => {
-            cmd_disable(&paths, &name)
+    }
+
+    fn parse(content: &str) -> Self {
+        let newline = if content.contains("\r\n") { "\r\n" } else { "\n" };
+        let lines: Vec<&str> = content.lines().collect();
+        let mut entries = Vec::new();
+        let mut i = 0;
+        while i < lines.len() {
+            let line = lines[i];
+            let trimmed = line.trim_start();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                entries.push(EnvEntry::Text(line.to_string()));
+                i += 1;
+                continue;
+            }
+            match parse_env_var(&lines[i..]) {
+                Some((var, used)) => {
+                    let text = lines[i..i + used].iter().map(|l| l.to_string()).collect();
+                    entries.push(EnvEntry::Var { lines: text, var });
+                    i += used;
+                }
+                None => {
+                    entries.push(EnvEntry::Invalid(line.to_string()));
+                    i += 1;
+                }
+            }
         }
-        Commands::Show { name, json } => {
-            cmd_show(&paths, &name, json)
+        Self { entries, newline }
+    }
+
+    /// The variables, later definitions winning.
+    fn vars(&self) -> BTreeMap<String, String> {
+        self.entries
+            .iter()
+            .filter_map(|entry| match entry {
+                EnvEntry::Var { var, .. } => Some((var.key.clone(), var.value.clone())),
+                EnvEntry::Text(_) | EnvEntry::Invalid(_) => None,
+            })
+            .collect()
+    }
+
+    /// 1-based line numbers and text of lines that could not be parsed.
+    fn invalid_lines(&self) -> Vec<(usize, &str)> {
+        let mut invalid = Vec::new();
+        let mut number = 1;
+        for entry in &self.entries {
+            match entry {
+                EnvEntry::Text(_) => number += 1,
+                EnvEntry::Var { lines, .. } => number += lines.len(),
+                EnvEntry::Invalid(line) => {
+                    invalid.push((number, line.as_str()));
+                    number += 1;
+                }
+            }
         }
-        Commands::Env { action } => {
-            cmd_env(&paths, action)
+        invalid
+    }
+
+    /// Set `key`, rewriting its last definition in place (keeping `export`,
+    /// the quote style and any comment) or appending it. Returns whether
+    /// the file changed.
+    fn set(&mut self, key: &str, value: &str) -> bool {
+        let existing = self.entries.iter_mut().rev().find_map(|entry| match entry {
+            EnvEntry::Var { lines, var } if var.key == key => Some((lines, var)),
+            _ => None,
+        });
+        match existing {
+            Some((_, var)) if var.value == value => false,
+            Some((lines, var)) => {
+                var.value = value.to_string();
+                *lines = var.render();
+                true
+            }
+            None => {
+                let var = EnvVar {
+                    key: key.to_string(),
+                    value: value.to_string(),
+                    export: false,
+                    quote: None,
+                    comment: String::new(),
+                };
+                self.entries.push(EnvEntry::Var { lines: var.render(), var });
+                true
+            }
         }
-        Commands::Info => {
-            cmd_info(&paths)
+    }
+
+    /// Remove every definition of `key`. Returns whether there was one.
+    fn remove(&mut self, key: &str) -> bool {
+        let before = self.entries.len();
+        self.entries
+            .retain(|entry| !matches!(entry, EnvEntry::Var { var, .. } if var.key == key));
+        self.entries.len() != before
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
         }
-        Commands::Completions { shell } => {
-            cmd_completions(shell)
+        fs::write(path, self.contents()).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    fn contents(&self) -> String {
+        let mut content = String::new();
+        for entry in &self.entries {
+            let lines = match entry {
+                EnvEntry::Text(line) | EnvEntry::Invalid(line) => std::slice::from_ref(line),
+                EnvEntry::Var { lines, .. } => lines.as_slice(),
+            };
+            for line in lines {
+                content.push_str(line);
+                content.push_str(self.newline);
+            }
+        }
+        content
+    }
+}
+
+impl EnvVar {
+    /// The definition as file lines, quoting the value as little as
+    /// possible but keeping an existing quote style.
+    fn render(&self) -> Vec<String> {
+        let bare = !self.value.is_empty()
+            && !self
+                .value
+                .chars()
+                .any(|c| c.is_whitespace() || matches!(c, '#' | '"' | '\'' | '\\'));
+        let value = match self.quote {
+            Some('\'') if !self.value.contains('\'') => format!("'{}'", self.value),
+            None if bare || self.value.is_empty() => self.value.clone(),
+            _ => {
+                let mut quoted = String::from('"');
+                for c in self.value.chars() {
+                    match c {
+                        '"' => quoted.push_str("\\\""),
+                        '\\' => quoted.push_str("\\\\"),
+                        '\n' => quoted.push_str("\\n"),
+                        '\r' => quoted.push_str("\\r"),
+                        '\t' => quoted.push_str("\\t"),
+                        c => quoted.push(c),
+                    }
+                }
+                quoted.push('"');
+                quoted
+            }
+        };
+        let export = if self.export { "export " } else { "" };
+        format!("{}{}={}{}", export, self.key, value, self.comment)
+            .split('\n')
+            .map(String::from)
+            .collect()
+    }
+}
+
+/// Parse the variable starting at `lines[0]`, returning it and how many
+/// lines it spans.
+fn parse_env_var(lines: &[&str]) -> Option<(EnvVar, usize)> {
+    let line = lines[0].trim_start();
+    let (export, line) = match line.strip_prefix("export") {
+        Some(rest) if rest.starts_with([' ', '\t']) => (true, rest.trim_start()),
+        _ => (false, line),
+    };
+    let (key, rest) = line.split_once('=')?;
+    let key = key.trim_end();
+    let mut chars = key.chars();
+    if !chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        || !chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+    {
+        return None;
+    }
+
+    let unquoted = rest.trim_start_matches([' ', '\t']);
+    let Some(quote) = unquoted.chars().next().filter(|c| matches!(c, '"' | '\'')) else {
+        // Bare: up to a comment that follows whitespace. A trailing `\`
+        // escapes the newline, unless the next line is a definition, comment
+        // or blank, so Windows paths like `C:\tools\` stay on their line.
+        let mut value = String::new();
+        let mut text = rest;
+        let mut used = 1;
+        loop {
+            let comment_at = text
+                .char_indices()
+                .find(|&(i, c)| c == '#' && text[..i].ends_with([' ', '\t']))
+                .map(|(i, _)| text[..i].trim_end_matches([' ', '\t']).len());
+            let (part, comment) = text.split_at(comment_at.unwrap_or(text.len()));
+            let next = lines.get(used).filter(|next| {
+                let next = next.trim_start();
+                !next.is_empty() && !next.starts_with('#') && parse_env_var(&[next]).is_none()
+            });
+            match (part.strip_suffix('\\'), next) {
+                (Some(part), Some(next)) if comment.is_empty() => {
+                    value.push_str(part);
+                    value.push('\n');
+                    text = next;
+                    used += 1;
+                }
+                _ => {
+                    value.push_str(part);
+                    let var = EnvVar {
+                        key: key.to_string(),
+                        value: value.trim().to_string(),
+                        export,
+                        quote: None,
+                        comment: comment.to_string(),
+                    };
+                    return Some((var, used));
+                }
+            }
+        }
+    };
+
+    let mut value = String::new();
+    let mut text = &unquoted[1..];
+    let mut used = 1;
+    loop {
+        let mut chars = text.char_indices();
+        while let Some((i, c)) = chars.next() {
+            if c == quote {
+                let comment = &text[i + 1..];
+                let tail = comment.trim_start();
+                if !tail.is_empty() && !tail.starts_with('#') {
+                    return None;
+                }
+                let var = EnvVar {
+                    key: key.to_string(),
+                    value,
+                    export,
+                    quote: Some(quote),
+                    comment: comment.to_string(),
+                };
+                return Some((var, used));
+            }
+            if quote == '"' && c == '\\' {
+                match chars.next().map(|(_, c)| c) {
+                    Some('n') => value.push('\n'),
+                    Some('r') => value.push('\r'),
+                    Some('t') => value.push('\t'),
+                    Some(c @ ('"' | '\\' | '$')) => value.push(c),
+                    Some(c) => {
+                        value.push('\\');
+                        value.push(c);
+                    }
+                    None => value.push('\\'),
+                }
+            } else {
+                value.push(c);
+            }
+        }
+        // The value continues on the next line
+        text = lines.get(used)?;
+        value.push('\n');
+        used += 1;
+    }
+}
+
+// ============================================================================
+// Secret Stores
+// ============================================================================
+
+/// A place variable values are kept (`secrets` in servers.json).
+trait SecretStore {
+    /// Where the values live, for messages.
+    fn describe(&self) -> String;
+    fn load(&self) -> Result<BTreeMap<String, String>>;
+    fn set(&self, key: &str, value: &str) -> Result<()>;
+    /// Returns whether `key` was stored.
+    fn remove(&self, key: &str) -> Result<bool>;
+}
+
+fn secret_store(paths: &McpPaths, config: &ServersConfig) -> Box<dyn SecretStore> {
+    match config.secrets() {
+        SecretsConfig::Env => Box::new(DotEnvStore {
+            path: paths.env_file.clone(),
+        }),
+        SecretsConfig::Age { file, identity } => Box::new(AgeStore {
+            file: config_path(paths, file.as_deref(), "secrets.age"),
+            identity: config_path(paths, identity.as_deref(), "age.key"),
+        }),
+        SecretsConfig::Keyring { service } => Box::new(KeyringStore {
+            service: service.clone().unwrap_or_else(|| "mcp".to_string()),
+        }),
+    }
+}
+
+/// A path from servers.json: `~/` is the home directory and relative paths
+/// are inside the MCP directory.
+fn config_path(paths: &McpPaths, path: Option<&Path>, default: &str) -> PathBuf {
+    match path {
+        Some(path) => match path.strip_prefix("~") {
+            Ok(rest) => paths.home.join(rest),
+            Err(_) => paths.mcp_dir.join(path),
+        },
+        None => paths.mcp_dir.join(default),
+    }
+}
+
+impl ServersConfig {
+    fn secrets(&self) -> &SecretsConfig {
+        self.secrets.as_ref().unwrap_or(&SecretsConfig::Env)
+    }
+}
+
+struct DotEnvStore {
+    path: PathBuf,
+}
+
+impl SecretStore for DotEnvStore {
+    fn describe(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    fn load(&self) -> Result<BTreeMap<String, String>> {
+        Ok(EnvFile::load(&self.path)?.vars())
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<()> {
+        let mut file = EnvFile::load(&self.path)?;
+        if file.set(key, value) {
+            file.save(&self.path)?;
+        }
+        Ok(())
+    }
+
+    fn remove(&self, key: &str) -> Result<bool> {
+        let mut file = EnvFile::load(&self.path)?;
+        let removed = file.remove(key);
+        if removed {
+            file.save(&self.path)?;
+        }
+        Ok(removed)
+    }
+}
+
+/// Values in .env format, encrypted to an age identity kept in a key file.
+struct AgeStore {
+    file: PathBuf,
+    identity: PathBuf,
+}
+
+impl AgeStore {
+    fn identity(&self) -> Result<age::x25519::Identity> {
+        let content = fs::read_to_string(&self.identity)
+            .with_context(|| format!("Failed to read age identity {}", self.identity.display()))?;
+        content
+            .lines()
+            .map(str::trim)
+            .find(|line| line.starts_with("AGE-SECRET-KEY-"))
+            .with_context(|| format!("No AGE-SECRET-KEY in {}", self.identity.display()))?
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid age identity {}: {}", self.identity.display(), e))
+    }
+
+    /// The identity, generating one the first time something is stored.
+    fn identity_or_create(&self) -> Result<age::x25519::Identity> {
+        if self.identity.exists() {
+            return self.identity();
+        }
+        use age::secrecy::ExposeSecret;
+        let identity = age::x25519::Identity::generate();
+        let content = format!(
+            "# created: {}\n# public key: {}\n{}\n",
+            chrono::Local::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            identity.to_public(),
+            identity.to_string().expose_secret()
+        );
+        if let Some(parent) = self.identity.parent() {
+            fs::create_dir_all(parent)?;
         }
+        write_private(&self.identity, &content)?;
+        println!("{} Created age identity {}", "✓".green(), self.identity.display());
+        Ok(identity)
+    }
+
+    fn read(&self) -> Result<EnvFile> {
+        if !self.file.exists() {
+            return Ok(EnvFile::parse(""));
+        }
+        let encrypted = fs::read(&self.file).with_context(|| format!("Failed to read {}", self.file.display()))?;
+        let plaintext = age::decrypt(&self.identity()?, &encrypted)
+            .with_context(|| format!("Failed to decrypt {}", self.file.display()))?;
+        Ok(EnvFile::parse(&String::from_utf8(plaintext)?))
+    }
+
+    fn write(&self, env: &EnvFile) -> Result<()> {
+        let recipient = self.identity_or_create()?.to_public();
+        let encrypted = age::encrypt_and_armor(&recipient, env.contents().as_bytes())?;
+        // Replace the store in one step so a failed write cannot lose it
+        let mut temp = self.file.as_os_str().to_owned();
+        temp.push(".tmp");
+        fs::write(&temp, encrypted)?;
+        fs::rename(&temp, &self.file).with_context(|| format!("Failed to write {}", self.file.display()))
+    }
+}
+
+impl SecretStore for AgeStore {
+    fn describe(&self) -> String {
+        format!("{} (age)", self.file.display())
+    }
+
+    fn load(&self) -> Result<BTreeMap<String, String>> {
+        Ok(self.read()?.vars())
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<()> {
+        let mut env = self.read()?;
+        if env.set(key, value) {
+            self.write(&env)?;
+        }
+        Ok(())
+    }
+
+    fn remove(&self, key: &str) -> Result<bool> {
+        let mut env = self.read()?;
+        let removed = env.remove(key);
+        if removed {
+            self.write(&env)?;
+        }
+        Ok(removed)
+    }
+}
+
+/// Values as OS credentials, one per variable. Credential stores cannot be
+/// listed, so an extra entry records the variable names.
+struct KeyringStore {
+    service: String,
+}
+
+const KEYRING_INDEX: &str = ".index";
+
+impl KeyringStore {
+    fn entry(&self, key: &str) -> Result<keyring::Entry> {
+        keyring::Entry::new(&self.service, key).with_context(|| format!("Keyring entry {}/{}", self.service, key))
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>> {
+        match self.entry(key)?.get_password() {
+            Ok(value) => Ok(Some(value)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Failed to read {} from the keyring", key)),
+        }
+    }
+
+    fn names(&self) -> Result<BTreeSet<String>> {
+        match self.get(KEYRING_INDEX)? {
+            Some(index) => serde_json::from_str(&index).context("Corrupt keyring index"),
+            None => Ok(BTreeSet::new()),
+        }
+    }
+
+    fn save_names(&self, names: &BTreeSet<String>) -> Result<()> {
+        self.entry(KEYRING_INDEX)?
+            .set_password(&serde_json::to_string(names)?)
+            .context("Failed to write the keyring index")
+    }
+}
+
+impl SecretStore for KeyringStore {
+    fn describe(&self) -> String {
+        format!("OS keyring (service '{}')", self.service)
+    }
+
+    fn load(&self) -> Result<BTreeMap<String, String>> {
+        let mut values = BTreeMap::new();
+        for name in self.names()? {
+            if let Some(value) = self.get(&name)? {
+                values.insert(name, value);
+            }
+        }
+        Ok(values)
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<()> {
+        self.entry(key)?
+            .set_password(value)
+            .with_context(|| format!("Failed to store {} in the keyring", key))?;
+        let mut names = self.names()?;
+        if names.insert(key.to_string()) {
+            self.save_names(&names)?;
+        }
+        Ok(())
+    }
+
+    fn remove(&self, key: &str) -> Result<bool> {
+        let removed = match self.entry(key)?.delete_credential() {
+            Ok(()) => true,
+            Err(keyring::Error::NoEntry) => false,
+            Err(e) => return Err(e).with_context(|| format!("Failed to remove {} from the keyring", key)),
+        };
+        let mut names = self.names()?;
+        if names.remove(key) {
+            self.save_names(&names)?;
+        }
+        Ok(removed)
+    }
+}
+
+// ============================================================================
+// Client Targets
+// ============================================================================
+
+/// A client whose MCP config `gen` can write.
+struct Target {
+    name: &'static str,
+    label: &'static str,
+    /// Key holding the server map in the client's config file
+    section: &'static str,
+    default_path: fn(&McpPaths) -> PathBuf,
+    /// Project-level config file relative to the project root, if the
+    /// client reads one
+    project_path: Option<&'static str>,
+    /// Syntax the client expands to an environment variable, if any
+    env_placeholder: Option<fn(&str) -> String>,
+    /// Build one server entry, or `None` to leave the server out
+    entry: fn(&ServerEntry, EntryConfig) -> Option<JsonMap>,
+    /// Top-level keys added when missing
+    extra: &'static [(&'static str, &'static str)],
+}
+
+type JsonMap = serde_json::Map<String, serde_json::Value>;
+
+const TARGETS: &[Target] = &[
+    Target {
+        name: "cursor",
+        label: "Cursor",
+        section: "mcpServers",
+        default_path: |paths| paths.home.join(".cursor").join("mcp.json"),
+        project_path: Some(".cursor/mcp.json"),
+        env_placeholder: Some(|var| format!("${{env:{}}}", var)),
+        entry: cursor_entry,
+        extra: &[],
+    },
+    Target {
+        name: "opencode",
+        label: "OpenCode",
+        section: "mcp",
+        default_path: |paths| paths.home.join(".config").join("opencode").join("opencode.json"),
+        project_path: Some("opencode.json"),
+        env_placeholder: Some(|var| format!("{{env:{}}}", var)),
+        entry: opencode_entry,
+        extra: &[("$schema", "https://opencode.ai/config.json")],
+    },
+    Target {
+        name: "claude",
+        label: "Claude Desktop",
+        section: "mcpServers",
+        default_path: |paths| paths.config_dir().join("Claude").join("claude_desktop_config.json"),
+        project_path: None,
+        env_placeholder: None,
+        entry: claude_entry,
+        extra: &[],
+    },
+    Target {
+        name: "vscode",
+        label: "VS Code",
+        section: "servers",
+        default_path: |paths| paths.config_dir().join("Code").join("User").join("mcp.json"),
+        project_path: Some(".vscode/mcp.json"),
+        env_placeholder: Some(|var| format!("${{env:{}}}", var)),
+        entry: vscode_entry,
+        extra: &[],
+    },
+    Target {
+        name: "zed",
+        label: "Zed",
+        section: "context_servers",
+        default_path: |paths| {
+            if cfg!(windows) {
+                paths.config_dir().join("Zed").join("settings.json")
+            } else {
+                paths.home.join(".config").join("zed").join("settings.json")
+            }
+        },
+        project_path: None,
+        env_placeholder: None,
+        entry: zed_entry,
+        extra: &[],
+    },
+    Target {
+        name: "windsurf",
+        label: "Windsurf",
+        section: "mcpServers",
+        default_path: |paths| {
+            paths
+                .home
+                .join(".codeium")
+                .join("windsurf")
+                .join("mcp_config.json")
+        },
+        project_path: None,
+        env_placeholder: Some(|var| format!("${{env:{}}}", var)),
+        entry: windsurf_entry,
+        extra: &[],
+    },
+    Target {
+        name: "continue",
+        label: "Continue",
+        section: "mcpServers",
+        default_path: |paths| paths.home.join(".continue").join("mcpServers").join("mcp.json"),
+        project_path: None,
+        env_placeholder: None,
+        entry: claude_entry,
+        extra: &[],
+    },
+];
+
+/// Targets written when servers.json has no `targets` list.
+const DEFAULT_TARGETS: &[&str] = &["cursor", "opencode"];
+
+fn find_target(name: &str) -> Result<&'static Target> {
+    TARGETS.iter().find(|t| t.name == name).with_context(|| {
+        let known: Vec<_> = TARGETS.iter().map(|t| t.name).collect();
+        format!("Unknown target '{}' (available: {})", name, known.join(", "))
+    })
+}
+
+/// How a client launches a server, independent of its config schema.
+enum Launch {
+    Stdio { command: String, args: Vec<String> },
+    Remote { url: String, transport: Transport },
+}
+
+/// A server prepared for the target-specific entry builders.
+struct ServerEntry<'a> {
+    server: &'a Server,
+    launch: Launch,
+    /// `mcp run <name>`, for clients that reach remote servers through the
+    /// stdio bridge
+    bridge: (String, Vec<String>),
+    /// `(server variable, .env variable, value from .env)` per declared var
+    env: Vec<(&'a str, &'a str, Option<String>)>,
+    env_vars: &'a BTreeMap<String, String>,
+}
+
+/// Blocks rendered for one target from a server's env, headers and OAuth
+/// settings, honouring `--env` and the target's placeholder syntax.
+struct EntryConfig {
+    env: Option<serde_json::Value>,
+    headers: Option<serde_json::Value>,
+    oauth: Option<serde_json::Value>,
+}
+
+impl<'a> ServerEntry<'a> {
+    /// `project_root` is set for servers from a project overlay.
+    fn new(
+        paths: &McpPaths,
+        name: &str,
+        server: &'a Server,
+        env_vars: &'a BTreeMap<String, String>,
+        project_root: Option<&Path>,
+    ) -> Self {
+        let launch = match &server.kind {
+            ServerKind::Remote(remote) => Launch::Remote {
+                url: remote.url.clone(),
+                transport: remote.transport(),
+            },
+            ServerKind::Native(native) => Launch::Stdio {
+                command: native.command.clone(),
+                args: native.args.clone(),
+            },
+            // Resolved when launched, through `mcp run`
+            ServerKind::Runtime(_) | ServerKind::Docker(_) => {
+                let (command, args) = paths.launcher(name, project_root);
+                Launch::Stdio { command, args }
+            }
+        };
+
+        let env = server
+            .env
+            .iter()
+            .flat_map(EnvSpec::bindings)
+            .map(|(target, source)| {
+                let value = env_vars.get(source).filter(|v| !v.is_empty()).cloned();
+                (target, source, value)
+            })
+            .collect();
+
+        Self {
+            server,
+            launch,
+            bridge: paths.launcher(name, project_root),
+            env,
+            env_vars,
+        }
+    }
+
+    fn config(&self, mode: EnvMode, placeholder: Option<fn(&str) -> String>) -> EntryConfig {
+        EntryConfig {
+            env: self.env_block(mode, placeholder),
+            headers: self.headers_block(mode, placeholder),
+            oauth: self.oauth_block(mode, placeholder),
+        }
+    }
+
+    /// The env block for one target, or `None` when there is nothing to write.
+    fn env_block(&self, mode: EnvMode, placeholder: Option<fn(&str) -> String>) -> Option<serde_json::Value> {
+        let block: JsonMap = match (mode, placeholder) {
+            (EnvMode::None, _) | (EnvMode::Placeholders, None) => return None,
+            (EnvMode::Placeholders, Some(placeholder)) => self
+                .env
+                .iter()
+                .map(|(target, source, _)| (target.to_string(), serde_json::json!(placeholder(source))))
+                .collect(),
+            (EnvMode::Values, _) => self
+                .env
+                .iter()
+                .filter_map(|(target, _, value)| {
+                    value.as_ref().map(|v| (target.to_string(), serde_json::json!(v)))
+                })
+                .collect(),
+        };
+        (!block.is_empty()).then_some(serde_json::Value::Object(block))
+    }
+
+    /// Headers with `${VAR}` references written as the target's placeholder,
+    /// or as .env values with `--env values`. `None` when there are no
+    /// headers, or when they reference variables the target cannot expand
+    /// and values were not asked for.
+    fn headers_block(&self, mode: EnvMode, placeholder: Option<fn(&str) -> String>) -> Option<serde_json::Value> {
+        let headers = self.server.remote().map(|r| &r.headers).filter(|h| !h.is_empty())?;
+        let mut missing = Vec::new();
+        let block: JsonMap = headers
+            .iter()
+            .map(|(key, value)| {
+                let value = match (mode, placeholder) {
+                    (EnvMode::Values, _) | (_, None) => {
+                        expand_env_refs(value, |var| self.env_vars.get(var).cloned(), &mut missing)
+                    }
+                    (_, Some(placeholder)) => expand_env_refs(value, |var| Some(placeholder(var)), &mut missing),
+                };
+                (key.clone(), serde_json::json!(value))
+            })
+            .collect();
+        let has_refs = headers.values().any(|v| v.contains("${"));
+        if placeholder.is_none() && mode != EnvMode::Values && has_refs {
+            return None;
+        }
+        Some(serde_json::Value::Object(block))
+    }
+
+    /// OAuth client settings in the camelCase shape clients use.
+    fn oauth_block(&self, mode: EnvMode, placeholder: Option<fn(&str) -> String>) -> Option<serde_json::Value> {
+        let oauth = self.server.remote()?.oauth.as_ref()?;
+        let mut map = JsonMap::new();
+        if let Some(client_id) = &oauth.client_id {
+            map.insert("clientId".to_string(), serde_json::json!(client_id));
+        }
+        let secret = oauth.client_secret_env.as_deref().and_then(|var| match (mode, placeholder) {
+            (EnvMode::Values, _) => self.env_vars.get(var).cloned(),
+            (_, Some(placeholder)) => Some(placeholder(var)),
+            (_, None) => None,
+        });
+        if let Some(secret) = secret {
+            map.insert("clientSecret".to_string(), serde_json::json!(secret));
+        }
+        if let Some(scopes) = &oauth.scopes {
+            map.insert("scope".to_string(), serde_json::json!(scopes.join(" ")));
+        }
+        Some(serde_json::Value::Object(map))
+    }
+
+    /// `command` plus `args` for clients that take a single argv array.
+    fn argv(command: &str, args: &[String]) -> Vec<String> {
+        std::iter::once(command.to_string())
+            .chain(args.iter().cloned())
+            .collect()
+    }
+}
+
+fn generate_target_config(
+    target: &Target,
+    entries: &[(&String, ServerEntry)],
+    env_mode: EnvMode,
+) -> serde_json::Value {
+    let mut servers = JsonMap::new();
+    for (name, entry) in entries {
+        let config = entry.config(env_mode, target.env_placeholder);
+        if let Some(value) = (target.entry)(entry, config) {
+            servers.insert(name.to_string(), serde_json::Value::Object(value));
+        }
+    }
+
+    let mut root = JsonMap::new();
+    root.insert(target.section.to_string(), serde_json::Value::Object(servers));
+    for (key, value) in target.extra {
+        root.insert(key.to_string(), serde_json::json!(value));
+    }
+    serde_json::Value::Object(root)
+}
+
+fn cursor_entry(entry: &ServerEntry, config: EntryConfig) -> Option<JsonMap> {
+    // Skip OpenCode-only servers
+    if entry.server.opencode_only {
+        return None;
+    }
+
+    let mut map = JsonMap::new();
+    map.insert("disabled".to_string(), serde_json::json!(entry.server.disabled));
+    match &entry.launch {
+        Launch::Remote { url, .. } => {
+            map.insert("url".to_string(), serde_json::json!(url));
+            if let Some(headers) = config.headers {
+                map.insert("headers".to_string(), headers);
+            }
+        }
+        Launch::Stdio { command, args } => {
+            map.insert("command".to_string(), serde_json::json!(command));
+            map.insert("args".to_string(), serde_json::json!(args));
+        }
+    }
+
+    let tools = entry.server.tools.clone().unwrap_or_default();
+    map.insert("alwaysAllow".to_string(), serde_json::json!(tools));
+    if let Some(env) = config.env {
+        map.insert("env".to_string(), env);
+    }
+    Some(map)
+}
+
+fn opencode_entry(entry: &ServerEntry, config: EntryConfig) -> Option<JsonMap> {
+    let mut map = JsonMap::new();
+    match &entry.launch {
+        Launch::Remote { url, .. } => {
+            map.insert("type".to_string(), serde_json::json!("remote"));
+            map.insert("url".to_string(), serde_json::json!(url));
+            if let Some(headers) = config.headers {
+                map.insert("headers".to_string(), headers);
+            }
+            if let Some(oauth) = config.oauth {
+                map.insert("oauth".to_string(), oauth);
+            }
+        }
+        Launch::Stdio { command, args } => {
+            map.insert("type".to_string(), serde_json::json!("local"));
+            map.insert("command".to_string(), serde_json::json!(ServerEntry::argv(command, args)));
+        }
+    }
+    if let Some(env) = config.env {
+        map.insert("environment".to_string(), env);
+    }
+    Some(map)
+}
+
+/// Claude Desktop (and Continue, which reads the same format) has no
+/// disabled flag and only launches local processes, so remote servers go
+/// through the `mcp run` bridge.
+fn claude_entry(entry: &ServerEntry, config: EntryConfig) -> Option<JsonMap> {
+    if entry.server.opencode_only || entry.server.disabled {
+        return None;
+    }
+    let (command, args) = match &entry.launch {
+        Launch::Stdio { command, args } => (command, args),
+        Launch::Remote { .. } => (&entry.bridge.0, &entry.bridge.1),
+    };
+
+    let mut map = JsonMap::new();
+    map.insert("command".to_string(), serde_json::json!(command));
+    map.insert("args".to_string(), serde_json::json!(args));
+    if let Some(env) = config.env {
+        map.insert("env".to_string(), env);
+    }
+    Some(map)
+}
+
+fn vscode_entry(entry: &ServerEntry, config: EntryConfig) -> Option<JsonMap> {
+    if entry.server.opencode_only || entry.server.disabled {
+        return None;
+    }
+
+    let mut map = JsonMap::new();
+    match &entry.launch {
+        Launch::Remote { url, transport } => {
+            map.insert("type".to_string(), serde_json::json!(transport.as_str()));
+            map.insert("url".to_string(), serde_json::json!(url));
+            if let Some(headers) = config.headers {
+                map.insert("headers".to_string(), headers);
+            }
+        }
+        Launch::Stdio { command, args } => {
+            map.insert("type".to_string(), serde_json::json!("stdio"));
+            map.insert("command".to_string(), serde_json::json!(command));
+            map.insert("args".to_string(), serde_json::json!(args));
+        }
+    }
+    if let Some(env) = config.env {
+        map.insert("env".to_string(), env);
+    }
+    Some(map)
+}
+
+/// Zed cannot expand variables in headers, so a remote server whose headers
+/// reference .env goes through the `mcp run` bridge unless `--env values`.
+fn zed_entry(entry: &ServerEntry, config: EntryConfig) -> Option<JsonMap> {
+    if entry.server.opencode_only {
+        return None;
+    }
+
+    let mut map = JsonMap::new();
+    map.insert("enabled".to_string(), serde_json::json!(!entry.server.disabled));
+    match (&entry.launch, config.headers) {
+        (Launch::Remote { url, .. }, headers) if headers.is_some() || entry.server.remote().is_none_or(|r| r.headers.is_empty()) => {
+            map.insert("url".to_string(), serde_json::json!(url));
+            if let Some(headers) = headers {
+                map.insert("headers".to_string(), headers);
+            }
+        }
+        (Launch::Remote { .. }, _) => {
+            map.insert("command".to_string(), serde_json::json!(entry.bridge.0));
+            map.insert("args".to_string(), serde_json::json!(entry.bridge.1));
+        }
+        (Launch::Stdio { command, args }, _) => {
+            map.insert("command".to_string(), serde_json::json!(command));
+            map.insert("args".to_string(), serde_json::json!(args));
+        }
+    }
+    if let Some(env) = config.env {
+        map.insert("env".to_string(), env);
+    }
+    Some(map)
+}
+
+fn windsurf_entry(entry: &ServerEntry, config: EntryConfig) -> Option<JsonMap> {
+    if entry.server.opencode_only {
+        return None;
+    }
+
+    let mut map = JsonMap::new();
+    map.insert("disabled".to_string(), serde_json::json!(entry.server.disabled));
+    match &entry.launch {
+        Launch::Remote { url, .. } => {
+            map.insert("serverUrl".to_string(), serde_json::json!(url));
+            if let Some(headers) = config.headers {
+                map.insert("headers".to_string(), headers);
+            }
+        }
+        Launch::Stdio { command, args } => {
+            map.insert("command".to_string(), serde_json::json!(command));
+            map.insert("args".to_string(), serde_json::json!(args));
+        }
+    }
+    if let Some(env) = config.env {
+        map.insert("env".to_string(), env);
+    }
+    Some(map)
+}
+
+// ============================================================================
+// Client Import
+// ============================================================================
+
+/// A server entry read back from a client config, before shape detection.
+#[derive(Default)]
+struct ClientEntry {
+    command: Option<String>,
+    args: Vec<String>,
+    url: Option<String>,
+    transport: Option<Transport>,
+    headers: BTreeMap<String, String>,
+    env: BTreeMap<String, String>,
+    disabled: bool,
+    tools: Vec<String>,
+}
+
+impl ClientEntry {
+    /// Read the union of every target's entry schema.
+    fn parse(value: &serde_json::Value) -> Self {
+        let strings = |key: &str| -> Vec<String> {
+            value
+                .get(key)
+                .and_then(|v| v.as_array())
+                .map(|items| {
+                    items
+                        .iter()
+                        .filter_map(|item| item.as_str().map(String::from))
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        let string = |key: &str| value.get(key).and_then(|v| v.as_str()).map(String::from);
+
+        let mut entry = ClientEntry {
+            url: string("url").or_else(|| string("serverUrl")),
+            args: strings("args"),
+            tools: strings("alwaysAllow"),
+            disabled: value.get("disabled").and_then(|v| v.as_bool()).unwrap_or(false)
+                || value.get("enabled").and_then(|v| v.as_bool()) == Some(false),
+            ..Default::default()
+        };
+
+        // OpenCode keeps the whole argv in `command`
+        match value.get("command") {
+            Some(serde_json::Value::Array(_)) => {
+                let mut argv = strings("command").into_iter();
+                entry.command = argv.next();
+                entry.args = argv.collect();
+            }
+            _ => entry.command = string("command"),
+        }
+
+        // VS Code names the remote transport in `type`
+        entry.transport = match string("type").as_deref() {
+            Some("sse") => Some(Transport::Sse),
+            Some("http" | "streamable-http" | "streamableHttp") => Some(Transport::Http),
+            _ => None,
+        };
+
+        for key in ["env", "environment"] {
+            if let Some(env) = value.get(key).and_then(|v| v.as_object()) {
+                for (name, val) in env {
+                    if let Some(val) = val.as_str() {
+                        entry.env.insert(name.clone(), val.to_string());
+                    }
+                }
+            }
+        }
+
+        // Client placeholders inside header values become `${VAR}`
+        if let Some(headers) = value.get("headers").and_then(|v| v.as_object()) {
+            for (name, val) in headers {
+                if let Some(val) = val.as_str() {
+                    let val = val.replace("${env:", "${").replace("{env:", "${");
+                    entry.headers.insert(name.clone(), val);
+                }
+            }
+        }
+        entry
+    }
+}
+
+/// Name of the variable a client placeholder (`${env:X}`, `{env:X}`, `${X}`)
+/// refers to, or `None` for a literal value.
+fn env_placeholder_name(value: &str) -> Option<&str> {
+    let inner = value
+        .strip_prefix("${env:")
+        .or_else(|| value.strip_prefix("{env:"))
+        .or_else(|| value.strip_prefix("${"))?;
+    let name = inner.strip_suffix('}')?;
+    (!name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'))
+        .then_some(name)
+}
+
+/// Program name without directory or Windows extension (`C:\\x\\npx.cmd` -> `npx`).
+fn program_stem(command: &str) -> String {
+    let file = command.rsplit(['/', '\\']).next().unwrap_or(command);
+    let stem = match file.rsplit_once('.') {
+        Some((stem, ext)) if ["exe", "cmd", "bat", "ps1"].contains(&ext.to_ascii_lowercase().as_str()) => stem,
+        _ => file,
+    };
+    stem.to_ascii_lowercase()
+}
+
+/// Map a client entry back onto a `Server`, recognising remote servers,
+/// scripts in a global node_modules, registry runtimes such as `npx -y pkg`,
+/// the legacy `mcp-run.cmd runtime:path` wrapper, and plain native commands.
+/// Returns `None` for entries that already launch through `mcp run`.
+fn detect_server_shape(
+    entry: &ClientEntry,
+    registry: &BTreeMap<String, RuntimeDef>,
+) -> Option<Server> {
+    if let Some(url) = &entry.url {
+        return Some(Server::new(ServerKind::Remote(RemoteServer {
+            url: url.clone(),
+            transport: entry.transport,
+            headers: entry.headers.clone(),
+            oauth: None,
+        })));
+    }
+
+    let mut command = entry.command.clone().unwrap_or_default();
+    let mut args = entry.args.clone();
+
+    // Unwrap `cmd /c <program> ...`
+    if program_stem(&command) == "cmd" && args.first().is_some_and(|a| a.eq_ignore_ascii_case("/c")) {
+        args.remove(0);
+        if !args.is_empty() {
+            command = args.remove(0);
+        }
+    }
+
+    let program = program_stem(&command);
+    let runtime_server = |runtime: &str, path: String, rest: &[String]| {
+        Server::new(ServerKind::Runtime(RuntimeServer {
+            runtime: runtime.to_string(),
+            path,
+            version: None,
+            args: rest.to_vec(),
+        }))
+    };
+
+    if program == "mcp" && args.iter().any(|a| a == "run") {
+        return None;
+    }
+
+    // Legacy runner: mcp-run.cmd node:@pkg/dist/index.js
+    if program == "mcp-run" {
+        if let Some((runtime, path)) = args.first().and_then(|spec| spec.split_once(':')) {
+            return Some(runtime_server(runtime, path.to_string(), &args[1..]));
+        }
+    }
+
+    // node/bun running a script out of a global node_modules
+    let script_index = match program.as_str() {
+        "node" => Some(0),
+        "bun" if args.first().map(String::as_str) == Some("run") => Some(1),
+        _ => None,
+    };
+    if let Some(index) = script_index {
+        if let Some(script) = args.get(index) {
+            let normalized = script.replace('\\', "/");
+            if let Some((_, path)) = normalized.rsplit_once("node_modules/") {
+                return Some(runtime_server(&program, path.to_string(), &args[index + 1..]));
+            }
+        }
+    }
+
+    if program == "docker" {
+        if let Some(docker) = parse_docker_run(&args) {
+            return Some(Server::new(ServerKind::Docker(docker)));
+        }
+    }
+
+    // Registry runtimes with a fixed prefix before `{path}` (npx -y, uvx, ...)
+    for (name, def) in registry {
+        if def.module_root.is_some() || program_stem(&def.program) != program {
+            continue;
+        }
+        let Some(path_index) = def.args.iter().position(|a| a == "{path}") else {
+            continue;
+        };
+        let prefix = &def.args[..path_index];
+        if args.len() > path_index && args[..path_index] == *prefix {
+            return Some(runtime_server(
+                name,
+                args[path_index].clone(),
+                &args[path_index + 1..],
+            ));
+        }
+    }
+
+    Some(Server::new(ServerKind::Native(NativeServer { command, args })))
+}
+
+/// `docker run` options that take a value as the next argument.
+const DOCKER_VALUE_OPTIONS: &[&str] = &[
+    "-e", "--env", "--env-file", "-v", "--volume", "--mount", "-p", "--publish", "--network", "--name",
+    "-w", "--workdir", "-u", "--user", "--entrypoint", "--platform", "-l", "--label", "--add-host",
+];
+
+/// Split `run [options] image [args]`, dropping the `-i`, `--rm` and `-t`
+/// that every docker server gets anyway.
+fn parse_docker_run(args: &[String]) -> Option<DockerServer> {
+    let mut rest = args.strip_prefix(&["run".to_string()])?.iter();
+    let mut docker_args = Vec::new();
+    while let Some(arg) = rest.next() {
+        if !arg.starts_with('-') {
+            return Some(DockerServer {
+                image: arg.clone(),
+                docker_args,
+                args: rest.cloned().collect(),
+            });
+        }
+        match arg.as_str() {
+            "-i" | "--interactive" | "--rm" | "-t" | "--tty" | "-it" | "-ti" => {}
+            option if DOCKER_VALUE_OPTIONS.contains(&option) => {
+                docker_args.push(arg.clone());
+                docker_args.extend(rest.next().cloned());
+            }
+            _ => docker_args.push(arg.clone()),
+        }
+    }
+    None
+}
+
+// ============================================================================
+// Traffic Recording
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Direction {
+    ClientToServer,
+    ServerToClient,
+}
+
+/// One line of a `--record` JSONL file.
+#[derive(Debug, Serialize, Deserialize)]
+struct RecordedMessage {
+    ts: String,
+    direction: Direction,
+    message: serde_json::Value,
+}
+
+struct Recorder {
+    file: Mutex<fs::File>,
+}
+
+impl Recorder {
+    fn create(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let file = fs::File::create(path)
+            .with_context(|| format!("Failed to create {}", path.display()))?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+
+    fn log(&self, direction: Direction, line: &str) {
+        // Non-JSON output (e.g. a stray console.log) is kept as a plain string
+        let message = serde_json::from_str(line)
+            .unwrap_or_else(|_| serde_json::Value::String(line.to_string()));
+        let entry = RecordedMessage {
+            ts: chrono::Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            direction,
+            message,
+        };
+        if let Ok(json) = serde_json::to_string(&entry) {
+            let mut file = self.file.lock().unwrap();
+            let _ = writeln!(file, "{}", json);
+            let _ = file.flush();
+        }
+    }
+}
+
+/// Where proxied messages are copied: a `--record` file and/or the
+/// server log when `log_traffic` is set.
+#[derive(Default)]
+struct Taps {
+    recorder: Option<Recorder>,
+    traffic_log: Option<Arc<RotatingLog>>,
+}
+
+impl Taps {
+    fn is_empty(&self) -> bool {
+        self.recorder.is_none() && self.traffic_log.is_none()
+    }
+
+    fn copy(&self, direction: Direction, line: &str) {
+        if line.trim().is_empty() {
+            return;
+        }
+        if let Some(recorder) = &self.recorder {
+            recorder.log(direction, line);
+        }
+        if let Some(log) = &self.traffic_log {
+            let source = match direction {
+                Direction::ClientToServer => "client",
+                Direction::ServerToClient => "server",
+            };
+            log.log(source, line);
+        }
+    }
+}
+
+/// Copy newline-delimited messages from `reader` to `writer`, tapping each one.
+fn pump(reader: impl BufRead, mut writer: impl Write, taps: &Taps, direction: Direction) -> io::Result<()> {
+    for line in reader.lines() {
+        let line = line?;
+        taps.copy(direction, &line);
+        writeln!(writer, "{}", line)?;
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+/// A client message from a recording and the server messages that followed it.
+struct Exchange {
+    request: serde_json::Value,
+    replies: Vec<serde_json::Value>,
+    used: bool,
+}
+
+/// Split a recording into server messages sent before the first client
+/// message, and the client/server exchanges after it. Responses are attached
+/// to the request with the matching id, since a client may pipeline several
+/// requests before the server answers; anything else the server sends is
+/// attached to the latest client message.
+fn load_recording(path: &Path) -> Result<(Vec<serde_json::Value>, Vec<Exchange>)> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+
+    let mut preamble = Vec::new();
+    let mut exchanges: Vec<Exchange> = Vec::new();
+
+    for (i, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: RecordedMessage = serde_json::from_str(line)
+            .with_context(|| format!("{}:{}: invalid recording entry", path.display(), i + 1))?;
+
+        if entry.direction == Direction::ClientToServer {
+            exchanges.push(Exchange {
+                request: entry.message,
+                replies: Vec::new(),
+                used: false,
+            });
+            continue;
+        }
+
+        let response_id = entry
+            .message
+            .get("id")
+            .filter(|_| entry.message.get("method").is_none());
+        let target = match response_id {
+            Some(id) => exchanges.iter_mut().rev().find(|e| {
+                e.request.get("id") == Some(id) && e.request.get("method").is_some()
+            }),
+            None => exchanges.last_mut(),
+        };
+        match target {
+            Some(exchange) => exchange.replies.push(entry.message),
+            None => preamble.push(entry.message),
+        }
+    }
+
+    Ok((preamble, exchanges))
+}
+
+/// Pick the recorded exchange that best answers `incoming`: an unused one with
+/// the same method and params, then any unused one with the same method, then
+/// fall back to reusing earlier exchanges.
+fn find_exchange<'a>(
+    exchanges: &'a mut [Exchange],
+    incoming: &serde_json::Value,
+) -> Option<&'a Exchange> {
+    let method = incoming.get("method")?;
+    let params = incoming.get("params");
+    let same_method = |e: &Exchange| e.request.get("method") == Some(method);
+    let same_params = |e: &Exchange| e.request.get("params") == params;
+
+    let index = exchanges
+        .iter()
+        .position(|e| !e.used && same_method(e) && same_params(e))
+        .or_else(|| exchanges.iter().position(|e| !e.used && same_method(e)))
+        .or_else(|| exchanges.iter().rposition(|e| same_method(e) && same_params(e)))
+        .or_else(|| exchanges.iter().rposition(same_method))?;
+
+    exchanges[index].used = true;
+    Some(&exchanges[index])
+}
+
+fn write_message(out: &mut impl Write, message: &serde_json::Value) -> io::Result<()> {
+    match message {
+        serde_json::Value::String(raw) => writeln!(out, "{}", raw)?,
+        _ => writeln!(out, "{}", message)?,
+    }
+    out.flush()
+}
+
+// ============================================================================
+// Remote Bridge
+// ============================================================================
+
+/// Stdio side of `mcp run` for a remote server: messages from stdin are sent
+/// over HTTP and everything the server sends back is written to `output`
+/// (stdout).
+struct RemoteBridge {
+    url: String,
+    headers: Vec<(String, String)>,
+    session: Mutex<Option<String>>,
+    output: Mutex<Box<dyn Write + Send>>,
+    taps: Taps,
+}
+
+impl RemoteBridge {
+    fn request(&self, method: &str, url: &str) -> ureq::Request {
+        let mut request = ureq::request(method, url);
+        for (key, value) in &self.headers {
+            request = request.set(key, value);
+        }
+        if let Some(session) = self.session.lock().unwrap().as_deref() {
+            request = request.set("Mcp-Session-Id", session);
+        }
+        request
+    }
+
+    /// POST one client message and return the response.
+    fn post(&self, url: &str, message: &str) -> Result<ureq::Response> {
+        let request = self
+            .request("POST", url)
+            .set("Content-Type", "application/json")
+            .set("Accept", "application/json, text/event-stream");
+        match request.send_string(message) {
+            Ok(response) => Ok(response),
+            Err(ureq::Error::Status(code, response)) => {
+                let body = response.into_string().unwrap_or_default();
+                anyhow::bail!("HTTP {} from {}: {}", code, url, body.trim())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn emit(&self, message: &str) {
+        self.taps.copy(Direction::ServerToClient, message);
+        let mut output = self.output.lock().unwrap();
+        let _ = writeln!(output, "{}", message);
+        let _ = output.flush();
+    }
+
+    /// Answer a request the server could not be asked, so the client does
+    /// not wait for a reply that will never come.
+    fn emit_error(&self, message: &str, error: &anyhow::Error) {
+        eprintln!("[mcp] {:#}", error);
+        let id = serde_json::from_str::<serde_json::Value>(message)
+            .ok()
+            .and_then(|m| m.get("id").cloned());
+        if let Some(id) = id {
+            let reply = serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": -32000, "message": format!("{:#}", error) },
+            });
+            self.emit(&reply.to_string());
+        }
+    }
+
+    /// Streamable HTTP: every message is its own POST, answered with JSON,
+    /// an SSE stream, or 202 for notifications.
+    fn send_streamable(&self, message: &str) -> Result<()> {
+        let response = self.post(&self.url, message)?;
+        if let Some(session) = response.header("mcp-session-id") {
+            *self.session.lock().unwrap() = Some(session.to_string());
+        }
+
+        if response.content_type() == "text/event-stream" {
+            read_sse(BufReader::new(response.into_reader()), |event, data| {
+                if event == "message" && !data.is_empty() {
+                    self.emit(data);
+                }
+                true
+            })?;
+        } else {
+            let body = response.into_string()?;
+            match serde_json::from_str::<serde_json::Value>(&body) {
+                Ok(serde_json::Value::Array(batch)) => {
+                    for reply in batch {
+                        self.emit(&reply.to_string());
+                    }
+                }
+                _ if !body.trim().is_empty() => self.emit(body.trim()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parse a `text/event-stream`, calling `on_event(event, data)` for each
+/// event until it returns false or the stream ends.
+fn read_sse(reader: impl BufRead, mut on_event: impl FnMut(&str, &str) -> bool) -> io::Result<()> {
+    let mut event = String::new();
+    let mut data = String::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.is_empty() {
+            if !data.is_empty() || !event.is_empty() {
+                let name = if event.is_empty() { "message" } else { event.as_str() };
+                if !on_event(name, &data) {
+                    return Ok(());
+                }
+            }
+            event.clear();
+            data.clear();
+            continue;
+        }
+        if line.starts_with(':') {
+            continue;
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line.as_str(), ""),
+        };
+        match field {
+            "event" => event = value.to_string(),
+            "data" => {
+                if !data.is_empty() {
+                    data.push('\n');
+                }
+                data.push_str(value);
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Resolve the endpoint an SSE server announces against the stream URL.
+fn resolve_endpoint(base: &str, endpoint: &str) -> String {
+    if endpoint.contains("://") {
+        return endpoint.to_string();
+    }
+    let origin_end = base
+        .find("://")
+        .and_then(|i| base[i + 3..].find('/').map(|j| i + 3 + j))
+        .unwrap_or(base.len());
+    if endpoint.starts_with('/') {
+        format!("{}{}", &base[..origin_end], endpoint)
+    } else {
+        let dir_end = base.rfind('/').filter(|&i| i >= origin_end).unwrap_or(origin_end);
+        format!("{}/{}", &base[..dir_end], endpoint)
+    }
+}
+
+/// Fetch an access token with the client-credentials grant.
+fn fetch_oauth_token(oauth: &OAuthConfig, token_url: &str, env_vars: &BTreeMap<String, String>) -> Result<String> {
+    let client_id = oauth
+        .client_id
+        .as_deref()
+        .context("oauth.token_url is set but oauth.client_id is missing")?;
+    let secret = match &oauth.client_secret_env {
+        Some(var) => Some(lookup_env_var(env_vars, var).with_context(|| {
+            format!("OAuth client secret {} is not set. Set with: mcp env set {} <value>", var, var)
+        })?),
+        None => None,
+    };
+    let scope = oauth.scopes.as_ref().map(|s| s.join(" "));
+
+    let mut form = vec![("grant_type", "client_credentials"), ("client_id", client_id)];
+    if let Some(secret) = &secret {
+        form.push(("client_secret", secret));
+    }
+    if let Some(scope) = &scope {
+        form.push(("scope", scope));
+    }
+
+    let body = match ureq::post(token_url).send_form(&form) {
+        Ok(response) => response.into_string()?,
+        Err(ureq::Error::Status(code, response)) => {
+            let body = response.into_string().unwrap_or_default();
+            anyhow::bail!("Token request to {} failed with HTTP {}: {}", token_url, code, body.trim())
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let token: serde_json::Value = serde_json::from_str(&body).context("Invalid token response")?;
+    token
+        .get("access_token")
+        .and_then(|t| t.as_str())
+        .map(String::from)
+        .context("Token response has no access_token")
+}
+
+fn run_remote(name: &str, server: &RemoteServer, env_vars: &BTreeMap<String, String>, taps: Taps) -> Result<()> {
+    let url = server.url.clone();
+    let mut headers = resolve_headers(name, server, env_vars)?;
+
+    let has_auth = headers.iter().any(|(k, _)| k.eq_ignore_ascii_case("authorization"));
+    if let Some(oauth) = server.oauth.as_ref().filter(|_| !has_auth) {
+        match &oauth.token_url {
+            Some(token_url) => {
+                let token = fetch_oauth_token(oauth, token_url, env_vars)?;
+                headers.push(("Authorization".to_string(), format!("Bearer {}", token)));
+            }
+            None => eprintln!(
+                "[mcp] '{}' uses interactive OAuth, which the stdio bridge cannot do; set oauth.token_url or an Authorization header",
+                name
+            ),
+        }
+    }
+
+    let bridge = Arc::new(RemoteBridge {
+        url,
+        headers,
+        session: Mutex::new(None),
+        output: Mutex::new(Box::new(io::stdout())),
+        taps,
+    });
+
+    match server.transport() {
+        Transport::Http => bridge_streamable(bridge, io::stdin().lock()),
+        Transport::Sse => bridge_sse(bridge, BufReader::new(io::stdin())),
+    }
+}
+
+fn bridge_streamable(bridge: Arc<RemoteBridge>, input: impl BufRead) -> Result<()> {
+    let mut workers = Vec::new();
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        bridge.taps.copy(Direction::ClientToServer, &line);
+
+        // Requests are sent concurrently, since a response stream can stay
+        // open for a long-running call. `initialize` is waited for (the
+        // session id comes back on it), and notifications and responses go
+        // out in order, so `notifications/initialized` precedes later calls.
+        let message = serde_json::from_str::<serde_json::Value>(&line).ok();
+        let method = message.as_ref().and_then(|m| m.get("method")).and_then(|m| m.as_str());
+        let is_request = method.is_some() && message.as_ref().is_some_and(|m| m.get("id").is_some());
+        if !is_request || method == Some("initialize") {
+            if let Err(e) = bridge.send_streamable(&line) {
+                bridge.emit_error(&line, &e);
+            }
+            continue;
+        }
+        let worker_bridge = Arc::clone(&bridge);
+        workers.push(thread::spawn(move || {
+            if let Err(e) = worker_bridge.send_streamable(&line) {
+                worker_bridge.emit_error(&line, &e);
+            }
+        }));
+    }
+    for worker in workers {
+        let _ = worker.join();
+    }
+
+    // Let the server drop the session
+    if bridge.session.lock().unwrap().is_some() {
+        let _ = bridge.request("DELETE", &bridge.url).call();
+    }
+    Ok(())
+}
+
+/// Why the legacy SSE bridge stopped: the client's input ended, or the
+/// server's event stream did (after which no reply can arrive).
+enum SseBridgeEnd {
+    Input(Result<()>),
+    Stream(io::Result<()>),
+}
+
+/// Bridge to a legacy SSE server. Input is read on its own thread, so the
+/// bridge can stop when the event stream closes even while the client is
+/// quiet.
+fn bridge_sse(bridge: Arc<RemoteBridge>, input: impl BufRead + Send + 'static) -> Result<()> {
+    let response = match bridge.request("GET", &bridge.url).set("Accept", "text/event-stream").call() {
+        Ok(response) => response,
+        Err(ureq::Error::Status(code, _)) => anyhow::bail!("HTTP {} from {}", code, bridge.url),
+        Err(e) => return Err(e.into()),
+    };
+
+    let (endpoint_tx, endpoint_rx) = mpsc::channel();
+    let (end_tx, end_rx) = mpsc::channel();
+    let stream_bridge = Arc::clone(&bridge);
+    let stream_end = end_tx.clone();
+    thread::spawn(move || {
+        let mut endpoint_tx = Some(endpoint_tx);
+        let result = read_sse(BufReader::new(response.into_reader()), |event, data| {
+            match event {
+                "endpoint" => {
+                    if let Some(tx) = endpoint_tx.take() {
+                        let _ = tx.send(resolve_endpoint(&stream_bridge.url, data));
+                    }
+                }
+                "message" if !data.is_empty() => stream_bridge.emit(data),
+                _ => {}
+            }
+            true
+        });
+        let _ = stream_end.send(SseBridgeEnd::Stream(result));
+    });
+
+    let endpoint = endpoint_rx
+        .recv_timeout(std::time::Duration::from_secs(30))
+        .context("Server did not announce an SSE message endpoint")?;
+
+    let input_bridge = Arc::clone(&bridge);
+    thread::spawn(move || {
+        let _ = end_tx.send(SseBridgeEnd::Input(post_sse_input(&input_bridge, &endpoint, input)));
+    });
+
+    match end_rx.recv() {
+        Ok(SseBridgeEnd::Input(result)) => result,
+        Ok(SseBridgeEnd::Stream(Ok(()))) => anyhow::bail!("SSE stream closed by server"),
+        Ok(SseBridgeEnd::Stream(Err(e))) => Err(anyhow::Error::new(e).context("SSE stream failed")),
+        Err(_) => anyhow::bail!("SSE bridge stopped unexpectedly"),
+    }
+}
+
+/// Post each input line to the SSE message endpoint. Replies arrive on the
+/// event stream, not in the POST response.
+fn post_sse_input(bridge: &RemoteBridge, endpoint: &str, input: impl BufRead) -> Result<()> {
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        bridge.taps.copy(Direction::ClientToServer, &line);
+        if let Err(e) = bridge.post(endpoint, &line) {
+            bridge.emit_error(&line, &e);
+        }
+    }
+    Ok(())
+}
+
+// ============================================================================
+// HTTP Serving
+// ============================================================================
+
+/// Where messages for one HTTP response or event stream are sent.
+type Outbox = mpsc::Sender<String>;
+
+/// A client request forwarded to the shared server under a new id.
+struct PendingRequest {
+    session: String,
+    id: serde_json::Value,
+    progress_token: Option<serde_json::Value>,
+    outbox: Outbox,
+}
+
+impl PendingRequest {
+    /// Answer the client with an error instead of the server's response.
+    fn fail(self, reason: &str) {
+        let reply = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": self.id,
+            "error": { "code": -32000, "message": reason },
+        });
+        let _ = self.outbox.send(reply.to_string());
+    }
+}
+
+/// A connected client. `stream` is its standalone event stream (streamable
+/// HTTP GET or legacy SSE), used for server notifications and requests.
+#[derive(Default)]
+struct Session {
+    stream: Option<Outbox>,
+}
+
+/// One stdio server process shared by every session (HTTP clients for
+/// `serve`, socket clients for the daemon). Request ids (and progress tokens)
+/// are rewritten so sessions cannot collide, and `initialize` reaches the
+/// server once; later sessions get the cached result.
+#[derive(Default)]
+struct Hub {
+    stdin: Mutex<Option<Box<dyn Write + Send>>>,
+    next_id: AtomicU64,
+    pending: Mutex<HashMap<u64, PendingRequest>>,
+    sessions: Mutex<HashMap<String, Session>>,
+    init: Mutex<Option<serde_json::Value>>,
+    /// The first session's `initialize`, replayed when the process restarts
+    init_request: Mutex<Option<serde_json::Value>>,
+    initialized_sent: AtomicBool,
+}
+
+impl Hub {
+    /// Write a message to the server; false if no process is attached (e.g.
+    /// while the daemon waits to restart it) or it stopped reading.
+    fn send(&self, message: &serde_json::Value) -> bool {
+        match self.stdin.lock().unwrap().as_mut() {
+            Some(stdin) => writeln!(stdin, "{}", message).and_then(|_| stdin.flush()).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Point the hub at a (re)started process. Sessions that already
+    /// initialized keep working: their handshake is replayed, and the
+    /// response is dropped since no request is waiting for it.
+    fn attach_process(&self, stdin: impl Write + Send + 'static) {
+        *self.stdin.lock().unwrap() = Some(Box::new(stdin));
+        if let Some(mut request) = self.init_request.lock().unwrap().clone() {
+            request["id"] = serde_json::json!(self.next_id.fetch_add(1, Ordering::Relaxed));
+            self.send(&request);
+            if self.initialized_sent.load(Ordering::SeqCst) {
+                self.send(&serde_json::json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }));
+            }
+        }
+    }
+
+    /// Answer every in-flight request with an error, e.g. after a crash.
+    fn fail_pending(&self, reason: &str) {
+        for (_, pending) in self.pending.lock().unwrap().drain() {
+            pending.fail(reason);
+        }
+    }
+
+    fn open_session(&self) -> String {
+        let id = uuid::Uuid::new_v4().simple().to_string();
+        self.sessions.lock().unwrap().insert(id.clone(), Session::default());
+        id
+    }
+
+    fn has_session(&self, id: &str) -> bool {
+        self.sessions.lock().unwrap().contains_key(id)
+    }
+
+    /// End a session whose client stopped sending; requests in flight are
+    /// still answered.
+    fn close_session(&self, id: &str) {
+        self.sessions.lock().unwrap().remove(id);
+    }
+
+    /// End a session whose client is gone, cancelling the requests it was
+    /// still waiting on.
+    fn drop_session(&self, id: &str) {
+        self.close_session(id);
+        let abandoned: Vec<u64> = {
+            let mut pending = self.pending.lock().unwrap();
+            let ids: Vec<u64> = pending.iter().filter(|(_, p)| p.session == id).map(|(id, _)| *id).collect();
+            for request_id in &ids {
+                pending.remove(request_id);
+            }
+            ids
+        };
+        for request_id in abandoned {
+            self.send(&serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/cancelled",
+                "params": { "requestId": request_id, "reason": "Client disconnected" },
+            }));
+        }
+    }
+
+    fn set_stream(&self, id: &str, outbox: Outbox) {
+        if let Some(session) = self.sessions.lock().unwrap().get_mut(id) {
+            session.stream = Some(outbox);
+        }
+    }
+
+    /// Forward a client request under a fresh id; the response goes to `outbox`.
+    fn forward_request(&self, session: &str, mut message: serde_json::Value, outbox: &Outbox) {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let original_id = message["id"].take();
+        message["id"] = serde_json::json!(id);
+
+        let progress_token = message
+            .pointer_mut("/params/_meta/progressToken")
+            .map(|token| std::mem::replace(token, serde_json::json!(id)));
+
+        self.pending.lock().unwrap().insert(
+            id,
+            PendingRequest {
+                session: session.to_string(),
+                id: original_id,
+                progress_token,
+                outbox: outbox.clone(),
+            },
+        );
+        // No process to answer (it is restarting, or gone): fail now rather
+        // than leave the client waiting
+        if !self.send(&message) {
+            if let Some(pending) = self.pending.lock().unwrap().remove(&id) {
+                pending.fail("Server is not running");
+            }
+        }
+    }
+
+    /// Handle one message from a client. Responses to its requests are sent
+    /// to `outbox`; returns whether the message was a request.
+    fn client_message(&self, session: &str, mut message: serde_json::Value, outbox: &Outbox) -> bool {
+        let method = message.get("method").and_then(|m| m.as_str()).map(String::from);
+        let is_request = message.get("id").is_some() && method.is_some();
+
+        match method.as_deref() {
+            Some("initialize") if is_request => {
+                // Held across the round trip so concurrent first sessions
+                // do not initialize the server twice
+                let mut init = self.init.lock().unwrap();
+                if init.is_none() {
+                    *self.init_request.lock().unwrap() = Some(message.clone());
+                    let (tx, rx) = mpsc::channel();
+                    self.forward_request(session, message.clone(), &tx);
+                    drop(tx);
+                    if let Ok(reply) = rx.recv() {
+                        let reply: serde_json::Value = serde_json::from_str(&reply).unwrap_or_default();
+                        *init = reply.get("result").cloned();
+                        let _ = outbox.send(reply.to_string());
+                        return true;
+                    }
+                }
+                if let Some(result) = init.as_ref() {
+                    let reply = serde_json::json!({ "jsonrpc": "2.0", "id": message["id"], "result": result });
+                    let _ = outbox.send(reply.to_string());
+                }
+            }
+            Some("notifications/initialized") => {
+                if !self.initialized_sent.swap(true, Ordering::SeqCst) {
+                    self.send(&message);
+                }
+            }
+            Some("notifications/cancelled") => {
+                let request_id = message.pointer("/params/requestId").cloned();
+                let pending = self.pending.lock().unwrap();
+                let forwarded = pending
+                    .iter()
+                    .find(|(_, p)| p.session == session && Some(&p.id) == request_id.as_ref())
+                    .map(|(id, _)| *id);
+                drop(pending);
+                if let Some(id) = forwarded {
+                    message["params"]["requestId"] = serde_json::json!(id);
+                    self.send(&message);
+                }
+            }
+            _ if is_request => self.forward_request(session, message, outbox),
+            // Notifications, and responses to server requests
+            _ => {
+                self.send(&message);
+            }
+        }
+        is_request
+    }
+
+    /// Route one line of server output to the session it belongs to.
+    fn server_message(&self, line: &str) {
+        let Ok(mut message) = serde_json::from_str::<serde_json::Value>(line) else {
+            eprintln!("[mcp] {}", line);
+            return;
+        };
+        let method = message.get("method").and_then(|m| m.as_str()).map(String::from);
+
+        match (method.as_deref(), message.get("id").is_some()) {
+            // Response to a forwarded request
+            (None, true) => {
+                let pending = message["id"]
+                    .as_u64()
+                    .and_then(|id| self.pending.lock().unwrap().remove(&id));
+                if let Some(pending) = pending {
+                    message["id"] = pending.id;
+                    let _ = pending.outbox.send(message.to_string());
+                }
+            }
+            (Some("notifications/progress"), false) => {
+                let token = message.pointer("/params/progressToken").and_then(|t| t.as_u64());
+                let pending = self.pending.lock().unwrap();
+                if let Some(pending) = token.and_then(|t| pending.get(&t)) {
+                    if let Some(original) = &pending.progress_token {
+                        message["params"]["progressToken"] = original.clone();
+                    }
+                    let _ = pending.outbox.send(message.to_string());
+                }
+            }
+            // Server notification: every session with an open stream
+            (Some(_), false) => {
+                let mut sessions = self.sessions.lock().unwrap();
+                for session in sessions.values_mut() {
+                    if let Some(stream) = &session.stream {
+                        if stream.send(line.to_string()).is_err() {
+                            session.stream = None;
+                        }
+                    }
+                }
+            }
+            // Server request (sampling, roots, ...): the first session able to answer
+            (Some(_), true) => {
+                let mut sessions = self.sessions.lock().unwrap();
+                let delivered = sessions.values_mut().any(|session| match &session.stream {
+                    Some(stream) if stream.send(line.to_string()).is_ok() => true,
+                    Some(_) => {
+                        session.stream = None;
+                        false
+                    }
+                    None => false,
+                });
+                drop(sessions);
+                if !delivered {
+                    self.send(&serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "id": message["id"],
+                        "error": { "code": -32000, "message": "No client connected to handle this request" },
+                    }));
+                }
+            }
+            (None, false) => {}
+        }
+    }
+}
+
+/// A `text/event-stream` response written straight to the connection with
+/// chunked encoding, so each event reaches the client immediately.
+struct EventStream {
+    writer: Box<dyn Write + Send>,
+}
+
+impl EventStream {
+    fn start(request: tiny_http::Request, session: Option<&str>) -> io::Result<Self> {
+        let mut writer = request.into_writer();
+        write!(
+            writer,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nTransfer-Encoding: chunked\r\n"
+        )?;
+        if let Some(session) = session {
+            write!(writer, "Mcp-Session-Id: {}\r\n", session)?;
+        }
+        write!(writer, "\r\n")?;
+        writer.flush()?;
+        Ok(Self { writer })
+    }
+
+    fn chunk(&mut self, data: &str) -> io::Result<()> {
+        write!(self.writer, "{:x}\r\n{}\r\n", data.len(), data)?;
+        self.writer.flush()
+    }
+
+    fn event(&mut self, event: &str, data: &str) -> io::Result<()> {
+        let mut frame = format!("event: {}\n", event);
+        for line in data.lines() {
+            frame.push_str(&format!("data: {}\n", line));
+        }
+        frame.push('\n');
+        self.chunk(&frame)
+    }
+
+    /// Forward messages until every sender is gone, pinging an idle stream
+    /// so a vanished client is noticed.
+    fn pump(&mut self, rx: &mpsc::Receiver<String>) -> io::Result<()> {
+        loop {
+            match rx.recv_timeout(std::time::Duration::from_secs(15)) {
+                Ok(message) => self.event("message", &message)?,
+                Err(mpsc::RecvTimeoutError::Timeout) => self.chunk(": ping\n\n")?,
+                Err(mpsc::RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+        }
+    }
+
+    fn finish(mut self) -> io::Result<()> {
+        write!(self.writer, "0\r\n\r\n")?;
+        self.writer.flush()
+    }
+}
+
+fn http_header(name: &str, value: &str) -> tiny_http::Header {
+    tiny_http::Header::from_bytes(name.as_bytes(), value.as_bytes()).expect("valid header")
+}
+
+fn request_header<'r>(request: &'r tiny_http::Request, name: &str) -> Option<&'r str> {
+    request
+        .headers()
+        .iter()
+        .find(|h| h.field.as_str().as_str().eq_ignore_ascii_case(name))
+        .map(|h| h.value.as_str())
+}
+
+fn respond_status(request: tiny_http::Request, status: u16, body: &str) -> io::Result<()> {
+    request.respond(tiny_http::Response::from_string(body).with_status_code(status))
+}
+
+/// Browsers may only reach the server from a local page unless a token is
+/// required, which guards against DNS rebinding.
+fn origin_allowed(request: &tiny_http::Request, token: Option<&str>) -> bool {
+    let Some(origin) = request_header(request, "Origin") else {
+        return true;
+    };
+    let host = origin.split("://").nth(1).unwrap_or(origin);
+    let host = host.rsplit_once(':').map_or(host, |(h, port)| {
+        if port.chars().all(|c| c.is_ascii_digit()) { h } else { host }
+    });
+    token.is_some() || matches!(host, "localhost" | "127.0.0.1" | "[::1]")
+}
+
+fn handle_http(hub: &Hub, mut request: tiny_http::Request, token: Option<&str>) -> io::Result<()> {
+    if let Some(token) = token {
+        let expected = format!("Bearer {}", token);
+        if request_header(&request, "Authorization") != Some(expected.as_str()) {
+            let response = tiny_http::Response::from_string("Unauthorized")
+                .with_status_code(401)
+                .with_header(http_header("WWW-Authenticate", "Bearer"));
+            return request.respond(response);
+        }
+    }
+    if !origin_allowed(&request, token) {
+        return respond_status(request, 403, "Origin not allowed");
+    }
+
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+    let session = request_header(&request, "Mcp-Session-Id").map(String::from);
+    let accepts_sse = request_header(&request, "Accept").is_some_and(|a| a.contains("text/event-stream"));
+
+    match (request.method(), path) {
+        (tiny_http::Method::Post, "/mcp") => {
+            let mut body = String::new();
+            request.as_reader().read_to_string(&mut body)?;
+            let Ok(parsed) = serde_json::from_str::<serde_json::Value>(&body) else {
+                return respond_status(request, 400, "Invalid JSON");
+            };
+            let batch = parsed.is_array();
+            let messages = match parsed {
+                serde_json::Value::Array(items) => items,
+                message => vec![message],
+            };
+
+            let initializing = messages
+                .iter()
+                .any(|m| m.get("method").and_then(|m| m.as_str()) == Some("initialize"));
+            let session = match (session, initializing) {
+                (_, true) => hub.open_session(),
+                (Some(id), false) if hub.has_session(&id) => id,
+                (Some(_), false) => return respond_status(request, 404, "Unknown session"),
+                (None, false) => return respond_status(request, 400, "Missing Mcp-Session-Id"),
+            };
+
+            let (tx, rx) = mpsc::channel();
+            let mut requests = 0;
+            for message in messages {
+                if hub.client_message(&session, message, &tx) {
+                    requests += 1;
+                }
+            }
+            drop(tx);
+
+            if requests == 0 {
+                return request.respond(
+                    tiny_http::Response::empty(202).with_header(http_header("Mcp-Session-Id", &session)),
+                );
+            }
+            if accepts_sse && !batch {
+                let mut stream = EventStream::start(request, Some(&session))?;
+                stream.pump(&rx)?;
+                return stream.finish();
+            }
+
+            // Plain JSON: only the responses, not progress notifications
+            let replies: Vec<serde_json::Value> = rx
+                .iter()
+                .filter_map(|m| serde_json::from_str::<serde_json::Value>(&m).ok())
+                .filter(|m| m.get("method").is_none())
+                .collect();
+            let body = if batch {
+                serde_json::Value::Array(replies)
+            } else {
+                replies.into_iter().next().unwrap_or_default()
+            };
+            let response = tiny_http::Response::from_string(body.to_string())
+                .with_header(http_header("Content-Type", "application/json"))
+                .with_header(http_header("Mcp-Session-Id", &session));
+            request.respond(response)
+        }
+        (tiny_http::Method::Get, "/mcp") => {
+            if !accepts_sse {
+                return respond_status(request, 405, "Expected Accept: text/event-stream");
+            }
+            let Some(session) = session.filter(|id| hub.has_session(id)) else {
+                return respond_status(request, 404, "Unknown session");
+            };
+            let (tx, rx) = mpsc::channel();
+            hub.set_stream(&session, tx);
+            let mut stream = EventStream::start(request, Some(&session))?;
+            stream.pump(&rx)?;
+            stream.finish()
+        }
+        (tiny_http::Method::Delete, "/mcp") => match session {
+            Some(id) if hub.has_session(&id) => {
+                hub.drop_session(&id);
+                respond_status(request, 200, "")
+            }
+            _ => respond_status(request, 404, "Unknown session"),
+        },
+        // Legacy HTTP+SSE transport
+        (tiny_http::Method::Get, "/sse") => {
+            let session = hub.open_session();
+            let (tx, rx) = mpsc::channel();
+            hub.set_stream(&session, tx);
+            let mut stream = EventStream::start(request, None)?;
+            let result = stream
+                .event("endpoint", &format!("/messages?sessionId={}", session))
+                .and_then(|_| stream.pump(&rx));
+            // Replies go out on this stream, so nothing can reach the client now
+            hub.drop_session(&session);
+            result
+        }
+        (tiny_http::Method::Post, "/messages") => {
+            let session = query
+                .split('&')
+                .find_map(|pair| pair.strip_prefix("sessionId="))
+                .map(String::from);
+            let outbox = session.as_deref().and_then(|id| {
+                hub.sessions.lock().unwrap().get(id).and_then(|s| s.stream.clone())
+            });
+            let (Some(session), Some(outbox)) = (session, outbox) else {
+                return respond_status(request, 404, "Unknown session");
+            };
+            let mut body = String::new();
+            request.as_reader().read_to_string(&mut body)?;
+            let Ok(message) = serde_json::from_str::<serde_json::Value>(&body) else {
+                return respond_status(request, 400, "Invalid JSON");
+            };
+            // Replies arrive on the session's event stream
+            hub.client_message(&session, message, &outbox);
+            respond_status(request, 202, "Accepted")
+        }
+        _ => respond_status(request, 404, "Not found"),
+    }
+}
+
+// ============================================================================
+// Server Logs
+// ============================================================================
+
+/// Size at which a log is rotated to `<name>.log.1`.
+const LOG_MAX_BYTES: u64 = 1024 * 1024;
+/// Rotated logs kept besides the current one.
+const LOG_KEEP: usize = 3;
+
+/// Append-only log under `~/.mcp/logs` with size-based rotation. Clients
+/// of one server each run their own `mcp run`, so the size is read from disk
+/// on every write, a handle is reopened once another process rotated its
+/// file away, and rotating happens under `<name>.log.lock`.
+struct RotatingLog {
+    path: PathBuf,
+    file: Mutex<Option<fs::File>>,
+}
+
+impl RotatingLog {
+    fn open(path: PathBuf) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let log = Self {
+            path,
+            file: Mutex::new(None),
+        };
+        *log.file.lock().unwrap() = Some(log.open_file()?);
+        Ok(log)
+    }
+
+    fn open_file(&self) -> Result<fs::File> {
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("Failed to open {}", self.path.display()))
+    }
+
+    /// Whether `handle` is still the file at `path`, not one rotated away.
+    fn is_current(&self, handle: &fs::File) -> bool {
+        match (handle.metadata(), fs::metadata(&self.path)) {
+            (Ok(open), Ok(on_disk)) => same_file(&open, &on_disk),
+            _ => false,
+        }
+    }
+
+    /// `<name>.log` -> `<name>.log.1` -> ... -> `<name>.log.N`, unless
+    /// another process is already at it or has just done it.
+    fn rotate(&self, incoming: u64) {
+        let lock = PathBuf::from(format!("{}.lock", self.path.display()));
+        // Left behind by a process that died while rotating
+        let stale = fs::metadata(&lock)
+            .and_then(|m| m.modified())
+            .is_ok_and(|t| t.elapsed().is_ok_and(|age| age.as_secs() > 10));
+        if stale {
+            let _ = fs::remove_file(&lock);
+        }
+        if fs::OpenOptions::new().write(true).create_new(true).open(&lock).is_err() {
+            return;
+        }
+
+        let size = fs::metadata(&self.path).map(|m| m.len()).unwrap_or(0);
+        if size + incoming > LOG_MAX_BYTES {
+            let _ = fs::remove_file(rotated_log(&self.path, LOG_KEEP));
+            for n in (1..LOG_KEEP).rev() {
+                let _ = fs::rename(rotated_log(&self.path, n), rotated_log(&self.path, n + 1));
+            }
+            let _ = fs::rename(&self.path, rotated_log(&self.path, 1));
+        }
+        let _ = fs::remove_file(&lock);
+    }
+
+    /// Append one timestamped line tagged with where it came from.
+    fn log(&self, source: &str, line: &str) {
+        let ts = chrono::Local::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+        let entry = format!("{} [{}] {}\n", ts, source, line);
+        let incoming = entry.len() as u64;
+
+        let mut file = self.file.lock().unwrap();
+        if !file.as_ref().is_some_and(|handle| self.is_current(handle)) {
+            *file = self.open_file().ok();
+        }
+        let size = fs::metadata(&self.path).map(|m| m.len()).unwrap_or(0);
+        if size + incoming > LOG_MAX_BYTES {
+            // Closed before renaming, which Windows requires
+            *file = None;
+            self.rotate(incoming);
+            *file = self.open_file().ok();
+        }
+        if let Some(handle) = file.as_mut() {
+            let _ = handle.write_all(entry.as_bytes());
+        }
+    }
+}
+
+#[cfg(unix)]
+fn same_file(a: &fs::Metadata, b: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    (a.dev(), a.ino()) == (b.dev(), b.ino())
+}
+
+/// Without file ids, a file rotated away differs in size or creation time;
+/// a concurrent write in between only costs a needless reopen.
+#[cfg(not(unix))]
+fn same_file(a: &fs::Metadata, b: &fs::Metadata) -> bool {
+    a.len() == b.len() && a.created().ok() == b.created().ok()
+}
+
+/// The `n`th rotated copy of a log, `<name>.log.<n>`.
+fn rotated_log(path: &Path, n: usize) -> PathBuf {
+    PathBuf::from(format!("{}.{}", path.display(), n))
+}
+
+/// When a log line was written, from its leading timestamp.
+fn log_line_time(line: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    let first = line.split_whitespace().next()?;
+    if let Ok(ts) = chrono::DateTime::parse_from_rfc3339(first) {
+        return Some(ts.with_timezone(&chrono::Utc));
+    }
+    // daemon.log: `2026-01-31 14:05:00 ...` in local time
+    let naive = chrono::NaiveDateTime::parse_from_str(line.get(..19)?, "%Y-%m-%d %H:%M:%S").ok()?;
+    local_time(naive)
+}
+
+fn local_time(naive: chrono::NaiveDateTime) -> Option<chrono::DateTime<chrono::Utc>> {
+    use chrono::TimeZone;
+    chrono::Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|t| t.with_timezone(&chrono::Utc))
+}
+
+/// Parse `--since`: a duration back from now (`30s`, `10m`, `2h`, `1d`), an
+/// RFC 3339 timestamp, or a local `YYYY-MM-DD[ HH:MM[:SS]]`.
+fn parse_since(value: &str) -> Result<chrono::DateTime<chrono::Utc>> {
+    let value = value.trim();
+    if let Some(unit) = value.chars().last().filter(|c| c.is_ascii_alphabetic()) {
+        if let Ok(amount) = value[..value.len() - 1].parse::<i64>() {
+            let duration = match unit {
+                's' => Some(chrono::Duration::seconds(amount)),
+                'm' => Some(chrono::Duration::minutes(amount)),
+                'h' => Some(chrono::Duration::hours(amount)),
+                'd' => Some(chrono::Duration::days(amount)),
+                _ => None,
+            };
+            if let Some(duration) = duration {
+                return Ok(chrono::Utc::now() - duration);
+            }
+        }
+    }
+    if let Ok(ts) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Ok(ts.with_timezone(&chrono::Utc));
+    }
+    let naive = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M"]
+        .iter()
+        .find_map(|format| chrono::NaiveDateTime::parse_from_str(value, format).ok())
+        .or_else(|| {
+            chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+        });
+    naive.and_then(local_time).with_context(|| {
+        format!(
+            "Invalid --since '{}': use a duration (30s, 10m, 2h, 1d) or a time (2026-01-31 14:00)",
+            value
+        )
+    })
+}
+
+/// Print a log line with its timestamp dimmed and its source coloured.
+fn print_log_line(line: &str) {
+    let parsed = line.split_once(' ').and_then(|(ts, rest)| {
+        let rest = rest.strip_prefix('[')?;
+        let (source, message) = rest.split_once("] ")?;
+        Some((ts, source, message))
+    });
+    match parsed {
+        Some((ts, source, message)) => {
+            let tag = format!("[{}]", source);
+            let tag = match source {
+                "mcp" => tag.yellow(),
+                "client" => tag.cyan(),
+                "server" => tag.green(),
+                _ => tag.normal(),
+            };
+            println!("{} {} {}", ts.dimmed(), tag, message);
+        }
+        None => println!("{}", line),
+    }
+}
+
+// ============================================================================
+// Daemon
+// ============================================================================
+
+/// Written by a running daemon so `mcp run` and `mcp daemon` can find it.
+#[derive(Debug, Serialize, Deserialize)]
+struct DaemonInfo {
+    pid: u32,
+    port: u16,
+    /// Required from every connection, so other local users cannot attach
+    token: String,
+    started: String,
+}
+
+/// First line a client sends on a daemon connection.
+#[derive(Debug, Serialize, Deserialize)]
+struct DaemonHello {
+    token: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    attach: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    control: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct DaemonServerStatus {
+    name: String,
+    state: String,
+    pid: Option<u32>,
+    restarts: u32,
+    since: Option<String>,
+    clients: usize,
+}
+
+/// A server the daemon keeps running.
+struct Supervised {
+    name: String,
+    hub: Arc<Hub>,
+    child: Mutex<Option<std::process::Child>>,
+    status: Mutex<DaemonServerStatus>,
+    log: RotatingLog,
+}
+
+struct Daemon {
+    info: DaemonInfo,
+    info_file: PathBuf,
+    servers: Vec<Arc<Supervised>>,
+    stopping: AtomicBool,
+}
+
+impl Daemon {
+    fn status(&self) -> Vec<DaemonServerStatus> {
+        self.servers
+            .iter()
+            .map(|s| DaemonServerStatus {
+                clients: s.hub.sessions.lock().unwrap().len(),
+                ..s.status.lock().unwrap().clone()
+            })
+            .collect()
+    }
+
+    fn shutdown(&self) -> ! {
+        self.stopping.store(true, Ordering::SeqCst);
+        for server in &self.servers {
+            if let Some(child) = server.child.lock().unwrap().as_mut() {
+                let _ = child.kill();
+            }
+            server.log.log("mcp", "stopped with the daemon");
+        }
+        let _ = fs::remove_file(&self.info_file);
+        eprintln!("{} daemon stopped", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"));
+        std::process::exit(0);
+    }
+}
+
+/// Restart delay after `failures` quick crashes in a row: 1s, 2s, 4s, ... 60s.
+fn restart_backoff(failures: u32) -> std::time::Duration {
+    std::time::Duration::from_secs((1u64 << failures.min(6)).min(60))
+}
+
+/// Run one server until the daemon stops, restarting it whenever it exits.
+fn supervise(
+    paths: &McpPaths,
+    config: &ServersConfig,
+    env_vars: &BTreeMap<String, String>,
+    daemon: &Daemon,
+    supervised: &Supervised,
+) {
+    let name = supervised.name.as_str();
+    let server = &config.servers[name];
+    let mut failures = 0;
+
+    while !daemon.stopping.load(Ordering::SeqCst) {
+        let started = std::time::Instant::now();
+        let spawned = server_command(paths, config, name, server, env_vars, &[]).and_then(|mut command| {
+            command
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()
+                .context("Failed to start server")
+        });
+
+        match spawned {
+            Err(e) => supervised.log.log("mcp", &format!("{:#}", e)),
+            Ok(mut child) => {
+                let (Some(stdin), Some(stdout), Some(stderr)) =
+                    (child.stdin.take(), child.stdout.take(), child.stderr.take())
+                else {
+                    let _ = child.kill();
+                    return;
+                };
+                supervised.hub.attach_process(stdin);
+
+                let hub = Arc::clone(&supervised.hub);
+                thread::spawn(move || {
+                    for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                        if !line.trim().is_empty() {
+                            hub.server_message(&line);
+                        }
+                    }
+                });
+                thread::scope(|scope| {
+                    scope.spawn(|| {
+                        for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                            supervised.log.log("stderr", &line);
+                        }
+                    });
+
+                    supervised.log.log("mcp", &format!("started (pid {})", child.id()));
+                    {
+                        let mut status = supervised.status.lock().unwrap();
+                        status.state = "running".to_string();
+                        status.pid = Some(child.id());
+                        status.since = Some(chrono::Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true));
+                    }
+                    *supervised.child.lock().unwrap() = Some(child);
+
+                    // Polled rather than waited on, so `shutdown` can take the
+                    // lock and kill the process
+                    let exit = loop {
+                        thread::sleep(std::time::Duration::from_millis(200));
+                        let mut child = supervised.child.lock().unwrap();
+                        match child.as_mut().map(|c| c.try_wait()) {
+                            Some(Ok(None)) => continue,
+                            Some(Ok(Some(status))) => break status.to_string(),
+                            Some(Err(e)) => break e.to_string(),
+                            None => break "killed".to_string(),
+                        }
+                    };
+                    *supervised.child.lock().unwrap() = None;
+                    *supervised.hub.stdin.lock().unwrap() = None;
+                    supervised.log.log("mcp", &format!("exited ({})", exit));
+                });
+                supervised.hub.fail_pending(&format!("Server '{}' exited", name));
+            }
+        }
+
+        if daemon.stopping.load(Ordering::SeqCst) {
+            break;
+        }
+        // A server that stayed up a while is not crash-looping
+        if started.elapsed() > std::time::Duration::from_secs(30) {
+            failures = 0;
+        }
+        let delay = restart_backoff(failures);
+        failures += 1;
+        {
+            let mut status = supervised.status.lock().unwrap();
+            status.state = format!("restarting in {}s", delay.as_secs());
+            status.pid = None;
+            status.restarts += 1;
+        }
+        supervised.log.log("mcp", &format!("restarting in {}s", delay.as_secs()));
+        thread::sleep(delay);
+    }
+    supervised.status.lock().unwrap().state = "stopped".to_string();
+}
+
+/// Serve one daemon connection: a control request, or a client attaching
+/// to a server for the rest of the connection.
+fn handle_daemon_client(daemon: &Daemon, stream: std::net::TcpStream) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+
+    let mut hello = String::new();
+    reader.read_line(&mut hello)?;
+    let hello: Option<DaemonHello> = serde_json::from_str(&hello).ok();
+    let Some(hello) = hello.filter(|h| h.token == daemon.info.token) else {
+        return writeln!(writer, "{}", serde_json::json!({ "error": "unauthorized" }));
+    };
+
+    match (hello.control.as_deref(), hello.attach) {
+        (Some("status"), _) => writeln!(writer, "{}", serde_json::json!({ "servers": daemon.status() })),
+        (Some("stop"), _) => {
+            writeln!(writer, "{}", serde_json::json!({ "ok": true }))?;
+            daemon.shutdown()
+        }
+        (None, Some(name)) => {
+            let Some(supervised) = daemon.servers.iter().find(|s| s.name == name) else {
+                return writeln!(writer, "{}", serde_json::json!({ "error": format!("'{}' is not run by the daemon", name) }));
+            };
+            writeln!(writer, "{}", serde_json::json!({ "ok": true }))?;
+
+            let hub = &supervised.hub;
+            let session = hub.open_session();
+            let (tx, rx) = mpsc::channel::<String>();
+            hub.set_stream(&session, tx.clone());
+            let (writer_hub, writer_session) = (Arc::clone(hub), session.clone());
+            thread::spawn(move || {
+                for message in rx {
+                    if writeln!(writer, "{}", message).and_then(|_| writer.flush()).is_err() {
+                        writer_hub.drop_session(&writer_session);
+                        break;
+                    }
+                }
+            });
+
+            for line in reader.lines() {
+                let Ok(line) = line else {
+                    hub.drop_session(&session);
+                    return Ok(());
+                };
+                if let Ok(message) = serde_json::from_str::<serde_json::Value>(&line) {
+                    hub.client_message(&session, message, &tx);
+                }
+            }
+            // The client only closed its side; the writer finishes once
+            // in-flight requests are answered
+            hub.close_session(&session);
+            Ok(())
+        }
+        _ => writeln!(writer, "{}", serde_json::json!({ "error": "unknown request" })),
+    }
+}
+
+/// Open a daemon connection and send `hello`, returning the stream and the
+/// daemon's first reply.
+fn daemon_connect(
+    paths: &McpPaths,
+    attach: Option<&str>,
+    control: Option<&str>,
+) -> Option<(std::net::TcpStream, serde_json::Value)> {
+    let info: DaemonInfo = serde_json::from_str(&fs::read_to_string(&paths.daemon_file).ok()?).ok()?;
+    let addr = std::net::SocketAddr::from(([127, 0, 0, 1], info.port));
+    let mut stream = std::net::TcpStream::connect_timeout(&addr, std::time::Duration::from_secs(1)).ok()?;
+    let hello = DaemonHello {
+        token: info.token,
+        attach: attach.map(String::from),
+        control: control.map(String::from),
+    };
+    writeln!(stream, "{}", serde_json::to_string(&hello).ok()?).ok()?;
+
+    // Read the reply byte by byte so nothing after it is buffered away
+    let mut reply = Vec::new();
+    let mut byte = [0u8];
+    while io::Read::read(&mut stream, &mut byte).ok()? == 1 && byte[0] != b'\n' {
+        reply.push(byte[0]);
+    }
+    let reply = serde_json::from_slice(&reply).ok()?;
+    Some((stream, reply))
+}
+
+/// Attach to the daemon's instance of `name`, if it runs one.
+fn daemon_attach(paths: &McpPaths, name: &str) -> Option<std::net::TcpStream> {
+    let (stream, reply) = daemon_connect(paths, Some(name), None)?;
+    (reply.get("ok") == Some(&serde_json::json!(true))).then_some(stream)
+}
+
+/// Proxy stdio to a daemon connection until the daemon closes it.
+fn run_attached(stream: std::net::TcpStream, taps: Taps) -> Result<()> {
+    let taps = Arc::new(taps);
+
+    let to_daemon = stream.try_clone()?;
+    let stdin_taps = Arc::clone(&taps);
+    thread::spawn(move || {
+        let _ = pump(io::stdin().lock(), &to_daemon, &stdin_taps, Direction::ClientToServer);
+        let _ = to_daemon.shutdown(std::net::Shutdown::Write);
+    });
+
+    pump(BufReader::new(stream), io::stdout().lock(), &taps, Direction::ServerToClient)?;
+    Ok(())
+}
+
+fn run_daemon(paths: &McpPaths, profile: Option<&str>) -> Result<()> {
+    if daemon_connect(paths, None, Some("status")).is_some() {
+        anyhow::bail!("The daemon is already running");
+    }
+
+    // Shared by every client, so never a project's servers
+    let mut config = load_servers(paths)?;
+    config.apply_profile(profile)?;
+    let env_vars = load_env(paths, &config)?;
+
+    let listener = std::net::TcpListener::bind(("127.0.0.1", 0)).context("Failed to open daemon socket")?;
+    let info = DaemonInfo {
+        pid: std::process::id(),
+        port: listener.local_addr()?.port(),
+        token: uuid::Uuid::new_v4().simple().to_string(),
+        started: chrono::Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+    };
+
+    let mut servers = Vec::new();
+    for (name, server) in &config.servers {
+        let local = match &server.kind {
+            ServerKind::Runtime(_) | ServerKind::Native(_) | ServerKind::Docker(_) => true,
+            // Clients bridge to these themselves
+            ServerKind::Remote(_) => false,
+        };
+        if server.disabled || server.standalone || !local {
+            continue;
+        }
+        servers.push(Arc::new(Supervised {
+            name: name.clone(),
+            hub: Arc::new(Hub::default()),
+            child: Mutex::new(None),
+            status: Mutex::new(DaemonServerStatus {
+                name: name.clone(),
+                state: "starting".to_string(),
+                ..Default::default()
+            }),
+            log: RotatingLog::open(paths.server_log(name))?,
+        }));
+    }
+
+    paths.ensure_mcp_dir()?;
+    write_private(&paths.daemon_file, &serde_json::to_string_pretty(&info)?)?;
+    let daemon = Daemon {
+        info,
+        info_file: paths.daemon_file.clone(),
+        servers,
+        stopping: AtomicBool::new(false),
+    };
+    eprintln!(
+        "{} daemon started (pid {}, port {}) with {} server(s)",
+        chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+        daemon.info.pid,
+        daemon.info.port,
+        daemon.servers.len()
+    );
+
+    thread::scope(|scope| {
+        for supervised in &daemon.servers {
+            let (config, env_vars, daemon) = (&config, &env_vars, &daemon);
+            scope.spawn(move || supervise(paths, config, env_vars, daemon, supervised));
+        }
+        for stream in listener.incoming().map_while(Result::ok) {
+            let daemon = &daemon;
+            scope.spawn(move || {
+                let _ = handle_daemon_client(daemon, stream);
+            });
+        }
+    });
+    Ok(())
+}
+
+/// Write a file only the current user can read.
+fn write_private(path: &Path, content: &str) -> Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(path)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    file.write_all(content.as_bytes())?;
+    Ok(())
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+#[allow(clippy::too_many_arguments)]
+fn cmd_list(
+    paths: &McpPaths,
+    enabled: bool,
+    disabled: bool,
+    filter: &ServerFilter,
+    sort: ListSort,
+    columns: &[ListColumn],
+    wide: bool,
+    json: bool,
+    profile: Option<&str>,
+) -> Result<()> {
+    let config = load_active_servers(paths, profile)?;
+
+    let servers: Vec<_> = config
+        .servers
+        .iter()
+        .filter(|(_, s)| {
+            if enabled {
+                !s.disabled
+            } else if disabled {
+                s.disabled
+            } else {
+                true
+            }
+        })
+        .filter(|(name, server)| filter.matches(name, server))
+        .collect();
+
+    if json {
+        let map: BTreeMap<_, _> = servers.into_iter().collect();
+        println!("{}", serde_json::to_string_pretty(&map)?);
+        return Ok(());
+    }
+
+    let registry = runtime_registry(&config);
+    let lock = load_lock(paths)?;
+    let mut missing = 0;
+    let mut mismatched = Vec::new();
+    let mut rows: Vec<_> = servers
+        .iter()
+        .map(|(name, server)| {
+            let package = server_package(server, &registry);
+            let installed = package.as_ref().is_none_or(|package| package.entry_point(paths).is_some());
+            // Locked version vs what is in node_modules
+            let mismatch = package.as_ref().filter(|_| installed).and_then(|package| {
+                let locked = lock.servers.get(name.as_str())?;
+                let version = package.installed_version(paths)?;
+                (locked.package != package.package
+                    || locked.version != version
+                    || locked.requested.as_deref() != package.requested)
+                    .then(|| (locked.clone(), version, package.requested.map(String::from)))
+            });
+            let status = if server.disabled {
+                "disabled"
+            } else if !installed {
+                missing += 1;
+                "not installed"
+            } else if let Some((locked, version, requested)) = mismatch {
+                mismatched.push((name.to_string(), locked, version, requested));
+                "lock mismatch"
+            } else {
+                "enabled"
+            };
+            (name.as_str(), *server, status)
+        })
+        .collect();
+
+    // Stable, so ties stay in name order
+    match sort {
+        ListSort::Name => {}
+        ListSort::Runtime => rows.sort_by_key(|(_, server, _)| server.kind_label().to_string()),
+        ListSort::Type => rows.sort_by_key(|(_, server, _)| ServerType::of(&server.kind).as_str()),
+        ListSort::Status => rows.sort_by_key(|(_, _, status)| *status),
+        // Untagged servers last
+        ListSort::Tag => rows.sort_by_key(|(_, server, _)| (server.tags.is_empty(), server.tags.first().cloned())),
+    }
+
+    let cell = |column: ListColumn, (name, server, status): &(&str, &Server, &str)| match column {
+        ListColumn::Name => name.to_string(),
+        ListColumn::Type => ServerType::of(&server.kind).as_str().to_string(),
+        ListColumn::Runtime => server.kind_label().to_string(),
+        ListColumn::Status => match *status {
+            "enabled" => status.green().to_string(),
+            "disabled" => status.red().to_string(),
+            _ => status.yellow().to_string(),
+        },
+        ListColumn::Tags => server.tags.join(", "),
+        ListColumn::Description => {
+            let description = server.description.as_deref().unwrap_or_default();
+            if wide || description.chars().count() <= 40 {
+                description.to_string()
+            } else {
+                format!("{}…", description.chars().take(39).collect::<String>())
+            }
+        }
+    };
+
+    if rows.is_empty() {
+        println!("{}", "No servers found.".yellow());
+    } else {
+        let mut builder = tabled::builder::Builder::default();
+        builder.push_record(columns.iter().map(|column| column.header()));
+        for row in &rows {
+            builder.push_record(columns.iter().map(|column| cell(*column, row)));
+        }
+        println!("{}", builder.build());
+        if rows.len() == config.servers.len() {
+            print!("\n{} servers total", config.servers.len().to_string().cyan());
+        } else {
+            print!("\n{} of {} servers", rows.len().to_string().cyan(), config.servers.len());
+        }
+        match profile.or(config.profile.as_deref()) {
+            Some(profile) => println!(" (profile {})", profile.cyan()),
+            None => println!(),
+        }
+        if let Some(project_json) = &paths.project_json {
+            println!(
+                "{} from {}",
+                config.project.len().to_string().cyan(),
+                project_json.display()
+            );
+        }
+        if missing > 0 {
+            println!(
+                "{} enabled server(s) not installed. Run {} to install them.",
+                missing.to_string().yellow(),
+                "mcp install".cyan()
+            );
+        }
+        for (name, locked, version, requested) in &mismatched {
+            let requested = requested.as_deref();
+            if locked.requested.as_deref() != requested {
+                println!(
+                    "{}: version changed to {} (locked {}) - run {}",
+                    name.yellow(),
+                    requested.unwrap_or("latest"),
+                    locked.version,
+                    "mcp install".cyan()
+                );
+            } else {
+                println!(
+                    "{}: locked {}@{}, installed {} - run {}",
+                    name.yellow(),
+                    locked.package,
+                    locked.version,
+                    version,
+                    "mcp install".cyan()
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn cmd_run(
+    paths: &McpPaths,
+    name: &str,
+    extra_args: &[String],
+    record: Option<&Path>,
+    no_daemon: bool,
+) -> Result<()> {
+    let config = load_merged_servers(paths)?;
+    let server = config
+        .servers
+        .get(name)
+        .with_context(|| format!("Server '{}' not found", name))?;
+
+    // Load environment variables
+    let env_vars = load_env(paths, &config)?;
+
+    // A log that cannot be opened is not a reason to refuse to start
+    let log = RotatingLog::open(paths.server_log(name)).ok().map(Arc::new);
+    let taps = Taps {
+        recorder: record.map(Recorder::create).transpose()?,
+        traffic_log: log.clone().filter(|_| server.log_traffic),
+    };
+
+    // Determine how to run the server
+    match &server.kind {
+        // Bridge stdio to the server's HTTP endpoint
+        ServerKind::Remote(remote) => return run_remote(name, remote, &env_vars, taps),
+        ServerKind::Runtime(_) | ServerKind::Native(_) | ServerKind::Docker(_) => {}
+    }
+
+    // Share the daemon's instance when it has one; extra args or a project
+    // definition make this launch different from it
+    if !no_daemon && !server.standalone && extra_args.is_empty() && !config.project.contains(name) {
+        if let Some(stream) = daemon_attach(paths, name) {
+            return run_attached(stream, taps);
+        }
+    }
+
+    let command = server_command(paths, &config, name, server, &env_vars, extra_args)?;
+    run_process(command, log, taps)
+}
+
+/// The process for a local (runtime, native or docker) server, with its
+/// environment applied and stdio inherited.
+fn server_command(
+    paths: &McpPaths,
+    config: &ServersConfig,
+    name: &str,
+    server: &Server,
+    env_vars: &BTreeMap<String, String>,
+    extra_args: &[String],
+) -> Result<Command> {
+    let (mut command, path_dirs) = match &server.kind {
+        ServerKind::Runtime(runtime) => {
+            // Launched as declared in the runtime registry
+            let mut args = runtime.args.clone();
+            args.extend(extra_args.iter().cloned());
+
+            let registry = runtime_registry(config);
+            let def = lookup_runtime(&registry, &runtime.runtime)?;
+            let RuntimeLaunch {
+                program,
+                args,
+                path_dirs,
+            } = resolve_runtime(paths, def, &runtime.path, &args)?;
+
+            let mut command = Command::new(&program);
+            command.args(&args);
+            (command, path_dirs)
+        }
+        ServerKind::Native(native) => {
+            let mut command = Command::new(&native.command);
+            command.args(&native.args).args(extra_args);
+            (command, Vec::new())
+        }
+        ServerKind::Docker(docker) => {
+            let mut command = Command::new("docker");
+            command.args(["run", "-i", "--rm"]);
+            // Declared variables are passed on into the container
+            for (target, _) in server.env.iter().flat_map(EnvSpec::bindings) {
+                command.args(["-e", target]);
+            }
+            command
+                .args(&docker.docker_args)
+                .arg(&docker.image)
+                .args(&docker.args)
+                .args(extra_args);
+            (command, Vec::new())
+        }
+        ServerKind::Remote(remote) => {
+            anyhow::bail!("Server '{}' is remote ({}) and has no local process", name, remote.url)
+        }
+    };
+
+    command
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit());
+    apply_server_env(&mut command, name, server, env_vars)?;
+    if !path_dirs.is_empty() {
+        command.env("PATH", prepend_path(&path_dirs)?);
+    }
+
+    Ok(command)
+}
+
+/// Run the server process to completion and exit with its status code.
+/// Stderr is passed through and copied into the server log. When there are
+/// taps, stdin/stdout are proxied through us so every message can be copied.
+fn run_process(mut command: Command, log: Option<Arc<RotatingLog>>, taps: Taps) -> Result<()> {
+    let proxied = !taps.is_empty();
+    if proxied {
+        command.stdin(Stdio::piped()).stdout(Stdio::piped());
+    }
+    let mut child = command
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to start server")?;
+
+    let stderr = child.stderr.take().context("Failed to open server stderr")?;
+    let stderr_thread = thread::spawn(move || {
+        for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+            eprintln!("{}", line);
+            if let Some(log) = &log {
+                log.log("stderr", &line);
+            }
+        }
+    });
+
+    if proxied {
+        let taps = Arc::new(taps);
+        let child_stdin = child.stdin.take().context("Failed to open server stdin")?;
+        let child_stdout = child.stdout.take().context("Failed to open server stdout")?;
+
+        // Detached: blocks on our stdin until the client closes it, which in
+        // turn closes the server's stdin.
+        let stdin_taps = Arc::clone(&taps);
+        thread::spawn(move || {
+            let _ = pump(io::stdin().lock(), child_stdin, &stdin_taps, Direction::ClientToServer);
+        });
+
+        pump(BufReader::new(child_stdout), io::stdout().lock(), &taps, Direction::ServerToClient)?;
+    }
+
+    let status = child.wait()?;
+    let _ = stderr_thread.join();
+    std::process::exit(status.code().unwrap_or(1));
+}
+
+fn cmd_serve(paths: &McpPaths, name: &str, addr: &str, token: Option<String>, token_env: Option<&str>) -> Result<()> {
+    let config = load_merged_servers(paths)?;
+    let server = config
+        .servers
+        .get(name)
+        .with_context(|| format!("Server '{}' not found", name))?;
+    if let Some(remote) = server.remote() {
+        anyhow::bail!("Server '{}' is already remote - connect to {}", name, remote.url);
+    }
+
+    let env_vars = load_env(paths, &config)?;
+    let token = match token_env {
+        Some(var) => Some(
+            lookup_env_var(&env_vars, var)
+                .with_context(|| format!("{} is not set. Set with: mcp env set {} <value>", var, var))?,
+        ),
+        None => token,
+    };
+
+    let mut command = server_command(paths, &config, name, server, &env_vars, &[])?;
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .context("Failed to start server")?;
+    let stdout = child.stdout.take().context("Failed to open server stdout")?;
+
+    let hub = Arc::new(Hub::default());
+    hub.attach_process(child.stdin.take().context("Failed to open server stdin")?);
+
+    let http = tiny_http::Server::http(addr).map_err(|e| anyhow::anyhow!("Failed to listen on {}: {}", addr, e))?;
+    eprintln!(
+        "{} Serving '{}' at http://{}/mcp (legacy SSE: http://{}/sse){}",
+        "✓".green(),
+        name.cyan(),
+        addr,
+        addr,
+        if token.is_some() { ", bearer token required" } else { "" }
+    );
+
+    let output_hub = Arc::clone(&hub);
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            if !line.trim().is_empty() {
+                output_hub.server_message(&line);
+            }
+        }
+    });
+
+    let token = token.map(Arc::<str>::from);
+    thread::spawn(move || {
+        for request in http.incoming_requests() {
+            let hub = Arc::clone(&hub);
+            let token = token.clone();
+            thread::spawn(move || {
+                if let Err(e) = handle_http(&hub, request, token.as_deref()) {
+                    eprintln!("[mcp] {}", e);
+                }
+            });
+        }
+    });
+
+    // Serving ends when the server process does
+    let status = child.wait()?;
+    eprintln!("{} Server '{}' exited ({})", "!".yellow(), name, status);
+    std::process::exit(status.code().unwrap_or(1));
+}
+
+fn cmd_replay(file: &Path) -> Result<()> {
+    let (preamble, mut exchanges) = load_recording(file)?;
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    for message in &preamble {
+        write_message(&mut out, message)?;
+    }
+
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        let Ok(incoming) = serde_json::from_str::<serde_json::Value>(&line) else {
+            continue;
+        };
+        let id = incoming.get("id").cloned();
+
+        match find_exchange(&mut exchanges, &incoming) {
+            Some(exchange) => {
+                let recorded_id = exchange.request.get("id");
+                for reply in &exchange.replies {
+                    let mut reply = reply.clone();
+                    // Responses carry the recorded request id; answer with the live one
+                    if let (Some(recorded_id), Some(id)) = (recorded_id, &id) {
+                        if reply.get("id") == Some(recorded_id) && reply.get("method").is_none() {
+                            reply["id"] = id.clone();
+                        }
+                    }
+                    write_message(&mut out, &reply)?;
+                }
+            }
+            None => {
+                // Only requests expect an answer; notifications are dropped
+                if let (Some(id), Some(method)) = (id, incoming.get("method").and_then(|m| m.as_str())) {
+                    let error = serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": {
+                            "code": -32601,
+                            "message": format!("No recorded response for {}", method),
+                        },
+                    });
+                    write_message(&mut out, &error)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn cmd_gen(
+    paths: &McpPaths,
+    targets: Vec<String>,
+    dry_run: bool,
+    env_mode: EnvMode,
+    yes: bool,
+    check: bool,
+    project: bool,
+    profile: Option<&str>,
+) -> Result<()> {
+    // User-level files get the global servers only; project files only the
+    // overlay's, which clients layer over the user-level ones
+    let project_root = if project {
+        let root = paths.project_root().context(
+            "No .mcp/servers.json found in this directory or its parents",
+        )?;
+        Some(root)
+    } else {
+        None
+    };
+    let mut config = if project {
+        load_merged_servers(paths)?
+    } else {
+        load_servers(paths)?
+    };
+    config.apply_profile(profile)?;
+    let env_vars = load_env(paths, &config)?;
+
+    let targets = if targets.is_empty() {
+        if project {
+            // Every client with a project-level file
+            TARGETS
+                .iter()
+                .filter(|t| t.project_path.is_some())
+                .map(|t| t.name.to_string())
+                .collect()
+        } else {
+            config.enabled_targets()
+        }
+    } else {
+        targets
+    };
+    let targets = targets
+        .iter()
+        .map(|name| find_target(name))
+        .collect::<Result<Vec<_>>>()?;
+    if let Some(target) = targets.iter().find(|t| project && t.project_path.is_none()) {
+        anyhow::bail!("{} has no project-level config", target.label);
+    }
+
+    let entries: Vec<_> = config
+        .servers
+        .iter()
+        .filter(|(name, _)| !project || config.project.contains(*name))
+        .map(|(name, server)| (name, ServerEntry::new(paths, name, server, &env_vars, project_root)))
+        .collect();
+
+    if env_mode == EnvMode::Values {
+        for (name, entry) in &entries {
+            for (_, source, _) in entry.env.iter().filter(|(_, _, value)| value.is_none()) {
+                eprintln!("{} {}: {} is not set in .env, skipping", "!".yellow(), name, source);
+            }
+        }
+    }
+
+    let mut stale = 0;
+    for target in targets {
+        if env_mode == EnvMode::Placeholders && target.env_placeholder.is_none() {
+            eprintln!(
+                "{} {} does not expand env placeholders, omitting env",
+                "!".yellow(),
+                target.label
+            );
+        }
+
+        let path = match (project_root, target.project_path) {
+            (Some(root), Some(relative)) => root.join(relative),
+            _ => (target.default_path)(paths),
+        };
+        let generated = generate_target_config(target, &entries, env_mode);
+        let existing = read_json_file(&path)?;
+        let merged = merge_client_config(existing.clone(), generated, target.section, &config);
+
+        if check {
+            if !check_client_config(&path, existing.as_ref(), &merged) {
+                stale += 1;
+            }
+            continue;
+        }
+
+        let json = serde_json::to_string_pretty(&merged)?;
+        if dry_run {
+            println!("{}", format!("=== {} Config ===", target.label).cyan());
+            println!("{}", json);
+        } else {
+            write_client_config(&path, &json, yes)?;
+        }
+    }
+
+    if check {
+        if stale > 0 {
+            println!(
+                "\n{} config(s) out of date. Run {} to update.",
+                stale.to_string().red(),
+                if project { "mcp gen --project" } else { "mcp gen" }.cyan()
+            );
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if !dry_run {
+        println!("{}", "Done!".green());
+    }
+
+    Ok(())
+}
+
+/// Compare a generated config with the file on disk, ignoring key order and
+/// formatting. Prints a diff and returns false on drift.
+fn check_client_config(
+    path: &Path,
+    existing: Option<&serde_json::Value>,
+    expected: &serde_json::Value,
+) -> bool {
+    match existing {
+        Some(existing) if existing == expected => {
+            println!("{} {}", "✓".green(), path.display());
+            true
+        }
+        Some(existing) => {
+            println!("{} {} (out of date)", "✗".red(), path.display());
+            print_diff(
+                &canonical_json(existing),
+                &canonical_json(expected),
+            );
+            false
+        }
+        None => {
+            println!("{} {} (missing)", "✗".red(), path.display());
+            false
+        }
+    }
+}
+
+/// Pretty JSON with keys sorted at every level, so diffs show only real changes.
+fn canonical_json(value: &serde_json::Value) -> String {
+    fn sort_keys(value: &serde_json::Value) -> serde_json::Value {
+        match value {
+            serde_json::Value::Object(map) => {
+                let mut entries: Vec<_> = map.iter().collect();
+                entries.sort_by_key(|(key, _)| *key);
+                serde_json::Value::Object(
+                    entries
+                        .into_iter()
+                        .map(|(key, value)| (key.clone(), sort_keys(value)))
+                        .collect(),
+                )
+            }
+            serde_json::Value::Array(items) => {
+                serde_json::Value::Array(items.iter().map(sort_keys).collect())
+            }
+            other => other.clone(),
+        }
+    }
+    serde_json::to_string_pretty(&sort_keys(value)).unwrap_or_default() + "\n"
+}
+
+fn read_json_file(path: &Path) -> Result<Option<serde_json::Value>> {
+    match fs::read_to_string(path) {
+        Ok(content) => serde_json::from_str(&strip_json_comments(&content))
+            .map(Some)
+            .with_context(|| format!("Failed to parse {}", path.display())),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+/// Remove `//` and `/* */` comments (as allowed in Zed and VS Code settings)
+/// so the file parses as plain JSON. Comments are not written back.
+fn strip_json_comments(content: &str) -> String {
+    let mut out = String::with_capacity(content.len());
+    let mut chars = content.chars().peekable();
+    let mut in_string = false;
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            out.push(c);
+            match c {
+                '\\' => out.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match (c, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                out.push(c);
+            }
+            ('/', Some('/')) => {
+                while chars.next_if(|&c| c != '\n').is_some() {}
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                while let Some(c) = chars.next() {
+                    if c == '*' && chars.next_if_eq(&'/').is_some() {
+                        break;
+                    }
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Replace only the server `section` of an existing client config, keeping
+/// every other setting. Servers listed as `unmanaged` in servers.json are
+/// carried over from the existing section.
+fn merge_client_config(
+    existing: Option<serde_json::Value>,
+    generated: serde_json::Value,
+    section: &str,
+    config: &ServersConfig,
+) -> serde_json::Value {
+    let (Some(serde_json::Value::Object(mut merged)), serde_json::Value::Object(generated)) =
+        (existing, generated.clone())
+    else {
+        return generated;
+    };
+
+    let old_servers = merged
+        .get(section)
+        .and_then(|s| s.as_object())
+        .cloned()
+        .unwrap_or_default();
+
+    for (key, value) in generated {
+        if key != section {
+            merged.entry(key).or_insert(value);
+            continue;
+        }
+
+        let mut servers = value.as_object().cloned().unwrap_or_default();
+        let mut dropped = Vec::new();
+        for (name, entry) in &old_servers {
+            if servers.contains_key(name) {
+                continue;
+            }
+            if config.unmanaged.contains(name) {
+                servers.insert(name.clone(), entry.clone());
+            } else if !config.servers.contains_key(name) {
+                // Disabled servers are left out on purpose; only warn about
+                // entries servers.json does not know
+                dropped.push(name.as_str());
+            }
+        }
+        if !dropped.is_empty() {
+            eprintln!(
+                "{} Removing servers not in servers.json: {} (list them under \"unmanaged\" to keep)",
+                "!".yellow(),
+                dropped.join(", ")
+            );
+        }
+        merged.insert(key, serde_json::Value::Object(servers));
+    }
+
+    serde_json::Value::Object(merged)
+}
+
+/// Write a client config, showing a diff against the current file and
+/// keeping the previous version as `<file>.bak`.
+fn write_client_config(path: &Path, content: &str, yes: bool) -> Result<()> {
+    let old = fs::read_to_string(path).ok();
+
+    match &old {
+        Some(old) if old == content => {
+            println!("{} {} (unchanged)", "·".white(), path.display());
+            return Ok(());
+        }
+        Some(old) => {
+            println!("{} {}", "Changes to".cyan().bold(), path.display());
+            print_diff(old, content);
+
+            if !yes && io::stdin().is_terminal() && !confirm("Write changes?")? {
+                println!("Skipped {}", path.display());
+                return Ok(());
+            }
+
+            let mut backup = path.as_os_str().to_owned();
+            backup.push(".bak");
+            fs::copy(path, &backup)
+                .with_context(|| format!("Failed to back up {}", path.display()))?;
+        }
+        None => {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+    }
+
+    fs::write(path, content)?;
+    println!("{} {}", "Wrote:".green(), path.display());
+    Ok(())
+}
+
+fn print_diff(old: &str, new: &str) {
+    let diff = TextDiff::from_lines(old, new);
+    for hunk in diff.unified_diff().context_radius(3).iter_hunks() {
+        println!("{}", hunk.header().to_string().cyan());
+        for change in hunk.iter_changes() {
+            let line = match change.tag() {
+                ChangeTag::Delete => format!("-{}", change).red(),
+                ChangeTag::Insert => format!("+{}", change).green(),
+                ChangeTag::Equal => format!(" {}", change).white(),
+            };
+            print!("{}", line);
+            if change.missing_newline() {
+                println!();
+            }
+        }
+    }
+}
+
+fn confirm(prompt: &str) -> Result<bool> {
+    eprint!("{} [y/N] ", prompt);
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    Ok(input.trim().eq_ignore_ascii_case("y"))
+}
+
+fn cmd_add(
+    paths: &McpPaths,
+    name: Option<String>,
+    fields: ServerFields,
+    disabled: bool,
+    opencode_only: bool,
+) -> Result<()> {
+    let mut config = load_servers(paths)?;
+
+    let wizard = fields.is_empty();
+    if wizard && !io::stdin().is_terminal() {
+        anyhow::bail!("Pass --path, --command, --url or --image (or run in a terminal to be asked)");
+    }
+    let name = match name {
+        Some(name) => name,
+        None if wizard => prompt("Name", "")?,
+        None => anyhow::bail!("Server name required"),
+    };
+    if name.is_empty() {
+        anyhow::bail!("Server name required");
+    }
+    if config.servers.contains_key(&name) {
+        anyhow::bail!("Server '{}' already exists (change it with mcp edit)", name);
+    }
+    let fields = if wizard { prompt_fields(None)? } else { fields };
+
+    let mut server = Server {
+        disabled,
+        opencode_only,
+        ..Server::new(fields.kind(None)?)
+    };
+    fields.apply(&mut server);
+
+    config.servers.insert(name.clone(), server);
+    let config = config
+        .validated()
+        .map_err(|errors| invalid_config(&paths.servers_json, &errors))?;
+    save_servers(paths, &config)?;
+
+    println!("{} Added server '{}'", "✓".green(), name.cyan());
+    Ok(())
+}
+
+fn cmd_edit(paths: &McpPaths, name: &str, fields: ServerFields) -> Result<()> {
+    let mut config = load_servers(paths)?;
+    let server = config
+        .servers
+        .get_mut(name)
+        .with_context(|| format!("Server '{}' not found", name))?;
+
+    let fields = if fields.is_empty() {
+        if !io::stdin().is_terminal() {
+            anyhow::bail!("Pass the settings to change (see mcp edit --help)");
+        }
+        prompt_fields(Some(server))?
+    } else {
+        fields
+    };
+
+    let old = serde_json::to_string_pretty(server)?;
+    server.kind = fields.kind(Some(&server.kind))?;
+    fields.apply(server);
+    let new = serde_json::to_string_pretty(server)?;
+    if old == new {
+        println!("{} Server '{}' unchanged", "·".white(), name);
+        return Ok(());
+    }
+    print_diff(&old, &new);
+
+    let config = config
+        .validated()
+        .map_err(|errors| invalid_config(&paths.servers_json, &errors))?;
+    save_servers(paths, &config)?;
+
+    println!("{} Updated server '{}'", "✓".green(), name.cyan());
+    Ok(())
+}
+
+impl ServerFields {
+    fn is_empty(&self) -> bool {
+        let ServerFields {
+            server_type,
+            runtime,
+            path,
+            command,
+            url,
+            headers,
+            image,
+            docker_args,
+            args,
+            description,
+            tools,
+            env,
+        } = self;
+        server_type.is_none()
+            && [runtime, path, command, url, image, description, tools, env].iter().all(|f| f.is_none())
+            && headers.is_none()
+            && docker_args.is_none()
+            && args.is_none()
+    }
+
+    /// The server's kind with these settings applied over `current`. The
+    /// type is kept unless given or implied by a type's own flag; arguments
+    /// carry over when it changes.
+    fn kind(&self, current: Option<&ServerKind>) -> Result<ServerKind> {
+        let server_type = self
+            .server_type
+            .or_else(|| self.url.as_ref().map(|_| ServerType::Remote))
+            .or_else(|| self.command.as_ref().map(|_| ServerType::Native))
+            .or_else(|| self.image.as_ref().map(|_| ServerType::Docker))
+            .or_else(|| (self.path.is_some() || self.runtime.is_some()).then_some(ServerType::Runtime))
+            .or(current.map(ServerType::of))
+            .unwrap_or(ServerType::Runtime);
+
+        let owned = [
+            ("--runtime", self.runtime.is_some(), &[ServerType::Runtime][..]),
+            ("--path", self.path.is_some(), &[ServerType::Runtime]),
+            ("--command", self.command.is_some(), &[ServerType::Native]),
+            ("--url", self.url.is_some(), &[ServerType::Remote]),
+            ("--header", self.headers.is_some(), &[ServerType::Remote]),
+            ("--image", self.image.is_some(), &[ServerType::Docker]),
+            ("--docker-arg", self.docker_args.is_some(), &[ServerType::Docker]),
+            (
+                "--arg",
+                self.args.is_some(),
+                &[ServerType::Runtime, ServerType::Native, ServerType::Docker],
+            ),
+        ];
+        for (flag, given, types) in owned {
+            if given && !types.contains(&server_type) {
+                anyhow::bail!("{} is not used by {} servers", flag, server_type.as_str());
+            }
+        }
+
+        let args = match &self.args {
+            Some(args) => args.clone(),
+            None => current.map(|kind| kind.args().to_vec()).unwrap_or_default(),
+        };
+        let required = |value: Option<String>, what: &str| {
+            value
+                .filter(|v| !v.is_empty())
+                .with_context(|| format!("{} servers need {}", server_type.as_str(), what))
+        };
+
+        Ok(match server_type {
+            ServerType::Runtime => {
+                let current = match current {
+                    Some(ServerKind::Runtime(runtime)) => Some(runtime),
+                    _ => None,
+                };
+                ServerKind::Runtime(RuntimeServer {
+                    runtime: self
+                        .runtime
+                        .clone()
+                        .or_else(|| current.map(|c| c.runtime.clone()))
+                        .unwrap_or_else(default_runtime),
+                    path: required(self.path.clone().or_else(|| current.map(|c| c.path.clone())), "a path (--path)")?,
+                    version: current.and_then(|c| c.version.clone()),
+                    args,
+                })
+            }
+            ServerType::Native => {
+                let current = match current {
+                    Some(ServerKind::Native(native)) => Some(native),
+                    _ => None,
+                };
+                ServerKind::Native(NativeServer {
+                    command: required(
+                        self.command.clone().or_else(|| current.map(|c| c.command.clone())),
+                        "a command (--command)",
+                    )?,
+                    args,
+                })
+            }
+            ServerType::Remote => {
+                let current = match current {
+                    Some(ServerKind::Remote(remote)) => Some(remote),
+                    _ => None,
+                };
+                ServerKind::Remote(RemoteServer {
+                    url: required(self.url.clone().or_else(|| current.map(|c| c.url.clone())), "a URL (--url)")?,
+                    transport: current.and_then(|c| c.transport),
+                    headers: match &self.headers {
+                        Some(headers) => headers.iter().cloned().collect(),
+                        None => current.map(|c| c.headers.clone()).unwrap_or_default(),
+                    },
+                    oauth: current.and_then(|c| c.oauth.clone()),
+                })
+            }
+            ServerType::Docker => {
+                let current = match current {
+                    Some(ServerKind::Docker(docker)) => Some(docker),
+                    _ => None,
+                };
+                ServerKind::Docker(DockerServer {
+                    image: required(self.image.clone().or_else(|| current.map(|c| c.image.clone())), "an image (--image)")?,
+                    docker_args: self
+                        .docker_args
+                        .clone()
+                        .or_else(|| current.map(|c| c.docker_args.clone()))
+                        .unwrap_or_default(),
+                    args,
+                })
+            }
+        })
+    }
+
+    /// Apply the settings every server type shares. Empty values clear them.
+    fn apply(&self, server: &mut Server) {
+        if let Some(description) = &self.description {
+            server.description = (!description.is_empty()).then(|| description.clone());
+        }
+        if let Some(tools) = &self.tools {
+            let tools = split_list(tools);
+            server.tools = (!tools.is_empty()).then_some(tools);
+        }
+        if let Some(env) = &self.env {
+            server.env = parse_env_spec(env);
+        }
+    }
+}
+
+/// Ask for each setting of a server, offering the current value.
+fn prompt_fields(current: Option<&Server>) -> Result<ServerFields> {
+    let kind = current.map(|server| &server.kind);
+    let current_type = kind.map(ServerType::of).unwrap_or(ServerType::Runtime);
+    let server_type = loop {
+        let answer = prompt("Type (runtime, native, remote, docker)", current_type.as_str())?;
+        match ServerType::from_str(&answer, true) {
+            Ok(server_type) => break server_type,
+            Err(_) => eprintln!("{} Unknown type '{}'", "✗".red(), answer),
+        }
+    };
+    // Settings of the current type are only offered while it stays the same
+    let kind = kind.filter(|_| server_type == current_type);
+
+    let mut fields = ServerFields {
+        server_type: Some(server_type),
+        ..Default::default()
+    };
+    match (server_type, kind) {
+        (ServerType::Runtime, kind) => {
+            let current = match kind {
+                Some(ServerKind::Runtime(runtime)) => Some(runtime),
+                _ => None,
+            };
+            fields.runtime = Some(prompt("Runtime", current.map_or("node", |c| &c.runtime))?);
+            fields.path = Some(prompt("Entry point", current.map_or("", |c| &c.path))?);
+        }
+        (ServerType::Native, kind) => {
+            let current = match kind {
+                Some(ServerKind::Native(native)) => Some(native.command.as_str()),
+                _ => None,
+            };
+            fields.command = Some(prompt("Command", current.unwrap_or_default())?);
+        }
+        (ServerType::Remote, kind) => {
+            let current = match kind {
+                Some(ServerKind::Remote(remote)) => Some(remote),
+                _ => None,
+            };
+            fields.url = Some(prompt("URL", current.map_or("", |c| &c.url))?);
+            let headers: Vec<_> = current
+                .iter()
+                .flat_map(|c| &c.headers)
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect();
+            let answer = prompt("Headers (NAME=VALUE ...)", &join_args(&headers))?;
+            fields.headers = Some(
+                split_args(&answer)
+                    .iter()
+                    .map(|header| parse_header(header).map_err(anyhow::Error::msg))
+                    .collect::<Result<_>>()?,
+            );
+        }
+        (ServerType::Docker, kind) => {
+            let current = match kind {
+                Some(ServerKind::Docker(docker)) => Some(docker),
+                _ => None,
+            };
+            fields.image = Some(prompt("Image", current.map_or("", |c| &c.image))?);
+            let docker_args = current.map(|c| c.docker_args.as_slice()).unwrap_or_default();
+            fields.docker_args = Some(split_args(&prompt("docker run options", &join_args(docker_args))?));
+        }
+    }
+    if server_type != ServerType::Remote {
+        let args = current.map(|server| server.kind.args()).unwrap_or_default();
+        fields.args = Some(split_args(&prompt("Arguments", &join_args(args))?));
+    }
+
+    let description = current.and_then(|s| s.description.as_deref());
+    fields.description = Some(prompt("Description", description.unwrap_or_default())?);
+    let tools = current.and_then(|s| s.tools.as_ref()).map(|t| t.join(",")).unwrap_or_default();
+    fields.tools = Some(prompt("Tools (comma-separated)", &tools)?);
+    let env = current.and_then(|s| s.env.as_ref()).map(env_spec_list).unwrap_or_default();
+    fields.env = Some(prompt("Environment variables (comma-separated)", &env)?);
+    Ok(fields)
+}
+
+/// Ask for a value on the terminal. Enter keeps `default`, `-` clears it.
+fn prompt(label: &str, default: &str) -> Result<String> {
+    if default.is_empty() {
+        eprint!("{}: ", label);
+    } else {
+        eprint!("{} [{}]: ", label, default);
+    }
+    let mut input = String::new();
+    if io::stdin().read_line(&mut input)? == 0 {
+        anyhow::bail!("Aborted");
+    }
+    Ok(match input.trim() {
+        "" => default.to_string(),
+        "-" => String::new(),
+        value => value.to_string(),
+    })
+}
+
+fn split_list(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// `NAME` entries pass a variable through; `NAME=ENV_NAME` reads it from
+/// another .env variable.
+fn parse_env_spec(list: &str) -> Option<EnvSpec> {
+    let names = split_list(list);
+    if names.is_empty() {
+        return None;
+    }
+    if !names.iter().any(|name| name.contains('=')) {
+        return Some(EnvSpec::List(names));
+    }
+    let map = names
+        .iter()
+        .map(|name| match name.split_once('=') {
+            Some((name, source)) => (name.trim().to_string(), source.trim().to_string()),
+            None => (name.clone(), name.clone()),
+        })
+        .collect();
+    Some(EnvSpec::Map(map))
+}
+
+/// The inverse of `parse_env_spec`.
+fn env_spec_list(spec: &EnvSpec) -> String {
+    let names: Vec<_> = spec
+        .bindings()
+        .into_iter()
+        .map(|(name, source)| {
+            if name == source {
+                name.to_string()
+            } else {
+                format!("{}={}", name, source)
+            }
+        })
+        .collect();
+    names.join(",")
+}
+
+/// Split a line into arguments on whitespace, honoring single and double
+/// quotes.
+fn split_args(line: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut quote = None;
+    for c in line.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => current.push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                in_arg = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            (None, c) => {
+                current.push(c);
+                in_arg = true;
+            }
+        }
+    }
+    if in_arg {
+        args.push(current);
+    }
+    args
+}
+
+/// The inverse of `split_args`.
+fn join_args(args: &[String]) -> String {
+    let quoted: Vec<_> = args
+        .iter()
+        .map(|arg| {
+            if !arg.is_empty() && !arg.contains(char::is_whitespace) && !arg.contains(['"', '\'']) {
+                arg.clone()
+            } else if arg.contains('"') && arg.contains('\'') {
+                // No quote holds both, but adjacent quoted parts make one
+                // argument: it's -> 'it'"'"'s'
+                arg.split('\'').map(|part| format!("'{}'", part)).collect::<Vec<_>>().join("\"'\"")
+            } else if arg.contains('"') {
+                format!("'{}'", arg)
+            } else {
+                format!("\"{}\"", arg)
+            }
+        })
+        .collect();
+    quoted.join(" ")
+}
+
+fn cmd_import(
+    paths: &McpPaths,
+    from: &str,
+    path: Option<PathBuf>,
+    overwrite: bool,
+    dry_run: bool,
+) -> Result<()> {
+    let target = find_target(from)?;
+    let path = path.unwrap_or_else(|| (target.default_path)(paths));
+    let client = read_json_file(&path)?
+        .with_context(|| format!("{} not found", path.display()))?;
+    let entries = client
+        .get(target.section)
+        .and_then(|s| s.as_object())
+        .with_context(|| format!("No '{}' section in {}", target.section, path.display()))?;
+
+    let mut config = load_servers(paths)?;
+    let env_vars = load_env(paths, &config)?;
+    let registry = runtime_registry(&config);
+    let (mut added, mut conflicts) = (0, 0);
+    let mut new_env = BTreeMap::new();
+
+    for (name, value) in entries {
+        let entry = ClientEntry::parse(value);
+        let Some(mut server) = detect_server_shape(&entry, &registry) else {
+            println!("{} {} (already launched via mcp run)", "·".white(), name);
+            continue;
+        };
+        server.disabled = entry.disabled;
+        server.tools = (!entry.tools.is_empty()).then(|| entry.tools.clone());
+        server.description = Some(format!("Imported from {}", target.label));
+
+        // Secrets go to the secret store; servers.json only records the names.
+        // They are kept aside until the server itself is imported.
+        let mut bindings = BTreeMap::new();
+        let mut entry_env = BTreeMap::new();
+        let mut env_conflicts = Vec::new();
+        for (var, value) in &entry.env {
+            let source = match env_placeholder_name(value) {
+                Some(source) => source.to_string(),
+                None => {
+                    match env_vars.get(var) {
+                        Some(existing) if existing != value && !overwrite => env_conflicts.push(var),
+                        Some(existing) if existing == value => {}
+                        _ => {
+                            entry_env.insert(var.clone(), value.clone());
+                        }
+                    }
+                    var.clone()
+                }
+            };
+            bindings.insert(var.clone(), source);
+        }
+        if !bindings.is_empty() {
+            server.env = Some(if bindings.iter().all(|(k, v)| k == v) {
+                EnvSpec::List(bindings.into_keys().collect())
+            } else {
+                EnvSpec::Map(bindings)
+            });
+        }
+
+        if let Some(existing) = config.servers.get(name) {
+            let same = existing.kind == server.kind;
+            if same {
+                println!("{} {} (unchanged)", "·".white(), name);
+                continue;
+            }
+            if !overwrite {
+                println!(
+                    "{} {} already exists with a different definition (use --overwrite)",
+                    "!".yellow(),
+                    name
+                );
+                conflicts += 1;
+                continue;
+            }
+            // Keep our own metadata when replacing the launch definition
+            server.description = existing.description.clone();
+            server.tools = server.tools.or_else(|| existing.tools.clone());
+        }
+
+        println!("{} {}", "+".green(), name.cyan());
+        for var in env_conflicts {
+            println!(
+                "{} {}: .env already has a different {} (use --overwrite)",
+                "!".yellow(),
+                name,
+                var
+            );
+            conflicts += 1;
+        }
+        if dry_run {
+            println!("{}", serde_json::to_string_pretty(&server)?);
+        }
+        config.servers.insert(name.clone(), server);
+        new_env.append(&mut entry_env);
+        added += 1;
+    }
+
+    if dry_run {
+        println!("\n{}", "(dry run - no files changed)".yellow());
+    } else {
+        if added > 0 {
+            save_servers(paths, &config)?;
+        }
+        let store = secret_store(paths, &config);
+        for (var, value) in &new_env {
+            store.set(var, value)?;
+        }
+    }
+
+    println!(
+        "\n{} imported, {} conflict(s)",
+        added.to_string().green(),
+        if conflicts > 0 {
+            conflicts.to_string().red()
+        } else {
+            conflicts.to_string().normal()
+        }
+    );
+    Ok(())
+}
+
+fn cmd_install(paths: &McpPaths, names: &[String], dry_run: bool, profile: Option<&str>) -> Result<()> {
+    let config = load_active_servers(paths, profile)?;
+    let registry = runtime_registry(&config);
+    let mut lock = load_lock(paths)?;
+
+    // Locked servers get exactly their locked version
+    let mut packages: Vec<_> = select_packages(&config, &registry, names)?
+        .into_iter()
+        .map(|(package, users)| {
+            let locked = users
+                .iter()
+                .find_map(|name| lock.entry(name, &package))
+                .cloned();
+            (package, users, locked)
+        })
+        .collect();
+
+    // Without names, only what is missing, differs from the lock or whose
+    // lock entry is stale; installed packages not yet locked are locked as is
+    if names.is_empty() {
+        let mut newly_locked = 0;
+        packages.retain(|(package, users, locked)| {
+            let version = package.entry_point(paths).and_then(|_| package.installed_version(paths));
+            let Some(version) = version else {
+                return true;
+            };
+            match locked {
+                Some(locked) => locked.version != version,
+                None if users.iter().any(|name| lock.servers.contains_key(*name)) => true,
+                // A dry run does not ask the registry for the integrity
+                None if dry_run => {
+                    println!("{} {} {} (would be locked)", "·".white(), package.package, version);
+                    false
+                }
+                None => {
+                    let spec = format!("{}@{}", package.package, version);
+                    match registry_dist(paths, &spec) {
+                        Ok(dist) => {
+                            println!("{} {} {} (locked)", "✓".green(), package.package, version);
+                            lock_package(&mut lock, package, users, &version, &dist.integrity);
+                            newly_locked += 1;
+                        }
+                        Err(e) => println!("{} {} {} not locked: {:#}", "!".yellow(), package.package, version, e),
+                    }
+                    false
+                }
+            }
+        });
+        if newly_locked > 0 && !dry_run {
+            save_lock(paths, &mut lock)?;
+        }
+    }
+    if packages.is_empty() {
+        println!("{}", "Nothing to install.".green());
+        return Ok(());
+    }
+
+    let mut failed = 0;
+    for (package, users, locked) in &packages {
+        let spec = match (locked, package.requested) {
+            (Some(locked), _) => format!("{}@{}", package.package, locked.version),
+            (None, Some(requested)) => format!("{}@{}", package.package, requested),
+            (None, None) => package.package.to_string(),
+        };
+
+        // Entries from an older lockfile have no integrity to hold it to
+        let expected = locked
+            .as_ref()
+            .map(|locked| locked.integrity.as_str())
+            .filter(|integrity| !integrity.is_empty());
+        let result = install_package(paths, package, &spec, users, expected, dry_run).map(|dist| {
+            let dist = dist?;
+            if expected.is_none() {
+                lock_package(&mut lock, package, users, &dist.version, &dist.integrity);
+            }
+            Some(dist.version)
+        });
+        match result {
+            Ok(Some(version)) => println!("{} {} {}", "✓".green(), package.package, version),
+            Ok(None) => {}
+            Err(e) => {
+                println!("{} {:#}", "✗".red(), e);
+                failed += 1;
+            }
+        }
+    }
+
+    if dry_run {
+        println!("\n{}", "(dry run - nothing installed)".yellow());
+    } else {
+        save_lock(paths, &mut lock)?;
+    }
+    if failed > 0 {
+        anyhow::bail!("{} package(s) failed to install", failed);
+    }
+    Ok(())
+}
+
+fn cmd_update(paths: &McpPaths, names: &[String], dry_run: bool, profile: Option<&str>) -> Result<()> {
+    let config = load_active_servers(paths, profile)?;
+    let registry = runtime_registry(&config);
+    let packages = select_packages(&config, &registry, names)?;
+    let mut lock = load_lock(paths)?;
+
+    let mut updated = 0;
+    let mut failed = 0;
+    for (package, users) in &packages {
+        let Some(before) = package.installed_version(paths) else {
+            println!(
+                "{} {} is not installed - run {}",
+                "-".dimmed(),
+                package.package,
+                "mcp install".cyan()
+            );
+            continue;
+        };
+        // Newest version the server's `version` allows; the lock follows
+        let spec = format!("{}@{}", package.package, package.requested.unwrap_or("latest"));
+        let result = install_package(paths, package, &spec, users, None, dry_run).map(|dist| {
+            let dist = dist?;
+            lock_package(&mut lock, package, users, &dist.version, &dist.integrity);
+            Some(dist.version)
+        });
+        match result {
+            Ok(Some(after)) if after == before => {
+                println!("{} {} {} (up to date)", "✓".green(), package.package, after)
+            }
+            Ok(Some(after)) => {
+                println!("{} {} {} -> {}", "✓".green(), package.package, before, after.green());
+                updated += 1;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                println!("{} {:#}", "✗".red(), e);
+                failed += 1;
+            }
+        }
+    }
+
+    if dry_run {
+        println!("\n{}", "(dry run - nothing installed)".yellow());
+    } else {
+        save_lock(paths, &mut lock)?;
+        if updated > 0 {
+            println!("\n{} package(s) updated", updated.to_string().cyan());
+        }
+    }
+    if failed > 0 {
+        anyhow::bail!("{} package(s) failed to update", failed);
+    }
+    Ok(())
+}
+
+fn cmd_remove(paths: &McpPaths, selection: &ServerSelection, force: bool) -> Result<()> {
+    let mut config = load_servers(paths)?;
+    let names = selection.select(&config)?;
+
+    let prompt = match names.as_slice() {
+        [name] => format!("Remove server '{}'?", name.cyan()),
+        _ => {
+            println!("{}", "Servers to remove:".cyan().bold());
+            for name in &names {
+                println!("  {} {}", "-".red(), name);
+            }
+            format!("Remove {} servers?", names.len())
+        }
+    };
+    let profiles: Vec<&str> = config
+        .profiles
+        .iter()
+        .filter(|(_, entries)| entries.iter().any(|entry| names.contains(entry)))
+        .map(|(profile, _)| profile.as_str())
+        .collect();
+    if !profiles.is_empty() {
+        println!("{} Also dropped from profiles: {}", "!".yellow(), profiles.join(", "));
+    }
+    if !force && !confirm(&prompt)? {
+        println!("Cancelled.");
+        return Ok(());
+    }
+
+    for name in &names {
+        config.servers.remove(name);
+    }
+    for entries in config.profiles.values_mut() {
+        entries.retain(|entry| !names.contains(entry));
+    }
+    let config = config
+        .validated()
+        .map_err(|errors| invalid_config(&paths.servers_json, &errors))?;
+    save_servers(paths, &config)?;
+
+    let mut lock = load_lock(paths)?;
+    let before = lock.servers.len();
+    lock.servers.retain(|name, _| !names.contains(name));
+    if lock.servers.len() != before {
+        save_lock(paths, &mut lock)?;
+    }
+
+    for name in &names {
+        println!("{} Removed server '{}'", "✓".green(), name);
+    }
+    Ok(())
+}
+
+fn cmd_enable(paths: &McpPaths, selection: &ServerSelection, force: bool) -> Result<()> {
+    set_disabled(paths, selection, false, force)
+}
+
+fn cmd_disable(paths: &McpPaths, selection: &ServerSelection, force: bool) -> Result<()> {
+    set_disabled(paths, selection, true, force)
+}
+
+fn set_disabled(paths: &McpPaths, selection: &ServerSelection, disabled: bool, force: bool) -> Result<()> {
+    let mut config = load_servers(paths)?;
+    let names = selection.select(&config)?;
+
+    let verb = if disabled { "Disabled" } else { "Enabled" };
+    let (unchanged, changing): (Vec<_>, Vec<_>) = names
+        .into_iter()
+        .partition(|name| config.servers[name].disabled == disabled);
+    for name in &unchanged {
+        println!("{} '{}' already {}", "·".white(), name, verb.to_lowercase());
+    }
+    if changing.is_empty() {
+        return Ok(());
+    }
+
+    // Several servers or --all: show what changes and ask first
+    if changing.len() > 1 || selection.all {
+        let action = if disabled { "Disable" } else { "Enable" };
+        println!("{}", format!("Servers to {}:", action.to_lowercase()).cyan().bold());
+        for name in &changing {
+            println!("  {} {}", if disabled { "-".red() } else { "+".green() }, name);
+        }
+        let prompt = match changing.as_slice() {
+            [name] => format!("{} server '{}'?", action, name.cyan()),
+            _ => format!("{} {} servers?", action, changing.len()),
+        };
+        if !force && !confirm(&prompt)? {
+            println!("Cancelled.");
+            return Ok(());
+        }
+    }
+
+    for name in &changing {
+        if let Some(server) = config.servers.get_mut(name) {
+            server.disabled = disabled;
+        }
+    }
+    save_servers(paths, &config)?;
+    for name in &changing {
+        println!("{} {} server '{}'", "✓".green(), verb, name.cyan());
+    }
+
+    // An active profile decides which servers run, so the flags only take
+    // effect once it is cleared
+    if let Some(profile) = &config.profile {
+        let selected = config.profile_servers(profile)?;
+        let overridden: Vec<&str> = changing
+            .iter()
+            .map(String::as_str)
+            .filter(|name| selected.contains(name) == disabled)
+            .collect();
+        if !overridden.is_empty() {
+            eprintln!(
+                "{} Profile '{}' is active, so {} stay {} until `mcp profile clear` (or edit \"profiles\".\"{}\" in servers.json)",
+                "!".yellow(),
+                profile,
+                overridden.join(", "),
+                if disabled { "enabled" } else { "disabled" },
+                profile
+            );
+        }
+    }
+    Ok(())
+}
+
+fn cmd_validate(paths: &McpPaths, file: Option<&Path>) -> Result<()> {
+    let read = |path: &Path| {
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))
+    };
+    let report = |path: &Path, result: &Result<ServersConfig, Vec<ConfigError>>| match result {
+        Ok(config) => {
+            println!("{} {} ({} servers)", "✓".green(), path.display(), config.servers.len());
+            true
+        }
+        Err(errors) => {
+            println!("{} {}", "✗".red(), path.display());
+            for error in errors {
+                println!("    {}", error);
+            }
+            false
+        }
+    };
+
+    let valid = match file {
+        Some(file) => report(file, &parse_servers(&read(file)?, ServersConfig::validated)),
+        None => {
+            let global = parse_servers(&read(&paths.servers_json)?, ServersConfig::validated);
+            let mut valid = report(&paths.servers_json, &global);
+            // The overlay is checked merged over servers.json, which it may
+            // reference (runtimes, profile servers)
+            if let (Some(project_json), Ok(mut config)) = (&paths.project_json, global) {
+                let merged = parse_servers(&read(project_json)?, |overlay| {
+                    config.merge_overlay(overlay);
+                    let mut merged = config.validated()?;
+                    let project = std::mem::take(&mut merged.project);
+                    merged.servers.retain(|name, _| project.contains(name));
+                    Ok(merged)
+                });
+                valid &= report(project_json, &merged);
+            }
+            valid
+        }
+    };
+
+    if !valid {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn cmd_schema() -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(&servers_schema())?);
+    Ok(())
+}
+
+/// Give every server an explicit `type`, and turn runtime servers launched
+/// through `docker run` into docker servers.
+fn cmd_migrate(paths: &McpPaths, dry_run: bool, yes: bool) -> Result<()> {
+    let global = load_servers(paths)?;
+    let registry = runtime_registry(&global);
+
+    let files = std::iter::once(&paths.servers_json).chain(paths.project_json.as_ref());
+    for file in files {
+        let content =
+            fs::read_to_string(file).with_context(|| format!("Failed to read {}", file.display()))?;
+        let (config, typed, containers) =
+            migrate_servers(&content, &registry).map_err(|errors| invalid_config(file, &errors))?;
+
+        if typed == 0 && containers == 0 {
+            println!("{} {} (up to date)", "·".white(), file.display());
+            continue;
+        }
+        println!(
+            "{} {}: {} server(s) given a type, {} moved to docker",
+            "→".cyan(),
+            file.display(),
+            typed,
+            containers
+        );
+        let migrated = serde_json::to_string_pretty(&config)?;
+        if dry_run {
+            print_diff(&content, &migrated);
+        } else {
+            write_client_config(file, &migrated, yes)?;
+        }
+    }
+    Ok(())
+}
+
+/// servers.json `content` in the current format, with how many servers were
+/// given a type and how many moved from a `docker run` runtime to docker.
+fn migrate_servers(
+    content: &str,
+    registry: &BTreeMap<String, RuntimeDef>,
+) -> Result<(ServersConfig, usize, usize), Vec<ConfigError>> {
+    let mut value: serde_json::Value =
+        serde_json::from_str(content).map_err(|e| vec![ConfigError::new("", e.to_string())])?;
+    let typed = upgrade_legacy_servers(&mut value);
+    let mut config = parse_servers(content, Ok)?;
+
+    let mut containers = 0;
+    for server in config.servers.values_mut() {
+        if let Some(docker) = docker_server(registry, &server.kind) {
+            server.kind = ServerKind::Docker(docker);
+            containers += 1;
+        }
+    }
+    Ok((config, typed, containers))
+}
+
+/// The docker server a runtime server amounts to, when its runtime is a
+/// `docker run` wrapper.
+fn docker_server(registry: &BTreeMap<String, RuntimeDef>, kind: &ServerKind) -> Option<DockerServer> {
+    let ServerKind::Runtime(runtime) = kind else {
+        return None;
+    };
+    let def = registry.get(&runtime.runtime).filter(|def| def.program == "docker")?;
+    let mut args = Vec::new();
+    for arg in &def.args {
+        if arg == "{args}" {
+            args.extend(runtime.args.iter().cloned());
+        } else {
+            args.push(arg.replace("{path}", &runtime.path));
+        }
+    }
+    if !def.args.iter().any(|arg| arg == "{args}") {
+        args.extend(runtime.args.iter().cloned());
+    }
+    parse_docker_run(&args)
+}
+
+fn cmd_show(paths: &McpPaths, name: &str, json: bool) -> Result<()> {
+    let config = load_merged_servers(paths)?;
+
+    let server = config
+        .servers
+        .get(name)
+        .with_context(|| format!("Server '{}' not found", name))?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(server)?);
+    } else {
+        println!("{}: {}", "Name".cyan(), name);
+        if let Some(desc) = &server.description {
+            println!("{}: {}", "Description".cyan(), desc);
+        }
+
+        let print_args = |label: &str, args: &[String]| {
+            if !args.is_empty() {
+                println!("{}: {:?}", label.cyan(), args);
+            }
+        };
+        match &server.kind {
+            ServerKind::Runtime(runtime) => {
+                println!("{}: {}", "Runtime".cyan(), runtime.runtime);
+                println!("{}: {}", "Path".cyan(), runtime.path);
+                if let Some(version) = &runtime.version {
+                    println!("{}: {}", "Version".cyan(), version);
+                }
+                if let Some(locked) = load_lock(paths)?.servers.get(name) {
+                    println!("{}: {}@{}", "Locked".cyan(), locked.package, locked.version);
+                }
+                print_args("Args", &runtime.args);
+            }
+            ServerKind::Native(native) => {
+                println!("{}: native", "Type".cyan());
+                println!("{}: {}", "Command".cyan(), native.command);
+                print_args("Args", &native.args);
+            }
+            ServerKind::Remote(remote) => {
+                println!("{}: remote", "Type".cyan());
+                println!("{}: {}", "URL".cyan(), remote.url);
+                println!("{}: {}", "Transport".cyan(), remote.transport().as_str());
+                if !remote.headers.is_empty() {
+                    let names: Vec<_> = remote.headers.keys().map(String::as_str).collect();
+                    println!("{}: {}", "Headers".cyan(), names.join(", "));
+                }
+                if let Some(oauth) = &remote.oauth {
+                    let client = oauth.client_id.as_deref().unwrap_or("(dynamic registration)");
+                    println!("{}: {}", "OAuth Client".cyan(), client);
+                }
+            }
+            ServerKind::Docker(docker) => {
+                println!("{}: docker", "Type".cyan());
+                println!("{}: {}", "Image".cyan(), docker.image);
+                print_args("Docker Args", &docker.docker_args);
+                print_args("Args", &docker.args);
+            }
+        }
+
+        println!(
+            "{}: {}",
+            "Status".cyan(),
+            if server.disabled {
+                "disabled".red()
+            } else {
+                "enabled".green()
+            }
+        );
+
+        if server.opencode_only {
+            println!("{}: yes", "OpenCode Only".cyan());
+        }
+        if !server.tags.is_empty() {
+            println!("{}: {}", "Tags".cyan(), server.tags.join(", "));
+        }
+
+        if let Some(tools) = &server.tools {
+            println!("{}: {}", "Tools".cyan(), tools.join(", "));
+        }
+        if let Some(env) = &server.env {
+            println!("{}: {}", "Env Vars".cyan(), env);
+        }
+    }
+
+    Ok(())
+}
+
+fn cmd_profile(paths: &McpPaths, profile: Option<&str>, action: ProfileCommands) -> Result<()> {
+    let mut config = load_servers(paths)?;
+
+    match action {
+        ProfileCommands::List => {
+            if config.profiles.is_empty() {
+                println!("{}", "No profiles defined.".yellow());
+                println!("Add a \"profiles\" map of name -> server names or tag:<tag> to servers.json");
+                return Ok(());
+            }
+            for (name, entries) in &config.profiles {
+                let marker = if config.profile.as_deref() == Some(name.as_str()) {
+                    "*".green()
+                } else {
+                    " ".normal()
+                };
+                let count = match config.profile_servers(name) {
+                    Ok(servers) => format!("{} servers", servers.len()),
+                    Err(e) => e.to_string().red().to_string(),
+                };
+                println!("{} {} {} ({})", marker, name.cyan(), entries.join(", ").dimmed(), count);
+            }
+        }
+        ProfileCommands::Use { name } => {
+            let count = config.profile_servers(&name)?.len();
+            config.profile = Some(name.clone());
+            save_servers(paths, &config)?;
+            println!("{} Using profile '{}' ({} servers)", "✓".green(), name, count);
+            println!("Run {} to update client configs", "mcp gen".cyan());
+        }
+        ProfileCommands::Clear => {
+            if config.profile.take().is_none() {
+                println!("{}", "No profile is active.".yellow());
+                return Ok(());
+            }
+            save_servers(paths, &config)?;
+            println!("{} Cleared the active profile", "✓".green());
+            println!("Run {} to update client configs", "mcp gen".cyan());
+        }
+        ProfileCommands::Show { name } => {
+            let Some(name) = name.as_deref().or(profile).or(config.profile.as_deref()) else {
+                println!("{}", "No profile is active.".yellow());
+                return Ok(());
+            };
+            for server in config.profile_servers(name)? {
+                println!("{}", server);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn cmd_daemon(paths: &McpPaths, profile: Option<&str>, action: DaemonCommands) -> Result<()> {
+    match action {
+        DaemonCommands::Start { foreground: true } => run_daemon(paths, profile),
+        DaemonCommands::Start { foreground: false } => {
+            if daemon_connect(paths, None, Some("status")).is_some() {
+                println!("{}", "Daemon is already running.".yellow());
+                return Ok(());
+            }
+
+            paths.ensure_mcp_dir()?;
+            let log = fs::OpenOptions::new().create(true).append(true).open(&paths.daemon_log)?;
+
+            let mut command = Command::new(std::env::current_exe()?);
+            if paths.custom_mcp_dir {
+                command.arg("--mcp-dir").arg(&paths.mcp_dir);
+            }
+            if let Some(profile) = profile {
+                command.args(["--profile", profile]);
+            }
+            command
+                .args(["daemon", "start", "--foreground"])
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(log);
+            // Detach from the terminal so closing it leaves the daemon running
+            #[cfg(unix)]
+            {
+                use std::os::unix::process::CommandExt;
+                command.process_group(0);
+            }
+            #[cfg(windows)]
+            {
+                use std::os::windows::process::CommandExt;
+                const DETACHED_PROCESS: u32 = 0x0000_0008;
+                const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+                command.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
+            }
+            command.spawn().context("Failed to start daemon")?;
+
+            for _ in 0..50 {
+                thread::sleep(std::time::Duration::from_millis(100));
+                if daemon_connect(paths, None, Some("status")).is_some() {
+                    println!("{} Daemon started", "✓".green());
+                    return cmd_daemon(paths, profile, DaemonCommands::Status);
+                }
+            }
+            anyhow::bail!("Daemon did not start; see {}", paths.daemon_log.display())
+        }
+        DaemonCommands::Stop => {
+            if daemon_connect(paths, None, Some("stop")).is_none() {
+                println!("{}", "Daemon is not running.".yellow());
+                return Ok(());
+            }
+            println!("{} Daemon stopped", "✓".green());
+            Ok(())
+        }
+        DaemonCommands::Status => {
+            let Some((_, reply)) = daemon_connect(paths, None, Some("status")) else {
+                println!("{}", "Daemon is not running.".yellow());
+                println!("Start it with {}", "mcp daemon start".cyan());
+                return Ok(());
+            };
+            let servers: Vec<DaemonServerStatus> =
+                serde_json::from_value(reply["servers"].clone()).unwrap_or_default();
+
+            let now = chrono::Utc::now();
+            let rows: Vec<DaemonRow> = servers
+                .into_iter()
+                .map(|s| {
+                    let uptime = s
+                        .since
+                        .as_deref()
+                        .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+                        .filter(|_| s.pid.is_some())
+                        .map(|t| format_duration((now - t.with_timezone(&chrono::Utc)).num_seconds()))
+                        .unwrap_or_else(|| "-".to_string());
+                    let status = if s.state == "running" {
+                        s.state.green().to_string()
+                    } else {
+                        s.state.yellow().to_string()
+                    };
+                    DaemonRow {
+                        name: s.name,
+                        status,
+                        pid: s.pid.map_or_else(|| "-".to_string(), |p| p.to_string()),
+                        uptime,
+                        restarts: s.restarts,
+                        clients: s.clients,
+                    }
+                })
+                .collect();
+
+            if rows.is_empty() {
+                println!("{}", "The daemon runs no servers.".yellow());
+            } else {
+                println!("{}", Table::new(rows));
+            }
+            println!("Logs: {}", paths.logs_dir.display());
+            Ok(())
+        }
+    }
+}
+
+/// Print a server's log, or the daemon's without a `name`.
+fn cmd_logs(paths: &McpPaths, name: Option<&str>, follow: bool, since: Option<&str>, lines: Option<usize>) -> Result<()> {
+    let path = name.map_or_else(|| paths.daemon_log.clone(), |name| paths.server_log(name));
+    if !path.exists() && !follow {
+        match name {
+            Some(name) => anyhow::bail!("No log for '{}' yet ({})", name, path.display()),
+            None => anyhow::bail!("No daemon log yet ({})", path.display()),
+        }
+    }
+    let since = since.map(parse_since).transpose()?;
+
+    // Oldest rotated log first
+    let mut files: Vec<PathBuf> = (1..=LOG_KEEP).rev().map(|n| rotated_log(&path, n)).collect();
+    files.push(path.clone());
+
+    let mut selected = Vec::new();
+    for file in files.iter().filter(|f| f.exists()) {
+        let content = fs::read_to_string(file)
+            .with_context(|| format!("Failed to read {}", file.display()))?;
+        selected.extend(
+            content
+                .lines()
+                .filter(|line| since.is_none_or(|since| log_line_time(line).is_none_or(|t| t >= since)))
+                .map(String::from),
+        );
+    }
+    let skip = lines.map_or(0, |n| selected.len().saturating_sub(n));
+    for line in &selected[skip..] {
+        print_log_line(line);
+    }
+
+    if !follow {
+        return Ok(());
+    }
+
+    // Poll for growth; a shorter file means it was rotated
+    let mut position = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    loop {
+        thread::sleep(std::time::Duration::from_millis(500));
+        let Ok(len) = fs::metadata(&path).map(|m| m.len()) else {
+            continue;
+        };
+        if len < position {
+            position = 0;
+        }
+        if len == position {
+            continue;
+        }
+
+        let mut file = fs::File::open(&path)?;
+        io::Seek::seek(&mut file, io::SeekFrom::Start(position))?;
+        let mut chunk = Vec::new();
+        io::Read::read_to_end(&mut file, &mut chunk)?;
+        // Leave a partly written line for the next poll
+        let Some(end) = chunk.iter().rposition(|&b| b == b'\n') else {
+            continue;
+        };
+        for line in String::from_utf8_lossy(&chunk[..end]).lines() {
+            print_log_line(line);
+        }
+        position += end as u64 + 1;
+    }
+}
+
+/// `90` -> `1m 30s`, `7200` -> `2h 0m`
+fn format_duration(secs: i64) -> String {
+    let secs = secs.max(0);
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m {}s", secs / 60, secs % 60),
+        3600..=86399 => format!("{}h {}m", secs / 3600, secs % 3600 / 60),
+        _ => format!("{}d {}h", secs / 86400, secs % 86400 / 3600),
+    }
+}
+
+fn cmd_env(paths: &McpPaths, profile: Option<&str>, action: EnvCommands) -> Result<()> {
+    // Without a servers.json there is no store configured, so plain .env
+    let config = if paths.servers_json.exists() {
+        load_servers(paths)?
+    } else {
+        ServersConfig::default()
+    };
+    let store = secret_store(paths, &config);
+    // With another store, anything still in .env is a plaintext leftover
+    let separate = !matches!(config.secrets(), SecretsConfig::Env);
+
+    match action {
+        EnvCommands::List => {
+            let file = EnvFile::load(&paths.env_file)?;
+            for (number, line) in file.invalid_lines() {
+                println!("{} {}:{}: not KEY=value: {}", "!".yellow(), paths.env_file.display(), number, line);
+            }
+            let plaintext = if separate { file.vars() } else { BTreeMap::new() };
+            let env = load_env(paths, &config)?;
+            if env.is_empty() {
+                println!("{}", "No environment variables set.".yellow());
+            } else {
+                for (key, value) in &env {
+                    // Mask sensitive values
+                    let display_value = if is_secret_name(key) {
+                        format!("{}...", &value.chars().take(8).collect::<String>())
+                    } else {
+                        value.clone()
+                    };
+                    if plaintext.get(key) == Some(value) {
+                        println!("{}={} {}", key.cyan(), display_value, "(plaintext .env)".yellow());
+                    } else {
+                        println!("{}={}", key.cyan(), display_value);
+                    }
+                }
+                if !plaintext.is_empty() {
+                    println!(
+                        "\n{} variable(s) still in .env. Run {} to move them to {}.",
+                        plaintext.len(),
+                        "mcp env migrate".cyan(),
+                        store.describe()
+                    );
+                }
+            }
+        }
+        EnvCommands::Set { key, value } => {
+            store.set(&key, &value)?;
+            if separate {
+                DotEnvStore {
+                    path: paths.env_file.clone(),
+                }
+                .remove(&key)?;
+            }
+            println!("{} Set {} in {}", "✓".green(), key.cyan(), store.describe());
+        }
+        EnvCommands::Remove { key } => {
+            let mut removed = store.remove(&key)?;
+            if separate {
+                removed |= DotEnvStore {
+                    path: paths.env_file.clone(),
+                }
+                .remove(&key)?;
+            }
+            if removed {
+                println!("{} Removed {}", "✓".green(), key);
+            } else {
+                println!("{} '{}' not found", "!".yellow(), key);
+            }
+        }
+        EnvCommands::Check => {
+            return cmd_env_check(paths, profile, store.as_ref());
+        }
+        EnvCommands::Migrate { keys, dry_run } => {
+            if !separate {
+                anyhow::bail!(
+                    "No secret store configured - set \"secrets\": {{\"backend\": \"age\"}} (or \"keyring\") in {}",
+                    paths.servers_json.display()
+                );
+            }
+            let mut file = EnvFile::load(&paths.env_file)?;
+            let vars = file.vars();
+            for key in &keys {
+                if !vars.contains_key(key) {
+                    anyhow::bail!("'{}' is not in {}", key, paths.env_file.display());
+                }
+            }
+            let moving: Vec<_> = vars
+                .iter()
+                .filter(|(key, _)| keys.is_empty() || keys.contains(key))
+                .collect();
+            if moving.is_empty() {
+                println!("{}", "Nothing to migrate.".yellow());
+                return Ok(());
+            }
+
+            for (key, value) in &moving {
+                println!("{} {}", "→".cyan(), key);
+                if !dry_run {
+                    store.set(key, value)?;
+                }
+            }
+            if dry_run {
+                println!("\n{}", "(dry run - no files changed)".yellow());
+                return Ok(());
+            }
+            // Only once everything is stored
+            for (key, _) in &moving {
+                file.remove(key);
+            }
+            file.save(&paths.env_file)?;
+            println!(
+                "{} Moved {} variable(s) from .env to {}",
+                "✓".green(),
+                moving.len(),
+                store.describe()
+            );
+        }
+        EnvCommands::Edit => {
+            paths.ensure_mcp_dir()?;
+            if !paths.env_file.exists() {
+                fs::write(&paths.env_file, ENV_FILE_HEADER)?;
+            }
+            if separate {
+                println!("Values in {} are changed with mcp env set.", store.describe());
+            }
+
+            let editor = std::env::var("EDITOR").unwrap_or_else(|_| "notepad".to_string());
+            Command::new(&editor)
+                .arg(&paths.env_file)
+                .status()?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Variables a server reads: its `env` sources and `${VAR}` references in
+/// remote headers.
+fn required_vars(server: &Server) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for (_, source) in server.env.iter().flat_map(EnvSpec::bindings) {
+        if !names.iter().any(|name| name == source) {
+            names.push(source.to_string());
+        }
+    }
+    for value in server.remote().iter().flat_map(|remote| remote.headers.values()) {
+        expand_env_refs(value, |_| None, &mut names);
+    }
+    names
+}
+
+fn is_secret_name(name: &str) -> bool {
+    let name = name.to_lowercase();
+    ["key", "secret", "token", "password"].iter().any(|s| name.contains(s))
+}
+
+/// Whether a value is an unfilled sample: a secret still at its .env.example
+/// value, an API key shaped like `sk-...`, or anything shaped like
+/// `your-api-key-here`, `<token>` or `changeme`.
+fn is_placeholder(name: &str, value: &str, example: Option<&String>) -> bool {
+    let lower = value.to_lowercase();
+    (is_secret_name(name)
+        && (example.is_some_and(|example| example == value) || lower.ends_with("...")))
+        || lower.starts_with("your-")
+        || lower.starts_with("your_")
+        || lower.ends_with("-here")
+        || lower.ends_with("_here")
+        || (lower.starts_with('<') && lower.ends_with('>'))
+        || matches!(lower.as_str(), "changeme" | "change-me" | "todo" | "xxx" | "placeholder")
+}
+
+fn cmd_env_check(paths: &McpPaths, profile: Option<&str>, store: &dyn SecretStore) -> Result<()> {
+    let config = load_active_servers(paths, profile)?;
+    let dotenv = EnvFile::load(&paths.env_file)?.vars();
+    let stored = store.load()?;
+    let env_vars = merge_env(dotenv.clone(), stored.clone());
+    let example = EnvFile::load(&paths.mcp_dir.join(".env.example"))?.vars();
+    let store_name = match config.secrets() {
+        SecretsConfig::Env => ".env",
+        SecretsConfig::Age { .. } => "age",
+        SecretsConfig::Keyring { .. } => "keyring",
+    };
+
+    let mut problems = 0;
+    let mut without = 0;
+    for (name, server) in config.servers.iter().filter(|(_, s)| !s.disabled) {
+        let vars = required_vars(server);
+        if vars.is_empty() {
+            without += 1;
+            continue;
+        }
+        println!("{}", name.cyan().bold());
+        for var in vars {
+            // Resolved as `mcp run` does; the sources only label the result
+            let values = [
+                (store_name, stored.get(&var).cloned()),
+                (".env", dotenv.get(&var).cloned()),
+                ("environment", std::env::var(&var).ok()),
+            ];
+            let found = lookup_env_var(&env_vars, &var).map(|value| {
+                let source = values
+                    .iter()
+                    .find(|(_, v)| v.as_ref() == Some(&value))
+                    .map_or("environment", |(source, _)| *source);
+                (source, value)
+            });
+            let empty = values.iter().find(|(_, value)| value.as_deref() == Some(""));
+            match (found, empty) {
+                (None, None) => {
+                    println!("  {} {} {}", "✗".red(), var, "missing".red());
+                    problems += 1;
+                }
+                (None, Some((source, _))) => {
+                    println!("  {} {} {} ({})", "✗".red(), var, "empty".red(), source);
+                    problems += 1;
+                }
+                (Some((source, value)), _) if is_placeholder(&var, &value, example.get(&var)) => {
+                    println!("  {} {} {} {:?} ({})", "✗".red(), var, "placeholder".red(), value, source);
+                    problems += 1;
+                }
+                (Some((source, _)), _) => println!("  {} {} ({})", "✓".green(), var, source),
+            }
+        }
+    }
+    if without > 0 {
+        println!("{}", format!("{} enabled server(s) need no variables", without).dimmed());
+    }
+
+    // Unused against every server, so disabled ones keep their variables
+    let all = load_merged_servers(paths)?;
+    let used: BTreeSet<String> = all.servers.values().flat_map(required_vars).collect();
+    let unused: Vec<_> = dotenv
+        .keys()
+        .chain(stored.keys())
+        .filter(|var| !used.contains(*var))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(String::as_str)
+        .collect();
+    if !unused.is_empty() {
+        println!();
+        println!("{} {}", "Unused:".yellow(), unused.join(", "));
+    }
+
+    if problems > 0 {
+        println!();
+        println!("{} {} variable(s) not set", "✗".red(), problems);
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn cmd_info(paths: &McpPaths, profile: Option<&str>) -> Result<()> {
+    println!("{}", "MCP Configuration".cyan().bold());
+    println!();
+    println!("{}: {}", "MCP Directory".cyan(), paths.mcp_dir.display());
+    println!("{}: {}", "Servers Config".cyan(), paths.servers_json.display());
+    println!("{}: {}", "Environment".cyan(), paths.env_file.display());
+    if let Some(project_json) = &paths.project_json {
+        println!("{}: {}", "Project Config".cyan(), project_json.display());
+    }
+    if let Ok(config) = load_servers(paths) {
+        if !matches!(config.secrets(), SecretsConfig::Env) {
+            println!("{}: {}", "Secrets".cyan(), secret_store(paths, &config).describe());
+        }
+    }
+    if let Some(profile) = profile.map(String::from).or_else(|| load_servers(paths).ok()?.profile) {
+        println!("{}: {}", "Profile".cyan(), profile);
+    }
+    println!();
+    println!("{}", "Output Paths".cyan().bold());
+    let enabled_targets = load_servers(paths)
+        .map(|config| config.enabled_targets())
+        .unwrap_or_else(|_| DEFAULT_TARGETS.iter().map(|t| t.to_string()).collect());
+    for target in TARGETS {
+        let marker = if enabled_targets.iter().any(|t| t == target.name) {
+            ""
+        } else {
+            " (not enabled)"
+        };
+        println!(
+            "{}: {}{}",
+            format!("{} Config", target.label).cyan(),
+            (target.default_path)(paths).display(),
+            marker.dimmed()
+        );
+    }
+    println!();
+    println!("{}", "Runtime Paths".cyan().bold());
+    for root in paths.node_roots.iter().chain([&paths.bun_root]) {
+        println!(
+            "{}: {}",
+            format!("{} node_modules", root.manager).cyan(),
+            root.node_modules.display()
+        );
+    }
+
+    // Check if paths exist
+    println!();
+    println!("{}", "Status".cyan().bold());
+
+    let check = |path: &PathBuf, name: &str| {
+        if path.exists() {
+            println!("  {} {}", "✓".green(), name);
+        } else {
+            println!("  {} {} (missing)", "✗".red(), name);
+        }
+    };
+
+    check(&paths.servers_json, "servers.json");
+    check(&paths.env_file, ".env");
+    for target in TARGETS.iter().filter(|t| enabled_targets.iter().any(|n| n == t.name)) {
+        check(&(target.default_path)(paths), &format!("{} config", target.label));
+    }
+    for root in paths.node_roots.iter().chain([&paths.bun_root]) {
+        check(&root.node_modules, &format!("{} node_modules", root.manager));
+    }
+
+    if let Ok(config) = load_active_servers(paths, profile) {
+        let registry = runtime_registry(&config);
+        let packages: Vec<_> = config
+            .servers
+            .iter()
+            .filter(|(_, s)| !s.disabled)
+            .filter_map(|(name, s)| server_package(s, &registry).map(|package| (name, package)))
+            .collect();
+        let missing: Vec<_> = packages
+            .iter()
+            .filter(|(_, package)| package.entry_point(paths).is_none())
+            .collect();
+        for (name, package) in &missing {
+            println!("  {} {} ({} not installed)", "✗".red(), name, package.package);
+        }
+        if missing.is_empty() {
+            println!("  {} {} server package(s) installed", "✓".green(), packages.len());
+        } else {
+            println!("  Run {} to install missing packages", "mcp install".cyan());
+        }
+    }
+
+    Ok(())
+}
+
+fn cmd_completions(shell: Shell) -> Result<()> {
+    let mut cmd = Cli::command();
+    generate(shell, &mut cmd, "mcp", &mut io::stdout());
+    Ok(())
+}
+
+// ============================================================================
+// Main
+// ============================================================================
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let paths = McpPaths::new(cli.mcp_dir, cli.project_dir)?;
+    let profile = cli.profile.as_deref();
+
+    match cli.command {
+        Commands::List {
+            enabled,
+            disabled,
+            filter,
+            sort,
+            columns,
+            wide,
+            json,
+        } => cmd_list(&paths, enabled, disabled, &filter, sort, &columns, wide, json, profile),
+        Commands::Run {
+            name,
+            record,
+            no_daemon,
+            args,
+        } => cmd_run(&paths, &name, &args, record.as_deref(), no_daemon),
+        Commands::Serve {
+            name,
+            http,
+            token,
+            token_env,
+        } => cmd_serve(&paths, &name, &http, token, token_env.as_deref()),
+        Commands::Replay { file } => {
+            cmd_replay(&file)
+        }
+        Commands::Gen {
+            mut targets,
+            cursor,
+            opencode,
+            dry_run,
+            env,
+            yes,
+            check,
+            project,
+        } => {
+            if cursor {
+                targets.push("cursor".to_string());
+            }
+            if opencode {
+                targets.push("opencode".to_string());
+            }
+            cmd_gen(&paths, targets, dry_run, env, yes, check, project, profile)
+        }
+        Commands::Add {
+            name,
+            fields,
+            disabled,
+            opencode_only,
+        } => cmd_add(&paths, name, fields, disabled, opencode_only),
+        Commands::Edit { name, fields } => {
+            cmd_edit(&paths, &name, fields)
+        }
+        Commands::Import {
+            from,
+            path,
+            overwrite,
+            dry_run,
+        } => cmd_import(&paths, &from, path, overwrite, dry_run),
+        Commands::Remove { selection, force } => {
+            cmd_remove(&paths, &selection, force)
+        }
+        Commands::Enable { selection, force } => {
+            cmd_enable(&paths, &selection, force)
+        }
+        Commands::Disable { selection, force } => {
+            cmd_disable(&paths, &selection, force)
+        }
+        Commands::Show { name, json } => {
+            cmd_show(&paths, &name, json)
+        }
+        Commands::Validate { file } => {
+            cmd_validate(&paths, file.as_deref())
+        }
+        Commands::Schema => {
+            cmd_schema()
+        }
+        Commands::Migrate { dry_run, yes } => {
+            cmd_migrate(&paths, dry_run, yes)
+        }
+        Commands::Env { action } => {
+            cmd_env(&paths, profile, action)
+        }
+        Commands::Install { names, dry_run } => {
+            cmd_install(&paths, &names, dry_run, profile)
+        }
+        Commands::Update { names, dry_run } => {
+            cmd_update(&paths, &names, dry_run, profile)
+        }
+        Commands::Logs {
+            name,
+            daemon: _,
+            follow,
+            since,
+            lines,
+        } => cmd_logs(&paths, name.as_deref(), follow, since.as_deref(), lines),
+        Commands::Profile { action } => {
+            cmd_profile(&paths, profile, action)
+        }
+        Commands::Daemon { action } => {
+            cmd_daemon(&paths, profile, action)
+        }
+        Commands::Info => {
+            cmd_info(&paths, profile)
+        }
+        Commands::Completions { shell } => {
+            cmd_completions(shell)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::time::{Duration, Instant};
+
+    fn json(text: &str) -> serde_json::Value {
+        serde_json::from_str(text).unwrap()
+    }
+
+    fn temp_dir(label: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mcp-test-{}-{}", label, uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // ------------------------------------------------------------------------
+    // Recording and replay
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn recording_pairs_pipelined_responses() {
+        let dir = temp_dir("recording");
+        let path = dir.join("session.jsonl");
+        let recorder = Recorder::create(&path).unwrap();
+        for (direction, line) in [
+            (Direction::ServerToClient, "booting"),
+            (Direction::ClientToServer, r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#),
+            (Direction::ServerToClient, r#"{"jsonrpc":"2.0","id":1,"result":{}}"#),
+            (Direction::ClientToServer, r#"{"jsonrpc":"2.0","id":2,"method":"tools/call","params":{"name":"a"}}"#),
+            (Direction::ClientToServer, r#"{"jsonrpc":"2.0","id":3,"method":"tools/call","params":{"name":"b"}}"#),
+            (Direction::ServerToClient, r#"{"jsonrpc":"2.0","id":3,"result":"b"}"#),
+            (Direction::ServerToClient, r#"{"jsonrpc":"2.0","id":2,"result":"a"}"#),
+            (Direction::ServerToClient, r#"{"jsonrpc":"2.0","method":"notifications/tools/list_changed"}"#),
+        ] {
+            recorder.log(direction, line);
+        }
+
+        let (preamble, mut exchanges) = load_recording(&path).unwrap();
+        // Non-JSON output is kept as a string
+        assert_eq!(preamble, [serde_json::json!("booting")]);
+        let replies: Vec<Vec<&serde_json::Value>> = exchanges
+            .iter()
+            .map(|e| e.replies.iter().map(|r| r.get("result").or(r.get("method")).unwrap()).collect())
+            .collect();
+        assert_eq!(
+            replies,
+            [
+                vec![&serde_json::json!({})],
+                vec![&serde_json::json!("a")],
+                vec![&serde_json::json!("b"), &serde_json::json!("notifications/tools/list_changed")],
+            ]
+        );
+
+        // Same method and params first, then the next unused call of the
+        // method, then the latest match again once everything is used
+        let call = |name: &str| json(&format!(r#"{{"jsonrpc":"2.0","id":9,"method":"tools/call","params":{{"name":"{}"}}}}"#, name));
+        let replayed = |exchanges: &mut [Exchange], incoming: &serde_json::Value| {
+            find_exchange(exchanges, incoming).map(|e| e.request["id"].clone())
+        };
+        assert_eq!(replayed(&mut exchanges, &call("b")), Some(serde_json::json!(3)));
+        assert_eq!(replayed(&mut exchanges, &call("b")), Some(serde_json::json!(2)));
+        assert_eq!(replayed(&mut exchanges, &call("b")), Some(serde_json::json!(3)));
+        assert_eq!(replayed(&mut exchanges, &call("c")), Some(serde_json::json!(3)));
+        assert_eq!(replayed(&mut exchanges, &json(r#"{"jsonrpc":"2.0","id":9,"method":"prompts/list"}"#)), None);
+        assert_eq!(replayed(&mut exchanges, &json(r#"{"jsonrpc":"2.0","id":9,"result":{}}"#)), None);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    // ------------------------------------------------------------------------
+    // Remote bridge
+    // ------------------------------------------------------------------------
+
+    /// Bridge output kept in memory.
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Captured {
+        fn messages(&self) -> Vec<serde_json::Value> {
+            String::from_utf8(self.0.lock().unwrap().clone())
+                .unwrap()
+                .lines()
+                .map(json)
+                .collect()
+        }
+
+        /// Wait for `count` messages, which the SSE transport delivers from
+        /// its stream thread.
+        fn wait_for(&self, count: usize) -> Vec<serde_json::Value> {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while self.messages().len() < count && Instant::now() < deadline {
+                thread::sleep(Duration::from_millis(10));
+            }
+            self.messages()
+        }
+    }
+
+    fn test_bridge(url: &str) -> (Arc<RemoteBridge>, Captured) {
+        let output = Captured::default();
+        let bridge = Arc::new(RemoteBridge {
+            url: url.to_string(),
+            headers: vec![("Authorization".to_string(), "Bearer test".to_string())],
+            session: Mutex::new(None),
+            output: Mutex::new(Box::new(output.clone())),
+            taps: Taps { recorder: None, traffic_log: None },
+        });
+        (bridge, output)
+    }
+
+    /// A request the stand-in server received.
+    #[derive(Clone, Debug)]
+    struct Seen {
+        method: String,
+        url: String,
+        session: Option<String>,
+        authorization: Option<String>,
+        body: String,
+    }
+
+    impl Seen {
+        fn message(&self) -> serde_json::Value {
+            json(&self.body)
+        }
+    }
+
+    /// A local HTTP server standing in for a remote MCP server. Each request
+    /// is recorded, then answered by `handle` on its own thread, so an open
+    /// event stream does not hold up other requests.
+    fn stand_in(
+        handle: impl Fn(&Seen, tiny_http::Request) + Send + Sync + 'static,
+    ) -> (String, Arc<Mutex<Vec<Seen>>>) {
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", server.server_addr().to_ip().unwrap());
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let handle = Arc::new(handle);
+        let record = Arc::clone(&seen);
+        thread::spawn(move || {
+            for mut request in server.incoming_requests() {
+                let mut body = String::new();
+                request.as_reader().read_to_string(&mut body).unwrap();
+                let entry = Seen {
+                    method: request.method().to_string(),
+                    url: request.url().to_string(),
+                    session: request_header(&request, "Mcp-Session-Id").map(String::from),
+                    authorization: request_header(&request, "Authorization").map(String::from),
+                    body,
+                };
+                record.lock().unwrap().push(entry.clone());
+                let handle = Arc::clone(&handle);
+                thread::spawn(move || handle(&entry, request));
+            }
+        });
+        (url, seen)
+    }
+
+    fn reply(id: &serde_json::Value, result: serde_json::Value) -> String {
+        serde_json::json!({ "jsonrpc": "2.0", "id": id, "result": result }).to_string()
+    }
+
+    fn respond_json(request: tiny_http::Request, body: String) {
+        let response = tiny_http::Response::from_string(body)
+            .with_header(http_header("Content-Type", "application/json"));
+        request.respond(response).unwrap();
+    }
+
+    const HANDSHAKE: &str = concat!(
+        r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#,
+        "\n",
+        r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#,
+        "\n",
+    );
+
+    #[test]
+    fn streamable_json_replies_and_session() {
+        let (url, seen) = stand_in(|seen, request| {
+            if seen.method != "POST" {
+                return respond_status(request, 200, "").unwrap();
+            }
+            let message = seen.message();
+            match message["method"].as_str() {
+                Some("initialize") => {
+                    let response = tiny_http::Response::from_string(reply(&message["id"], serde_json::json!({})))
+                        .with_header(http_header("Content-Type", "application/json"))
+                        .with_header(http_header("Mcp-Session-Id", "session-1"));
+                    request.respond(response).unwrap();
+                }
+                _ if message.get("id").is_none() => respond_status(request, 202, "").unwrap(),
+                _ => respond_json(request, reply(&message["id"], serde_json::json!({ "tools": [] }))),
+            }
+        });
+        let (bridge, output) = test_bridge(&format!("{}/mcp", url));
+
+        let input = format!("{}{}\n", HANDSHAKE, r#"{"jsonrpc":"2.0","id":2,"method":"tools/list"}"#);
+        bridge_streamable(bridge, Cursor::new(input)).unwrap();
+
+        let ids: Vec<_> = output.messages().iter().map(|m| m["id"].clone()).collect();
+        assert_eq!(ids, [serde_json::json!(1), serde_json::json!(2)]);
+
+        let seen = seen.lock().unwrap().clone();
+        assert_eq!(seen.len(), 4);
+        let posts: Vec<_> = seen[..3]
+            .iter()
+            .map(|s| (s.message()["method"].clone(), s.session.as_deref()))
+            .collect();
+        assert_eq!(posts[0], (serde_json::json!("initialize"), None));
+        // The notification is sent in order, before the next request
+        assert_eq!(posts[1], (serde_json::json!("notifications/initialized"), Some("session-1")));
+        assert_eq!(posts[2], (serde_json::json!("tools/list"), Some("session-1")));
+        assert_eq!(seen[3].method, "DELETE");
+        assert_eq!(seen[3].session.as_deref(), Some("session-1"));
+        assert!(seen.iter().all(|s| s.authorization.as_deref() == Some("Bearer test")));
+    }
+
+    #[test]
+    fn streamable_event_stream_replies() {
+        let (url, _) = stand_in(|seen, request| {
+            let message = seen.message();
+            let mut stream = EventStream::start(request, None).unwrap();
+            let progress = serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/progress",
+                "params": { "progress": 1 },
+            });
+            stream.event("message", &progress.to_string()).unwrap();
+            stream.chunk(": keep-alive\n\n").unwrap();
+            stream.event("message", &reply(&message["id"], serde_json::json!({ "content": [] }))).unwrap();
+            stream.finish().unwrap();
+        });
+        let (bridge, output) = test_bridge(&format!("{}/mcp", url));
+
+        let input = r#"{"jsonrpc":"2.0","id":7,"method":"tools/call","params":{"name":"x"}}"#;
+        bridge_streamable(bridge, Cursor::new(input)).unwrap();
+
+        let messages = output.messages();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0]["method"], "notifications/progress");
+        assert_eq!(messages[1]["id"], 7);
+        assert_eq!(messages[1]["result"], serde_json::json!({ "content": [] }));
+    }
+
+    #[test]
+    fn streamable_http_error_answers_request() {
+        let (url, _) = stand_in(|_, request| respond_status(request, 500, "broken").unwrap());
+        let (bridge, output) = test_bridge(&format!("{}/mcp", url));
+
+        let input = r#"{"jsonrpc":"2.0","id":3,"method":"tools/list"}"#;
+        bridge_streamable(bridge, Cursor::new(input)).unwrap();
+
+        let messages = output.messages();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0]["id"], 3);
+        assert!(messages[0]["error"]["message"].as_str().unwrap().contains("HTTP 500"));
+    }
+
+    #[test]
+    fn legacy_sse_discovers_endpoint() {
+        // Replies to POSTs go out on the GET event stream
+        let (replies_tx, replies_rx) = mpsc::channel::<String>();
+        let replies_rx = Mutex::new(Some(replies_rx));
+        let (url, seen) = stand_in(move |seen, request| match seen.method.as_str() {
+            "GET" => {
+                let replies = replies_rx.lock().unwrap().take().unwrap();
+                let mut stream = EventStream::start(request, None).unwrap();
+                stream.event("endpoint", "messages?sessionId=7").unwrap();
+                for message in replies {
+                    stream.event("message", &message).unwrap();
+                }
+            }
+            _ => {
+                let message = seen.message();
+                if message.get("id").is_some() {
+                    replies_tx.send(reply(&message["id"], serde_json::json!({}))).unwrap();
+                }
+                respond_status(request, 202, "").unwrap();
+            }
+        });
+        let (bridge, output) = test_bridge(&format!("{}/sse/stream", url));
+
+        let input = format!("{}{}\n", HANDSHAKE, r#"{"jsonrpc":"2.0","id":2,"method":"tools/list"}"#);
+        bridge_sse(bridge, Cursor::new(input)).unwrap();
+
+        let ids: Vec<_> = output.wait_for(2).iter().map(|m| m["id"].clone()).collect();
+        assert_eq!(ids, [serde_json::json!(1), serde_json::json!(2)]);
+        let posts: Vec<_> = seen
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|s| s.method == "POST")
+            .map(|s| s.url.clone())
+            .collect();
+        assert_eq!(posts, ["/sse/messages?sessionId=7"; 3]);
+    }
+
+    /// Client input that stays open, without sending anything, until the
+    /// sender is dropped.
+    struct QuietInput(mpsc::Receiver<()>);
+
+    impl io::Read for QuietInput {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            let _ = self.0.recv();
+            Ok(0)
+        }
+    }
+
+    #[test]
+    fn legacy_sse_stream_close_ends_bridge() {
+        let (url, _seen) = stand_in(|seen, request| match seen.method.as_str() {
+            "GET" => {
+                let mut stream = EventStream::start(request, None).unwrap();
+                stream.event("endpoint", "/messages").unwrap();
+                stream.event("message", r#"{"jsonrpc":"2.0","method":"notifications/bye"}"#).unwrap();
+                stream.finish().unwrap();
+            }
+            _ => respond_status(request, 202, "").unwrap(),
+        });
+        let (bridge, output) = test_bridge(&format!("{}/sse", url));
+
+        // The client is still connected but quiet; the bridge stops anyway
+        let (_open, quiet) = mpsc::channel();
+        let error = bridge_sse(bridge, BufReader::new(QuietInput(quiet))).unwrap_err();
+        assert_eq!(error.to_string(), "SSE stream closed by server");
+        assert_eq!(output.messages()[0]["method"], "notifications/bye");
+    }
+
+    #[test]
+    fn resolve_endpoint_forms() {
+        let base = "https://example.com/api/sse";
+        assert_eq!(resolve_endpoint(base, "/messages?s=1"), "https://example.com/messages?s=1");
+        assert_eq!(resolve_endpoint(base, "messages?s=1"), "https://example.com/api/messages?s=1");
+        assert_eq!(resolve_endpoint(base, "http://other:8080/m"), "http://other:8080/m");
+        assert_eq!(resolve_endpoint("http://localhost:3000", "messages"), "http://localhost:3000/messages");
+    }
+
+    #[test]
+    fn read_sse_events() {
+        let stream = ": comment\nevent: endpoint\ndata: /m\n\ndata: {\"a\":1}\ndata:{\"b\":2}\n\nevent: done\n\n";
+        let mut events = Vec::new();
+        read_sse(Cursor::new(stream), |event, data| {
+            events.push((event.to_string(), data.to_string()));
+            true
+        })
+        .unwrap();
+        assert_eq!(
+            events,
+            [
+                ("endpoint".to_string(), "/m".to_string()),
+                ("message".to_string(), "{\"a\":1}\n{\"b\":2}".to_string()),
+                ("done".to_string(), String::new()),
+            ]
+        );
+    }
+
+    // ------------------------------------------------------------------------
+    // Shared servers
+    // ------------------------------------------------------------------------
+
+    /// Everything a hub session's outbox has received so far.
+    fn drain(rx: &mpsc::Receiver<String>) -> Vec<serde_json::Value> {
+        rx.try_iter().map(|message| json(&message)).collect()
+    }
+
+    #[test]
+    fn hub_routes_requests_through_fake_child() {
+        let hub = Hub::default();
+        let session = hub.open_session();
+        let (tx, rx) = mpsc::channel();
+        let request = |id: u64| json(&format!(r#"{{"jsonrpc":"2.0","id":{},"method":"tools/list"}}"#, id));
+
+        // Between restarts the request fails at once instead of hanging
+        assert!(hub.client_message(&session, request(7), &tx));
+        let replies = drain(&rx);
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0]["id"], 7);
+        assert_eq!(replies[0]["error"]["message"], "Server is not running");
+        assert!(hub.pending.lock().unwrap().is_empty());
+
+        // The child sees a fresh id, and its response goes back under the
+        // client's
+        let child = Captured::default();
+        hub.attach_process(child.clone());
+        hub.client_message(&session, request(7), &tx);
+        let forwarded = child.messages()[0]["id"].clone();
+        assert_ne!(forwarded, 7);
+        hub.server_message(&format!(r#"{{"jsonrpc":"2.0","id":{},"result":{{"tools":[]}}}}"#, forwarded));
+        let replies = drain(&rx);
+        assert_eq!(replies[0]["id"], 7);
+        assert_eq!(replies[0]["result"], json(r#"{"tools":[]}"#));
+
+        // A crash answers what is in flight
+        hub.client_message(&session, request(8), &tx);
+        hub.fail_pending("Server 'x' exited");
+        assert_eq!(drain(&rx)[0]["error"]["message"], "Server 'x' exited");
+
+        // A client that goes away has its requests cancelled at the child
+        hub.client_message(&session, request(9), &tx);
+        let forwarded = child.messages().last().unwrap()["id"].clone();
+        hub.drop_session(&session);
+        assert!(hub.pending.lock().unwrap().is_empty());
+        let cancelled = child.messages().last().unwrap().clone();
+        assert_eq!(cancelled["method"], "notifications/cancelled");
+        assert_eq!(cancelled["params"]["requestId"], forwarded);
+        assert!(!hub.has_session(&session));
+    }
+
+    #[test]
+    fn hub_initializes_child_once() {
+        let hub = Hub::default();
+        let child = Captured::default();
+        hub.attach_process(child.clone());
+        let initialize = json(r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#);
+
+        // The first session's handshake waits for the child, so answer it
+        // from another thread
+        let first = hub.open_session();
+        let (tx, rx) = mpsc::channel();
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                let deadline = Instant::now() + Duration::from_secs(5);
+                while child.messages().is_empty() && Instant::now() < deadline {
+                    thread::sleep(Duration::from_millis(5));
+                }
+                let id = child.messages()[0]["id"].clone();
+                hub.server_message(&format!(r#"{{"jsonrpc":"2.0","id":{},"result":{{"name":"x"}}}}"#, id));
+            });
+            hub.client_message(&first, initialize.clone(), &tx);
+        });
+        assert_eq!(drain(&rx)[0]["result"]["name"], "x");
+
+        // A second session gets the cached result; the child sees one handshake
+        let second = hub.open_session();
+        hub.client_message(&second, initialize, &tx);
+        assert_eq!(drain(&rx)[0]["result"]["name"], "x");
+        assert_eq!(child.messages().len(), 1);
+    }
+
+    // ------------------------------------------------------------------------
+    // Config validation and migration
+    // ------------------------------------------------------------------------
+
+    fn config_errors(content: &str) -> Vec<String> {
+        checked_config_errors(content, Ok)
+    }
+
+    /// Errors including the cross-references `ServersConfig::validated` checks.
+    fn validation_errors(content: &str) -> Vec<String> {
+        checked_config_errors(content, ServersConfig::validated)
+    }
+
+    fn checked_config_errors(
+        content: &str,
+        then: impl FnOnce(ServersConfig) -> Result<ServersConfig, Vec<ConfigError>>,
+    ) -> Vec<String> {
+        match parse_servers(content, then) {
+            Ok(_) => Vec::new(),
+            Err(errors) => errors.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|item| item.to_string()).collect()
+    }
+
+    #[test]
+    fn unknown_keys_by_path() {
+        let content = r#"{
+            "editor": "vim",
+            "servers": {
+                "docs": { "type": "runtime", "runtime": "node", "path": "docs/index.js", "url": "http://localhost" },
+                "api": {
+                    "type": "remote",
+                    "url": "https://example.com/mcp",
+                    "oauth": { "client_id": "x", "client_idd": "y" },
+                    "headers": { "X-Anything": "goes" }
+                },
+                "tool": { "type": "native", "command": "tool", "descripton": "typo" }
+            }
+        }"#;
+        assert_eq!(
+            config_errors(content),
+            [
+                "editor: unknown field",
+                "servers.docs.url: not used by runtime servers",
+                "servers.api.oauth.client_idd: unknown field",
+                "servers.tool.descripton: unknown field",
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_type_reported_by_serde() {
+        let content = r#"{ "servers": { "x": { "type": "ftp", "host": "example.com" } } }"#;
+        let errors = config_errors(content);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("servers.x"), "{}", errors[0]);
+        assert!(errors[0].contains("unknown variant `ftp`"), "{}", errors[0]);
+    }
+
+    #[test]
+    fn legacy_servers_get_runtime_type() {
+        let mut value = json(r#"{ "servers": {
+            "old": { "runtime": "bun", "path": "pkg/index.js" },
+            "new": { "type": "native", "command": "tool" }
+        } }"#);
+        assert_eq!(upgrade_legacy_servers(&mut value), 1);
+        assert_eq!(value["servers"]["old"]["type"], "runtime");
+        assert_eq!(value["servers"]["new"]["type"], "native");
+        assert_eq!(upgrade_legacy_servers(&mut json(r#"{ "servers": {} }"#)), 0);
+    }
+
+    #[test]
+    fn migrate_legacy_and_docker_runtime_servers() {
+        let content = r#"{
+            "runtimes": {
+                "container": { "program": "docker", "args": ["run", "-i", "--rm", "-e", "GITHUB_TOKEN", "{path}", "{args}"] }
+            },
+            "servers": {
+                "docs": { "runtime": "node", "path": "@acme/docs/dist/index.js", "description": "Docs", "disabled": true },
+                "github": { "runtime": "container", "path": "ghcr.io/github/github-mcp-server", "args": ["stdio"], "env": ["GITHUB_TOKEN"] }
+            }
+        }"#;
+        let global = parse_servers(content, Ok).unwrap_or_else(|_| panic!("legacy file loads"));
+        let (config, typed, containers) = migrate_servers(content, &runtime_registry(&global))
+            .unwrap_or_else(|_| panic!("legacy file migrates"));
+        assert_eq!((typed, containers), (2, 1));
+
+        let migrated = serde_json::to_value(&config).unwrap();
+        assert_eq!(
+            migrated["servers"]["docs"],
+            json(r#"{ "type": "runtime", "runtime": "node", "path": "@acme/docs/dist/index.js", "description": "Docs", "disabled": true }"#)
+        );
+        assert_eq!(
+            migrated["servers"]["github"],
+            json(r#"{
+                "type": "docker",
+                "image": "ghcr.io/github/github-mcp-server",
+                "docker_args": ["-e", "GITHUB_TOKEN"],
+                "args": ["stdio"],
+                "env": ["GITHUB_TOKEN"],
+                "disabled": false
+            }"#)
+        );
+
+        // The result is current: migrating again changes nothing
+        let again = serde_json::to_string_pretty(&config).unwrap();
+        let (_, typed, containers) = migrate_servers(&again, &runtime_registry(&config))
+            .unwrap_or_else(|_| panic!("migrated file loads"));
+        assert_eq!((typed, containers), (0, 0));
+    }
+
+    #[test]
+    fn docker_run_arguments() {
+        let parsed = parse_docker_run(&strings(&[
+            "run", "-i", "--rm", "-e", "TOKEN", "-v", "/data:/data", "--read-only", "-t", "mcp/fetch:1.0", "--verbose", "-x",
+        ]))
+        .unwrap();
+        assert_eq!(
+            parsed,
+            DockerServer {
+                image: "mcp/fetch:1.0".to_string(),
+                docker_args: strings(&["-e", "TOKEN", "-v", "/data:/data", "--read-only"]),
+                args: strings(&["--verbose", "-x"]),
+            }
+        );
+
+        assert_eq!(parse_docker_run(&strings(&["run", "-it", "image"])).unwrap().docker_args, Vec::<String>::new());
+        // No image, or not a `docker run`
+        assert!(parse_docker_run(&strings(&["run", "-i", "--rm"])).is_none());
+        assert!(parse_docker_run(&strings(&["exec", "container", "cmd"])).is_none());
+    }
+
+    // ------------------------------------------------------------------------
+    // Project overlays
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn nearest_project_overlay_below_global() {
+        let dir = temp_dir("overlay-find");
+        let global = dir.join(".mcp").join("servers.json");
+        let project = dir.join("project");
+        let nested = project.join("sub");
+        for config in [&global, &project.join(".mcp/servers.json"), &nested.join(".mcp/servers.json")] {
+            fs::create_dir_all(config.parent().unwrap()).unwrap();
+            fs::write(config, "{}").unwrap();
+        }
+        fs::create_dir_all(nested.join("deep")).unwrap();
+        fs::create_dir_all(dir.join("other")).unwrap();
+
+        assert_eq!(find_project_overlay(&project, &global), Some(project.join(".mcp/servers.json")));
+        assert_eq!(find_project_overlay(&nested.join("deep"), &global), Some(nested.join(".mcp/servers.json")));
+        // The global servers.json is never its own overlay
+        assert_eq!(find_project_overlay(&dir.join("other"), &global), None);
+        assert_eq!(find_project_overlay(&dir, &global), None);
+    }
+
+    #[test]
+    fn project_overlay_replaces_servers_whole() {
+        let dir = temp_dir("overlay-merge");
+        let project = dir.join("project");
+        fs::create_dir_all(project.join(".mcp")).unwrap();
+        fs::write(
+            dir.join("servers.json"),
+            r#"{
+                "targets": ["cursor"],
+                "runtimes": { "tsx": { "program": "tsx", "args": ["{path}"] } },
+                "profiles": { "work": ["docs"] },
+                "servers": {
+                    "docs": { "type": "native", "command": "docs", "env": { "A": "1", "B": "1" } },
+                    "db": { "type": "native", "command": "db", "disabled": true },
+                    "search": { "type": "native", "command": "search" }
+                }
+            }"#,
+        )
+        .unwrap();
+        fs::write(
+            project.join(".mcp/servers.json"),
+            r#"{
+                "profile": "work",
+                "servers": {
+                    "docs": { "type": "native", "command": "docs", "env": { "B": "2" } },
+                    "db": { "type": "native", "command": "db" },
+                    "search": { "type": "native", "command": "search", "disabled": true },
+                    "local": { "type": "runtime", "runtime": "tsx", "path": "server.ts" }
+                }
+            }"#,
+        )
+        .unwrap();
+        let paths = McpPaths::new(Some(dir.clone()), Some(project)).unwrap();
+        let config = load_merged_servers(&paths).unwrap();
+        let env = |name: &str| serde_json::to_value(&config.servers[name].env).unwrap();
+
+        // The project server replaces the global one; env is not merged
+        assert_eq!(env("docs"), json(r#"{ "B": "2" }"#));
+        // Either way, the project's disabled flag wins
+        assert!(!config.servers["db"].disabled);
+        assert!(config.servers["search"].disabled);
+        // Overlay servers may use global runtimes; unset settings stay global
+        assert_eq!(config.servers["local"].kind_label(), "tsx");
+        assert_eq!(config.enabled_targets(), strings(&["cursor"]));
+        assert_eq!(config.profile.as_deref(), Some("work"));
+        assert_eq!(config.project.iter().collect::<Vec<_>>(), ["db", "docs", "local", "search"]);
+
+        // The global file alone is untouched
+        let global = load_servers(&paths).unwrap();
+        assert_eq!(serde_json::to_value(&global.servers["docs"].env).unwrap(), json(r#"{ "A": "1", "B": "1" }"#));
+        assert!(global.profile.is_none() && global.project.is_empty());
+    }
+
+    // ------------------------------------------------------------------------
+    // Server selection
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn glob_patterns() {
+        assert!(glob_match("*-thinking", "sequential-thinking"));
+        assert!(glob_match("*-thinking", "-thinking"));
+        assert!(!glob_match("*-thinking", "clear-thought"));
+        assert!(!glob_match("*-thinking", "thinking"));
+
+        assert!(glob_match("tavily-*", "tavily-search"));
+        assert!(glob_match("*search*", "tavily-search-api"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a*b*c", "aXbYbZc"));
+        assert!(!glob_match("a*b*c", "aXbYbZ"));
+
+        assert!(glob_match("mcp-?", "mcp-1"));
+        assert!(!glob_match("mcp-?", "mcp-"));
+        assert!(!glob_match("mcp-?", "mcp-12"));
+        assert!(glob_match("?*?", "ab"));
+
+        assert!(glob_match("exact", "exact"));
+        assert!(!glob_match("exact", "exactly"));
+        assert!(!glob_match("", "x"));
+    }
+
+    #[test]
+    fn filters_combine() {
+        let config: ServersConfig = serde_json::from_str(r#"{ "servers": {
+            "sequential-thinking": { "type": "runtime", "runtime": "npx", "path": "st", "tags": ["thinking"] },
+            "clear-thought": { "type": "runtime", "runtime": "node", "path": "ct", "tags": ["thinking"], "disabled": true },
+            "search": { "type": "remote", "url": "https://example.com/mcp", "tags": ["web"], "description": "Web SEARCH" },
+            "github": { "type": "docker", "image": "ghcr.io/github/github-mcp-server", "tags": ["code", "web"] }
+        } }"#)
+        .unwrap();
+        let matching = |filter: ServerFilter| -> Vec<&str> {
+            config
+                .servers
+                .iter()
+                .filter(|(name, server)| filter.matches(name, server))
+                .map(|(name, _)| name.as_str())
+                .collect()
+        };
+
+        // Disabled servers match too, so they can be enabled by filter
+        assert_eq!(
+            matching(ServerFilter { tags: strings(&["thinking"]), ..Default::default() }),
+            ["clear-thought", "sequential-thinking"]
+        );
+        // Repeated tags match any of them
+        assert_eq!(
+            matching(ServerFilter { tags: strings(&["thinking", "code"]), ..Default::default() }),
+            ["clear-thought", "github", "sequential-thinking"]
+        );
+        assert_eq!(
+            matching(ServerFilter { tags: strings(&["thinking"]), runtime: Some("npx".into()), ..Default::default() }),
+            ["sequential-thinking"]
+        );
+        assert_eq!(
+            matching(ServerFilter { tags: strings(&["web"]), server_type: Some(ServerType::Docker), ..Default::default() }),
+            ["github"]
+        );
+        // Search is case-insensitive over names, descriptions and tags
+        assert_eq!(
+            matching(ServerFilter { search: Some("search".into()), ..Default::default() }),
+            ["search"]
+        );
+        assert_eq!(matching(ServerFilter { search: Some("WEB".into()), ..Default::default() }), ["github", "search"]);
+        assert!(matching(ServerFilter { tags: strings(&["web"]), server_type: Some(ServerType::Native), ..Default::default() })
+            .is_empty());
+        assert_eq!(matching(ServerFilter::default()).len(), 4);
+    }
+
+    // ------------------------------------------------------------------------
+    // Profiles
+    // ------------------------------------------------------------------------
+
+    const PROFILES: &str = r#"{
+        "profile": "work",
+        "profiles": { "work": ["docs", "tag:db"], "web": ["search"] },
+        "servers": {
+            "docs": { "type": "native", "command": "docs" },
+            "pg": { "type": "native", "command": "pg", "tags": ["db"] },
+            "search": { "type": "native", "command": "search", "disabled": true }
+        }
+    }"#;
+
+    fn enabled(config: &ServersConfig) -> Vec<&str> {
+        config.servers.iter().filter(|(_, s)| !s.disabled).map(|(name, _)| name.as_str()).collect()
+    }
+
+    #[test]
+    fn profiles_pick_enabled_servers() {
+        let config = || -> ServersConfig { serde_json::from_str(PROFILES).unwrap() };
+
+        let mut active = config();
+        active.apply_profile(None).unwrap();
+        assert_eq!(enabled(&active), ["docs", "pg"]);
+
+        // A named profile overrides the active one, and the disabled flags
+        let mut web = config();
+        web.apply_profile(Some("web")).unwrap();
+        assert_eq!(enabled(&web), ["search"]);
+
+        // Without one, the flags stand
+        let mut none = ServersConfig { profile: None, ..config() };
+        none.apply_profile(None).unwrap();
+        assert_eq!(enabled(&none), ["docs", "pg"]);
+    }
+
+    #[test]
+    fn unknown_profiles_are_errors() {
+        let mut config: ServersConfig = serde_json::from_str(PROFILES).unwrap();
+        assert_eq!(
+            config.apply_profile(Some("home")).unwrap_err().to_string(),
+            "Profile 'home' not found (known: web, work)"
+        );
+        // Nothing was applied
+        assert_eq!(enabled(&config), ["docs", "pg"]);
+
+        config.profiles.clear();
+        assert_eq!(
+            config.profile_servers("work").unwrap_err().to_string(),
+            "Profile 'work' not found (servers.json has no profiles)"
+        );
+        assert_eq!(
+            validation_errors(r#"{ "profile": "home", "servers": {} }"#),
+            ["profile: unknown profile 'home'"]
+        );
+    }
+
+    #[test]
+    fn profiles_referencing_removed_servers() {
+        let mut config: ServersConfig = serde_json::from_str(PROFILES).unwrap();
+        config.servers.remove("docs");
+        assert_eq!(
+            config.profile_servers("work").unwrap_err().to_string(),
+            "Profile 'work' lists unknown server 'docs'"
+        );
+        // A tag with no servers left is just empty
+        config.servers.remove("pg");
+        config.profiles.insert("work".into(), strings(&["tag:db"]));
+        assert!(config.profile_servers("work").unwrap().is_empty());
+
+        assert_eq!(
+            validation_errors(r#"{ "profiles": { "work": ["tag:db", "gone"] }, "servers": {} }"#),
+            ["profiles.work.1: unknown server 'gone'"]
+        );
+
+        // `mcp remove` drops the server from profiles, so the config stays valid
+        let dir = temp_dir("profile-remove");
+        let paths = test_paths(&dir);
+        fs::write(&paths.servers_json, PROFILES).unwrap();
+        let selection = ServerSelection { names: strings(&["docs"]), all: false, filter: ServerFilter::default() };
+        cmd_remove(&paths, &selection, true).unwrap();
+        let config = load_servers(&paths).unwrap();
+        assert_eq!(config.profiles["work"], strings(&["tag:db"]));
+        assert_eq!(config.profile_servers("work").unwrap().into_iter().collect::<Vec<_>>(), ["pg"]);
+    }
+
+    // ------------------------------------------------------------------------
+    // .env files
+    // ------------------------------------------------------------------------
+
+    fn env_vars(content: &str) -> Vec<(String, String)> {
+        EnvFile::parse(content).vars().into_iter().collect()
+    }
+
+    fn pairs(items: &[(&str, &str)]) -> Vec<(String, String)> {
+        items.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn env_export_quotes_and_comments() {
+        let content = concat!(
+            "# header\n",
+            "export TOKEN=abc123\n",
+            "BARE = spaced value  # comment\n",
+            "HASH=a#b\n",
+            "SINGLE='literal \\n $HOME' # kept\n",
+            "DOUBLE=\"tab\\there \\\"quoted\\\" \\\\ end\"\n",
+            "EMPTY=\n",
+            "exporter=plain\n",
+        );
+        assert_eq!(
+            env_vars(content),
+            pairs(&[
+                ("BARE", "spaced value"),
+                ("DOUBLE", "tab\there \"quoted\" \\ end"),
+                ("EMPTY", ""),
+                ("HASH", "a#b"),
+                ("SINGLE", "literal \\n $HOME"),
+                ("TOKEN", "abc123"),
+                ("exporter", "plain"),
+            ])
+        );
+        assert!(EnvFile::parse(content).invalid_lines().is_empty());
+        // Nothing changed, nothing rewritten
+        assert_eq!(EnvFile::parse(content).contents(), content);
+    }
+
+    #[test]
+    fn env_multi_line_values() {
+        let content = concat!(
+            "KEY=\"-----BEGIN KEY-----\n",
+            "abc\n",
+            "-----END KEY-----\"\n",
+            "ESCAPED=\"one\\ntwo\"\n",
+            "CONTINUED=first \\\n",
+            "  second\n",
+            "WINDOWS=C:\\tools\\\n",
+            "AFTER=x\n",
+        );
+        assert_eq!(
+            env_vars(content),
+            pairs(&[
+                ("AFTER", "x"),
+                ("CONTINUED", "first \n  second"),
+                ("ESCAPED", "one\ntwo"),
+                ("KEY", "-----BEGIN KEY-----\nabc\n-----END KEY-----"),
+                ("WINDOWS", "C:\\tools\\"),
+            ])
+        );
+        assert_eq!(EnvFile::parse(content).contents(), content);
+    }
+
+    #[test]
+    fn env_invalid_lines_kept() {
+        let content = "GOOD=1\nnot a variable\n1BAD=2\nQUOTED=\"open\" trailing\n";
+        let file = EnvFile::parse(content);
+        assert_eq!(env_vars(content), pairs(&[("GOOD", "1")]));
+        assert_eq!(
+            file.invalid_lines(),
+            [(2, "not a variable"), (3, "1BAD=2"), (4, "QUOTED=\"open\" trailing")]
+        );
+        assert_eq!(file.contents(), content);
+    }
+
+    #[test]
+    fn env_crlf_preserved() {
+        let content = "# notes\r\nA=1\r\nB=\"x\r\ny\"\r\n";
+        let mut file = EnvFile::parse(content);
+        assert_eq!(file.vars()["B"], "x\ny");
+        assert!(file.set("A", "2"));
+        assert!(file.set("C", "3"));
+        assert_eq!(file.contents(), "# notes\r\nA=2\r\nB=\"x\r\ny\"\r\nC=3\r\n");
+    }
+
+    #[test]
+    fn env_set_and_remove_touch_only_their_lines() {
+        let content = concat!(
+            "# API keys\n",
+            "\n",
+            "export OPENAI_KEY='old' # rotate monthly\n",
+            "OTHER = \"unchanged\"   # spacing kept\n",
+            "DUP=first\n",
+            "DUP=second\n",
+            "GONE=1\n",
+            "  # indented comment\n",
+        );
+        let mut file = EnvFile::parse(content);
+
+        assert!(!file.set("OTHER", "unchanged"));
+        assert!(file.set("OPENAI_KEY", "new key"));
+        // The last definition wins, so that one is rewritten
+        assert!(file.set("DUP", "third"));
+        assert!(file.set("MULTI", "line one\nline two"));
+        assert!(file.remove("GONE"));
+        assert!(!file.remove("GONE"));
+
+        assert_eq!(
+            file.contents(),
+            concat!(
+                "# API keys\n",
+                "\n",
+                "export OPENAI_KEY='new key' # rotate monthly\n",
+                "OTHER = \"unchanged\"   # spacing kept\n",
+                "DUP=first\n",
+                "DUP=third\n",
+                "  # indented comment\n",
+                "MULTI=\"line one\\nline two\"\n",
+            )
+        );
+        let reparsed = EnvFile::parse(&file.contents()).vars();
+        assert_eq!(reparsed["OPENAI_KEY"], "new key");
+        assert_eq!(reparsed["MULTI"], "line one\nline two");
+    }
+
+    #[test]
+    fn env_placeholders() {
+        let example = "sample-value".to_string();
+        assert!(is_placeholder("TAVILY_API_KEY", "your-tavily-api-key-here", None));
+        assert!(is_placeholder("OPENAI_API_KEY", "sk-...", None));
+        assert!(is_placeholder("API_TOKEN", "sample-value", Some(&example)));
+        assert!(is_placeholder("HOST", "<hostname>", None));
+        // Only secrets are compared with .env.example or checked for "..."
+        assert!(!is_placeholder("LOG_LEVEL", "sample-value", Some(&example)));
+        assert!(!is_placeholder("GREETING", "Loading...", None));
+        assert!(!is_placeholder("API_TOKEN", "tvly-12345", Some(&example)));
+    }
+
+    // ------------------------------------------------------------------------
+    // Server environment
+    // ------------------------------------------------------------------------
+
+    fn server(text: &str) -> Server {
+        serde_json::from_str(text).unwrap()
+    }
+
+    /// Variables `command` sets, leaving out the allowlisted parent ones.
+    fn command_env(command: &Command) -> BTreeMap<String, Option<String>> {
+        command
+            .get_envs()
+            .map(|(key, value)| (key.to_string_lossy().into_owned(), value.map(|v| v.to_string_lossy().into_owned())))
+            .filter(|(key, _)| !ENV_ALLOWLIST.iter().any(|allowed| allowed.eq_ignore_ascii_case(key)))
+            .collect()
+    }
+
+    /// A process variable only this test uses, so parallel tests don't race.
+    fn process_var(label: &str, value: &str) -> String {
+        let name = format!("MCP_TEST_{}_{}", label, std::process::id());
+        std::env::set_var(&name, value);
+        name
+    }
+
+    #[test]
+    fn empty_values_fall_through_to_the_next_source() {
+        let fallback = process_var("FALLBACK", "from-process");
+        let dotenv = BTreeMap::from([
+            ("API_KEY".to_string(), "from-dotenv".to_string()),
+            (fallback.clone(), String::new()),
+        ]);
+        let stored = BTreeMap::from([("API_KEY".to_string(), String::new())]);
+        let env_vars = merge_env(dotenv, stored);
+        assert_eq!(lookup_env_var(&env_vars, "API_KEY").as_deref(), Some("from-dotenv"));
+        assert_eq!(lookup_env_var(&env_vars, &fallback).as_deref(), Some("from-process"));
+
+        // `mcp run` sees the same values `mcp env check` reports
+        let server = server(&format!(
+            r#"{{ "type": "native", "command": "tool", "env": ["API_KEY", "{}"] }}"#,
+            fallback
+        ));
+        let mut command = Command::new("tool");
+        apply_server_env(&mut command, "test", &server, &env_vars).unwrap();
+        let env = command_env(&command);
+        assert_eq!(env["API_KEY"].as_deref(), Some("from-dotenv"));
+        assert_eq!(env[&fallback].as_deref(), Some("from-process"));
+    }
+
+    fn applied_env(server_json: &str, env_vars: &[(&str, &str)]) -> Result<BTreeMap<String, Option<String>>> {
+        let env_vars: BTreeMap<_, _> = env_vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        let mut command = Command::new("tool");
+        apply_server_env(&mut command, "test", &server(server_json), &env_vars)?;
+        Ok(command_env(&command))
+    }
+
+    #[test]
+    fn server_env_scoped_to_declared_names() {
+        let env_vars = [("API_KEY", "a"), ("OTHER_KEY", "b")];
+        let scoped = applied_env(r#"{ "type": "native", "command": "tool", "env": ["API_KEY"] }"#, &env_vars).unwrap();
+        assert_eq!(scoped, BTreeMap::from([("API_KEY".to_string(), Some("a".to_string()))]));
+
+        let inherited = applied_env(
+            r#"{ "type": "native", "command": "tool", "env": ["API_KEY"], "inherit_env": true }"#,
+            &env_vars,
+        )
+        .unwrap();
+        assert_eq!(inherited.keys().collect::<Vec<_>>(), ["API_KEY", "OTHER_KEY"]);
+    }
+
+    #[test]
+    fn server_env_renames_apply_with_and_without_inherit() {
+        for inherit in [false, true] {
+            let env = applied_env(
+                &format!(
+                    r#"{{ "type": "native", "command": "tool", "env": {{ "GITHUB_TOKEN": "GH_PAT" }}, "inherit_env": {} }}"#,
+                    inherit
+                ),
+                &[("GH_PAT", "pat")],
+            )
+            .unwrap();
+            assert_eq!(env["GITHUB_TOKEN"].as_deref(), Some("pat"), "inherit_env: {}", inherit);
+        }
+    }
+
+    #[test]
+    fn server_env_reports_missing_names() {
+        let missing = format!("MCP_TEST_MISSING_{}", std::process::id());
+        let error = applied_env(
+            &format!(r#"{{ "type": "native", "command": "tool", "env": ["EMPTY_KEY", "{}"] }}"#, missing),
+            &[("EMPTY_KEY", "")],
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "Server 'test' requires EMPTY_KEY, {} which are not set. Set with: mcp env set EMPTY_KEY <value>",
+                missing
+            )
+        );
+        // Declared names are required even when everything is inherited
+        let error = applied_env(
+            &format!(r#"{{ "type": "native", "command": "tool", "env": ["{}"], "inherit_env": true }}"#, missing),
+            &[],
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("which is not set"), "{}", error);
+    }
+
+    // ------------------------------------------------------------------------
+    // Runtimes
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn nvm_default_resolves_installed_version() {
+        let nvm_dir = temp_dir("nvm");
+        for version in ["v18.19.0", "v20.9.0", "v20.11.0", "v22.1.0"] {
+            fs::create_dir_all(nvm_dir.join("versions").join("node").join(version)).unwrap();
+        }
+        fs::create_dir_all(nvm_dir.join("alias")).unwrap();
+        let default = |alias: &str| {
+            fs::write(nvm_dir.join("alias").join("default"), alias).unwrap();
+            nvm_default_version(&nvm_dir)
+        };
+
+        // Newest match, compared numerically rather than as text
+        assert_eq!(default("20\n").as_deref(), Some("v20.11.0"));
+        assert_eq!(default("v20.9.0").as_deref(), Some("v20.9.0"));
+        assert_eq!(default("lts/*").as_deref(), Some("v22.1.0"));
+        assert_eq!(default("19"), None);
+        // Not 2.x for 20
+        assert_eq!(default("2"), None);
+
+        fs::remove_file(nvm_dir.join("alias").join("default")).unwrap();
+        assert_eq!(nvm_default_version(&nvm_dir), None);
+    }
+
+    #[test]
+    fn templates_expand_install_locations() {
+        let dir = temp_dir("templates");
+        let paths = test_paths(&dir);
+        let root = ModuleRoot::node_prefix("fnm", dir.join("node"));
+        let display = |path: &Path| path.to_string_lossy().to_string();
+
+        assert_eq!(
+            expand_template(&paths, Some(&root), "{mcp_dir}/{home}"),
+            format!("{}/{}", display(&dir), display(&paths.home))
+        );
+        assert_eq!(expand_template(&paths, Some(&root), "{node_bin}"), display(&root.bin_dir));
+        assert_eq!(expand_template(&paths, Some(&root), "{node_modules}"), display(&root.node_modules));
+        assert_eq!(expand_template(&paths, None, "{bun_modules}"), display(&paths.bun_root.node_modules));
+        // Without a Node install the Node locations are empty
+        assert_eq!(expand_template(&paths, None, "[{node_bin}{node_modules}]"), "[]");
+        assert_eq!(expand_template(&paths, None, "{unknown}"), "{unknown}");
+    }
+
+    #[test]
+    fn runtime_launch_finds_script_and_places_args() {
+        let dir = temp_dir("runtime-launch");
+        let mut paths = test_paths(&dir);
+        let empty = ModuleRoot::node_prefix("volta", dir.join("empty"));
+        let node = ModuleRoot::node_prefix("fnm", dir.join("node"));
+        paths.node_roots = vec![empty, node];
+        paths.bun_root = ModuleRoot::node_prefix("bun", dir.join("bun"));
+        let script = paths.node_roots[1].node_modules.join("@acme/docs/index.js");
+        fs::create_dir_all(script.parent().unwrap()).unwrap();
+        fs::write(&script, "").unwrap();
+        let registry = runtime_registry(&ServersConfig::default());
+        let args = strings(&["--port", "1"]);
+
+        // The first root holding the script wins, and its bin goes on PATH
+        let launch = resolve_runtime(&paths, &registry["node"], "@acme/docs/index.js", &args).unwrap();
+        assert_eq!(launch.program, "node");
+        assert_eq!(launch.args, strings(&[&script.to_string_lossy(), "--port", "1"]));
+        assert_eq!(launch.path_dirs, vec![paths.node_roots[1].bin_dir.clone()]);
+
+        let error = resolve_runtime(&paths, &registry["node"], "missing/index.js", &args).err().unwrap();
+        assert!(error.to_string().starts_with("'missing/index.js' is not installed in any global node_modules"));
+
+        // Other module roots are joined as given; no node_modules lookup
+        let launch = resolve_runtime(&paths, &registry["bun"], "pkg/index.js", &[]).unwrap();
+        let bun_script = paths.bun_root.node_modules.join("pkg/index.js");
+        assert_eq!(launch.args, strings(&["run", &bun_script.to_string_lossy()]));
+
+        // Arguments go last when the definition has no `{args}`
+        let def = RuntimeDef::new("{mcp_dir}/run", &["--script={path}"], &[], None);
+        let launch = resolve_runtime(&paths, &def, "tool.js", &args).unwrap();
+        assert_eq!(launch.program, format!("{}/run", dir.to_string_lossy()));
+        assert_eq!(launch.args, strings(&["--script=tool.js", "--port", "1"]));
+        assert!(launch.path_dirs.is_empty());
+    }
+
+    // ------------------------------------------------------------------------
+    // Packages and lockfile
+    // ------------------------------------------------------------------------
+
+    fn test_paths(dir: &Path) -> McpPaths {
+        McpPaths::new(Some(dir.to_path_buf()), None).unwrap()
+    }
+
+    #[test]
+    fn lock_round_trip_and_drift() {
+        let dir = temp_dir("lock");
+        let paths = test_paths(&dir);
+        let registry = runtime_registry(&ServersConfig::default());
+        let docs = server(r#"{ "type": "runtime", "runtime": "node", "path": "@acme/docs/dist/index.js", "version": "^1" }"#);
+        let package = server_package(&docs, &registry).unwrap();
+        assert_eq!((package.package, package.requested), ("@acme/docs", Some("^1")));
+
+        let mut lock = ServersLock::default();
+        lock_package(&mut lock, &package, &["docs", "docs-copy"], "1.2.0", "sha512-abc");
+        save_lock(&paths, &mut lock).unwrap();
+        let lock = load_lock(&paths).unwrap();
+        assert_eq!(lock.lockfile_version, LOCKFILE_VERSION);
+        let expected = LockedPackage {
+            package: "@acme/docs".to_string(),
+            requested: Some("^1".to_string()),
+            version: "1.2.0".to_string(),
+            integrity: "sha512-abc".to_string(),
+        };
+        assert_eq!(lock.entry("docs", &package), Some(&expected));
+        assert_eq!(lock.entry("docs-copy", &package), Some(&expected));
+
+        // A changed `version` or package no longer matches the lock
+        let bumped = server(r#"{ "type": "runtime", "runtime": "node", "path": "@acme/docs/dist/index.js", "version": "^2" }"#);
+        assert_eq!(lock.entry("docs", &server_package(&bumped, &registry).unwrap()), None);
+        let other = server(r#"{ "type": "runtime", "runtime": "node", "path": "@acme/other/index.js", "version": "^1" }"#);
+        assert_eq!(lock.entry("docs", &server_package(&other, &registry).unwrap()), None);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn version_1_lock_drops_file_hashes() {
+        let dir = temp_dir("lock-v1");
+        let paths = test_paths(&dir);
+        fs::write(
+            &paths.lock_json,
+            r#"{ "lockfileVersion": 1, "servers": { "docs": { "package": "docs", "version": "1.0.0", "integrity": "sha256-files" } } }"#,
+        )
+        .unwrap();
+        let lock = load_lock(&paths).unwrap();
+        assert_eq!(lock.servers["docs"].version, "1.0.0");
+        assert_eq!(lock.servers["docs"].integrity, "");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn npm_view_integrity() {
+        let dist = parse_npm_view("docs@^1", br#"[
+            { "version": "1.0.0", "dist.integrity": "sha512-old" },
+            { "version": "1.1.0", "dist.integrity": "sha1-x sha512-new" }
+        ]"#)
+        .unwrap();
+        assert_eq!((dist.version.as_str(), dist.integrity.as_str()), ("1.1.0", "sha512-new"));
+
+        let error = parse_npm_view("@acme/docs@1", br#"{ "version": "1.0.0", "dist.integrity": "sha1-x" }"#).unwrap_err();
+        assert_eq!(error.to_string(), "@acme/docs@1.0.0 has no sha512 integrity in the registry");
+        let error = parse_npm_view("docs@9", b"\n").unwrap_err();
+        assert_eq!(error.to_string(), "No published version matches docs@9");
+    }
+
+    #[test]
+    fn tarball_integrity_matches_npm_format() {
+        let dir = temp_dir("integrity");
+        let tarball = dir.join("package.tgz");
+        fs::write(&tarball, "hello").unwrap();
+        assert_eq!(
+            file_integrity(&tarball).unwrap(),
+            "sha512-m3HSJL1i83hdltRq0+o9czGb+8KJDKra4t/3JRlnPKcjI8PZm6XBHXx6zG4UuMXaDEZjR1wuXDre9G9zvN7AQw=="
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    // ------------------------------------------------------------------------
+    // Server fields
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn field_flags_imply_type() {
+        let kind = |fields: ServerFields| fields.kind(None).unwrap();
+        assert_eq!(
+            kind(ServerFields { url: Some("https://example.com/mcp".into()), ..Default::default() }),
+            server(r#"{ "type": "remote", "url": "https://example.com/mcp" }"#).kind
+        );
+        assert_eq!(
+            kind(ServerFields { command: Some("tool".into()), args: Some(strings(&["--stdio"])), ..Default::default() }),
+            server(r#"{ "type": "native", "command": "tool", "args": ["--stdio"] }"#).kind
+        );
+        assert_eq!(
+            kind(ServerFields { image: Some("ghcr.io/x/y".into()), ..Default::default() }),
+            server(r#"{ "type": "docker", "image": "ghcr.io/x/y" }"#).kind
+        );
+        assert_eq!(
+            kind(ServerFields { path: Some("pkg/index.js".into()), ..Default::default() }),
+            server(r#"{ "type": "runtime", "runtime": "node", "path": "pkg/index.js" }"#).kind
+        );
+    }
+
+    #[test]
+    fn field_flags_checked_against_type() {
+        let error = |fields: ServerFields| fields.kind(None).unwrap_err().to_string();
+        assert_eq!(
+            error(ServerFields { server_type: Some(ServerType::Native), url: Some("https://x".into()), ..Default::default() }),
+            "--url is not used by native servers"
+        );
+        assert_eq!(
+            error(ServerFields { url: Some("https://x".into()), args: Some(strings(&["a"])), ..Default::default() }),
+            "--arg is not used by remote servers"
+        );
+        assert_eq!(
+            error(ServerFields { server_type: Some(ServerType::Native), ..Default::default() }),
+            "native servers need a command (--command)"
+        );
+        assert_eq!(error(ServerFields::default()), "runtime servers need a path (--path)");
+    }
+
+    #[test]
+    fn field_edits_keep_what_is_not_given() {
+        let current = server(r#"{ "type": "runtime", "runtime": "bun", "path": "pkg/index.js", "version": "^1", "args": ["--x"] }"#).kind;
+        // Same type: only the given setting changes
+        assert_eq!(
+            ServerFields { path: Some("pkg/main.js".into()), ..Default::default() }.kind(Some(&current)).unwrap(),
+            server(r#"{ "type": "runtime", "runtime": "bun", "path": "pkg/main.js", "version": "^1", "args": ["--x"] }"#).kind
+        );
+        // A new type carries the arguments over
+        assert_eq!(
+            ServerFields { command: Some("pkg".into()), ..Default::default() }.kind(Some(&current)).unwrap(),
+            server(r#"{ "type": "native", "command": "pkg", "args": ["--x"] }"#).kind
+        );
+
+        let remote = server(r#"{ "type": "remote", "url": "https://x/sse", "transport": "sse", "headers": { "A": "1" } }"#).kind;
+        assert_eq!(
+            ServerFields { url: Some("https://y/sse".into()), ..Default::default() }.kind(Some(&remote)).unwrap(),
+            server(r#"{ "type": "remote", "url": "https://y/sse", "transport": "sse", "headers": { "A": "1" } }"#).kind
+        );
+    }
+
+    #[test]
+    fn args_split_and_join_round_trip() {
+        assert_eq!(split_args(r#"  -y "my pkg" 'a "b"' x"y z"  "#), strings(&["-y", "my pkg", r#"a "b""#, "xy z"]));
+        assert_eq!(split_args(r#"--name "" end"#), strings(&["--name", "", "end"]));
+        assert!(split_args("   ").is_empty());
+
+        for args in [
+            strings(&["-y", "@acme/docs"]),
+            strings(&["my pkg", ""]),
+            strings(&[r#"say "hi""#, "it's"]),
+            strings(&[r#"it's "quoted""#, "tab\there"]),
+        ] {
+            assert_eq!(split_args(&join_args(&args)), args, "{}", join_args(&args));
+        }
+        assert_eq!(join_args(&strings(&["-y", "my pkg"])), r#"-y "my pkg""#);
+    }
+
+    // ------------------------------------------------------------------------
+    // Client configs
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn strip_comments_outside_strings() {
+        let content = "{\n  // line\n  \"url\": \"https://x/*y*/\", /* block */\n  \"a\": 1 // end\n}";
+        assert_eq!(
+            json(&strip_json_comments(content)),
+            json(r#"{ "url": "https://x/*y*/", "a": 1 }"#)
+        );
+    }
+
+    #[test]
+    fn merge_keeps_settings_and_unmanaged_servers() {
+        let config: ServersConfig = serde_json::from_str(r#"{
+            "unmanaged": ["mine"],
+            "servers": {
+                "docs": { "type": "native", "command": "docs" },
+                "off": { "type": "native", "command": "off", "disabled": true }
+            }
+        }"#)
+        .unwrap();
+        let existing = json(&strip_json_comments(r#"{
+            // user settings stay
+            "theme": "dark",
+            "mcpServers": {
+                "docs": { "command": "old" },
+                "off": { "command": "off" },
+                "mine": { "command": "hand-written" },
+                "gone": { "command": "stale" }
+            }
+        }"#));
+        let generated = json(r#"{ "mcpServers": { "docs": { "command": "mcp", "args": ["run", "docs"] } } }"#);
+
+        assert_eq!(
+            merge_client_config(Some(existing), generated.clone(), "mcpServers", &config),
+            json(r#"{
+                "theme": "dark",
+                "mcpServers": {
+                    "docs": { "command": "mcp", "args": ["run", "docs"] },
+                    "mine": { "command": "hand-written" }
+                }
+            }"#)
+        );
+        assert_eq!(merge_client_config(None, generated.clone(), "mcpServers", &config), generated);
+    }
+
+    #[test]
+    fn client_config_backed_up_before_write() {
+        let dir = temp_dir("client-config");
+        let path = dir.join("mcp.json");
+        fs::write(&path, "{ \"old\": true }").unwrap();
+
+        write_client_config(&path, "{ \"new\": true }", true).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{ \"new\": true }");
+        assert_eq!(fs::read_to_string(dir.join("mcp.json.bak")).unwrap(), "{ \"old\": true }");
+
+        // An unchanged file is not rewritten, so the backup stays
+        write_client_config(&path, "{ \"new\": true }", true).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("mcp.json.bak")).unwrap(), "{ \"old\": true }");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    // ------------------------------------------------------------------------
+    // Client import
+    // ------------------------------------------------------------------------
+
+    /// The server `mcp import` makes of a client entry, or `None` when the
+    /// entry already launches through `mcp run`.
+    fn detect(entry: &str) -> Option<ServerKind> {
+        let registry = runtime_registry(&ServersConfig::default());
+        detect_server_shape(&ClientEntry::parse(&json(entry)), &registry).map(|server| server.kind)
+    }
+
+    fn kind(text: &str) -> Option<ServerKind> {
+        Some(server(text).kind)
+    }
+
+    #[test]
+    fn import_registry_runtimes() {
+        assert_eq!(
+            detect(r#"{ "command": "npx", "args": ["-y", "@acme/docs", "--verbose"] }"#),
+            kind(r#"{ "type": "runtime", "runtime": "npx", "path": "@acme/docs", "args": ["--verbose"] }"#)
+        );
+        // Windows clients wrap the launcher in `cmd /c`
+        assert_eq!(
+            detect(r#"{ "command": "cmd", "args": ["/c", "npx.cmd", "-y", "@acme/docs"] }"#),
+            kind(r#"{ "type": "runtime", "runtime": "npx", "path": "@acme/docs" }"#)
+        );
+        // OpenCode keeps the argv in `command` and uses `enabled`
+        let opencode = json(r#"{ "type": "local", "command": ["bunx", "@acme/docs"], "enabled": false }"#);
+        assert!(ClientEntry::parse(&opencode).disabled);
+        assert_eq!(
+            detect(&opencode.to_string()),
+            kind(r#"{ "type": "runtime", "runtime": "bunx", "path": "@acme/docs" }"#)
+        );
+    }
+
+    #[test]
+    fn import_global_node_modules_scripts() {
+        assert_eq!(
+            detect(r#"{
+                "command": "C:\\Program Files\\nodejs\\node.exe",
+                "args": ["C:\\Users\\me\\AppData\\Roaming\\npm\\node_modules\\@acme\\docs\\dist\\index.js", "--stdio"]
+            }"#),
+            kind(r#"{ "type": "runtime", "runtime": "node", "path": "@acme/docs/dist/index.js", "args": ["--stdio"] }"#)
+        );
+        assert_eq!(
+            detect(r#"{ "command": "bun", "args": ["run", "/home/me/.bun/install/global/node_modules/tool/index.js"] }"#),
+            kind(r#"{ "type": "runtime", "runtime": "bun", "path": "tool/index.js" }"#)
+        );
+        // A script outside node_modules is run as is
+        assert_eq!(
+            detect(r#"{ "command": "node", "args": ["/srv/tool.js"] }"#),
+            kind(r#"{ "type": "native", "command": "node", "args": ["/srv/tool.js"] }"#)
+        );
+    }
+
+    #[test]
+    fn import_mcp_wrappers() {
+        assert_eq!(
+            detect(r#"{ "command": "C:\\Users\\me\\.mcp\\mcp-run.cmd", "args": ["node:@acme/docs/dist/index.js", "--x"] }"#),
+            kind(r#"{ "type": "runtime", "runtime": "node", "path": "@acme/docs/dist/index.js", "args": ["--x"] }"#)
+        );
+        assert_eq!(detect(r#"{ "command": "/usr/local/bin/mcp", "args": ["run", "docs"] }"#), None);
+    }
+
+    #[test]
+    fn import_docker_and_native() {
+        assert_eq!(
+            detect(r#"{
+                "command": "docker",
+                "args": ["run", "-i", "--rm", "-e", "GITHUB_TOKEN", "ghcr.io/github/github-mcp-server", "stdio"],
+                "env": { "GITHUB_TOKEN": "${env:GITHUB_TOKEN}" }
+            }"#),
+            kind(r#"{
+                "type": "docker",
+                "image": "ghcr.io/github/github-mcp-server",
+                "docker_args": ["-e", "GITHUB_TOKEN"],
+                "args": ["stdio"]
+            }"#)
+        );
+        assert_eq!(
+            detect(r#"{ "command": "/opt/tool/bin/tool", "args": ["--stdio"] }"#),
+            kind(r#"{ "type": "native", "command": "/opt/tool/bin/tool", "args": ["--stdio"] }"#)
+        );
+    }
+
+    #[test]
+    fn import_remote_servers() {
+        // VS Code names the transport in `type` and uses `${env:X}`
+        let vscode = json(r#"{
+            "type": "sse",
+            "url": "https://example.com/sse",
+            "headers": { "Authorization": "Bearer ${env:API_TOKEN}" },
+            "env": { "API_TOKEN": "${env:API_TOKEN}", "REGION": "eu" }
+        }"#);
+        let entry = ClientEntry::parse(&vscode);
+        assert_eq!(entry.env["REGION"], "eu");
+        assert_eq!(env_placeholder_name(&entry.env["API_TOKEN"]), Some("API_TOKEN"));
+        assert_eq!(env_placeholder_name("eu"), None);
+        assert_eq!(
+            detect(&vscode.to_string()),
+            kind(r#"{
+                "type": "remote",
+                "url": "https://example.com/sse",
+                "transport": "sse",
+                "headers": { "Authorization": "Bearer ${API_TOKEN}" }
+            }"#)
+        );
+        // Windsurf calls it `serverUrl`
+        assert_eq!(
+            detect(r#"{ "serverUrl": "https://example.com/mcp" }"#),
+            kind(r#"{ "type": "remote", "url": "https://example.com/mcp" }"#)
+        );
+    }
+
+    // ------------------------------------------------------------------------
+    // Secret stores
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn age_store_round_trip() {
+        let dir = temp_dir("age");
+        let store = AgeStore {
+            file: dir.join("secrets.age"),
+            identity: dir.join("age.key"),
+        };
+        assert!(store.load().unwrap().is_empty());
+
+        store.set("API_KEY", "sk-secret value").unwrap();
+        store.set("OTHER", "line one\nline two").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&store.identity).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        let encrypted = fs::read_to_string(&store.file).unwrap();
+        assert!(encrypted.starts_with("-----BEGIN AGE ENCRYPTED FILE-----"));
+        assert!(!encrypted.contains("sk-secret"));
+
+        // A fresh handle reads what the first wrote, with the same key
+        let reopened = AgeStore {
+            file: store.file.clone(),
+            identity: store.identity.clone(),
+        };
+        assert_eq!(
+            reopened.load().unwrap(),
+            BTreeMap::from([
+                ("API_KEY".to_string(), "sk-secret value".to_string()),
+                ("OTHER".to_string(), "line one\nline two".to_string()),
+            ])
+        );
+
+        assert!(reopened.remove("API_KEY").unwrap());
+        assert!(!reopened.remove("API_KEY").unwrap());
+        assert_eq!(store.load().unwrap().keys().collect::<Vec<_>>(), ["OTHER"]);
+        assert!(!dir.join("secrets.age.tmp").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    // ------------------------------------------------------------------------
+    // Logs
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn rotating_log_shared_between_writers() {
+        let dir = temp_dir("log");
+        let path = dir.join("server.log");
+        // Two `mcp run` processes for the same server
+        let first = RotatingLog::open(path.clone()).unwrap();
+        let second = RotatingLog::open(path.clone()).unwrap();
+        let big = "x".repeat(LOG_MAX_BYTES as usize * 3 / 5);
+
+        first.log("server", &format!("one {}", big));
+        first.log("server", &format!("two {}", big));
+        // The second writer follows the rotation instead of appending to .log.1
+        second.log("server", "after rotation");
+        let read = |path: &Path| fs::read_to_string(path).unwrap_or_default();
+        assert!(read(&rotated_log(&path, 1)).contains("one "));
+        assert!(!read(&rotated_log(&path, 1)).contains("after rotation"));
+        assert!(read(&path).contains("two "));
+        assert!(read(&path).contains("after rotation"));
+
+        // Rotated once, by whichever writer crosses the limit
+        second.log("server", &format!("three {}", big));
+        assert!(read(&rotated_log(&path, 1)).contains("after rotation"));
+        assert!(read(&rotated_log(&path, 2)).contains("one "));
+        assert!(read(&path).contains("three "));
+        assert!(!rotated_log(&path, 3).exists());
+        assert!(!PathBuf::from(format!("{}.lock", path.display())).exists());
+
+        fs::remove_dir_all(dir).unwrap();
     }
 }
@@ -353,7 +353,7 @@ fn get_sync_status(cfg: &ResolvedConfig) -> SyncStatus {
 fn cmd_status(configs: &[ResolvedConfig], category: Option<String>, outdated: bool) -> Result<()> {
     let filtered: Vec<_> = configs
         .iter()
-        .filter(|c| category.as_ref().map_or(true, |cat| &c.category == cat))
+        .filter(|c| category.as_ref().is_none_or(|cat| &c.category == cat))
         .collect();
 
     let rows: Vec<StatusRow> = filtered
@@ -398,7 +398,7 @@ fn cmd_status(configs: &[ResolvedConfig], category: Option<String>, outdated: bo
 fn cmd_list(configs: &[ResolvedConfig], category: Option<String>, json: bool) -> Result<()> {
     let filtered: Vec<_> = configs
         .iter()
-        .filter(|c| category.as_ref().map_or(true, |cat| &c.category == cat))
+        .filter(|c| category.as_ref().is_none_or(|cat| &c.category == cat))
         .collect();
 
     if json {
@@ -736,16 +736,21 @@ fn cmd_open(config: &TronConfig) -> Result<()> {
 
 fn cmd_mcp(args: Vec<String>) -> Result<()> {
     let home = dirs::home_dir().context("Could not find home directory")?;
-    let mcp_bin = home.join("bin").join("mcp.exe");
+    let mcp_bin = home
+        .join("bin")
+        .join(format!("mcp{}", std::env::consts::EXE_SUFFIX));
 
-    if !mcp_bin.exists() {
-        anyhow::bail!(
-            "mcp CLI not found at {}. Build and install it first.",
-            mcp_bin.display()
-        );
-    }
+    // Prefer the ~/bin copy, otherwise whatever `mcp` is on PATH
+    let program = if mcp_bin.exists() {
+        mcp_bin.into_os_string()
+    } else {
+        "mcp".into()
+    };
 
-    let status = Command::new(&mcp_bin).args(&args).status()?;
+    let status = Command::new(&program)
+        .args(&args)
+        .status()
+        .context("mcp CLI not found. Build and install it first.")?;
 
     std::process::exit(status.code().unwrap_or(1));
 }